@@ -0,0 +1,227 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::options::{
+    CommandAction, CommandNewOptions, CommandRmOptions, CommandShowOptions, ContextOptions,
+    RenderOptions,
+};
+use super::render::render;
+use super::table::print_table;
+use crate::constants::dir::COMMANDS_DIR;
+use crate::constants::features::COMMANDS_FEATURE;
+use crate::core::deploy::apply_plan;
+use crate::core::plan::DeployPlan;
+use crate::core::roots::AllowedRoots;
+use crate::core::scope::DeployScope;
+use crate::schema::builder::command::CommandBuilder;
+use crate::schema::command::{Command, NAMESPACE_SEPARATOR};
+use crate::schema::config::{AppConfig, CacheConfig};
+use crate::templates::helpers::get_templater;
+use crate::utils::did_you_mean;
+use crate::utils::path::{get_application_dir, get_workspace_dir};
+
+pub(super) fn run_command_action(action: CommandAction) -> Result<()> {
+    match action {
+        CommandAction::New(opts) => new_command(opts),
+        CommandAction::List => list_commands(),
+        CommandAction::Show(opts) => show_command(opts),
+        CommandAction::Rm(opts) => remove_command(opts),
+    }
+}
+
+fn find_command(mut commands: Vec<Command>, name: &str) -> Result<Command> {
+    let names: Vec<String> = commands.iter().map(Command::qualified_name).collect();
+
+    if let Some(index) = names.iter().position(|candidate| candidate == name) {
+        return Ok(commands.swap_remove(index));
+    }
+
+    match did_you_mean(name, names.iter().map(String::as_str)) {
+        Some(suggestion) => bail!("Unknown command '{}', did you mean '{}'?", name, suggestion),
+        None => bail!("Unknown command '{}'", name),
+    }
+}
+
+fn command_path(name: &str) -> Result<PathBuf> {
+    let segments: Vec<&str> = name.split(NAMESPACE_SEPARATOR).collect();
+
+    if segments
+        .iter()
+        .any(|segment| segment.is_empty() || *segment == "." || *segment == "..")
+    {
+        bail!("'{}' is not a valid command name", name);
+    }
+
+    // Not required to exist yet, e.g. after `init --no-command`; `new` creates it.
+    let mut path = get_application_dir()?.join(COMMANDS_DIR);
+    path.extend(&segments);
+    path.set_extension("md");
+
+    Ok(path)
+}
+
+fn open_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| if cfg!(windows) { "notepad" } else { "vi" }.into());
+
+    let mut parts = editor.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| anyhow!("editor command is empty"))?;
+
+    let status = process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .context(format!("failed to start {}", program))?;
+
+    if !status.success() {
+        bail!("{} exited with {}", program, status);
+    }
+
+    Ok(())
+}
+
+fn check_available(name: &str, path: &Path) -> Result<()> {
+    if path.exists() {
+        bail!("command '{}' already exists at {}", name, path.display());
+    }
+
+    if !get_application_dir()?.join(COMMANDS_DIR).is_dir() {
+        return Ok(());
+    }
+
+    // A broken commands directory could hide a command defined under the same name.
+    let commands = Command::from_application().context("failed to load commands")?;
+
+    if let Some(existing) = commands
+        .iter()
+        .find(|command| command.qualified_name() == name)
+    {
+        let defined_in = existing.source.as_deref().unwrap_or(path);
+        bail!(
+            "command '{}' is already defined in {}",
+            name,
+            defined_in.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn new_command(opts: CommandNewOptions) -> Result<()> {
+    let path = command_path(&opts.name).context("resolve command path")?;
+
+    check_available(&opts.name, &path).context("create command")?;
+
+    let leaf = opts
+        .name
+        .rsplit(NAMESPACE_SEPARATOR)
+        .next()
+        .unwrap_or(&opts.name);
+    let command = CommandBuilder::new(leaf, &opts.description)
+        .add_content(&format!("# {}\n", leaf))
+        .build();
+
+    let content = command.to_markdown().context("serialize command")?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context(format!("create {}", parent.display()))?;
+    }
+    fs::write(&path, content).context(format!("write {}", path.display()))?;
+    log::info!("Created {}", path.display());
+
+    if opts.editor {
+        open_editor(&path).context("open editor")?;
+    }
+
+    Ok(())
+}
+
+fn list_commands() -> Result<()> {
+    let config = AppConfig::from_application(get_templater()).context("load application config")?;
+    let commands = Command::from_application().context("load commands")?;
+    let providers = config.get_feature_providers(COMMANDS_FEATURE);
+
+    let rows: Vec<[String; 4]> = commands
+        .iter()
+        .map(|command| {
+            // A provider only receives the commands its settings can express.
+            let receivers: Vec<&str> = providers
+                .iter()
+                .filter(|(_, name, settings)| {
+                    command
+                        .to_template_value(Some((name.as_str(), settings)))
+                        .is_ok()
+                })
+                .map(|(_, name, _)| name.as_str())
+                .collect();
+
+            [
+                command.qualified_name(),
+                command.namespace.join(NAMESPACE_SEPARATOR),
+                command.metadata.description.clone(),
+                receivers.join(", "),
+            ]
+        })
+        .collect();
+
+    print_table(["NAME", "NAMESPACE", "DESCRIPTION", "PROVIDERS"], &rows);
+
+    Ok(())
+}
+
+fn show_command(opts: CommandShowOptions) -> Result<()> {
+    let commands = Command::from_application().context("load commands")?;
+    let command = find_command(commands, &opts.name).context("find command")?;
+
+    if opts.provider.is_none() {
+        let source = command
+            .source
+            .ok_or_else(|| anyhow!("command '{}' has no source file", opts.name))
+            .context("find command")?;
+        let content = fs::read_to_string(&source).context(format!("read {}", source.display()))?;
+
+        print!("{}", content);
+        return Ok(());
+    }
+
+    render(RenderOptions {
+        template: None,
+        context: ContextOptions {
+            provider: opts.provider,
+            feature: Some(COMMANDS_FEATURE.to_string()),
+            command: Some(opts.name),
+        },
+    })
+}
+
+fn remove_command(opts: CommandRmOptions) -> Result<()> {
+    let commands = Command::from_application().context("load commands")?;
+    let command = find_command(commands, &opts.name).context("find command")?;
+
+    if let Some(source) = &command.source {
+        fs::remove_file(source).context(format!("remove {}", source.display()))?;
+        log::info!("Removed {}", source.display());
+    }
+
+    // An empty plan scoped to the command prunes everything previously deployed from it.
+    let scope = DeployScope {
+        commands: [command.qualified_name()].into_iter().collect(),
+        ..Default::default()
+    };
+    let config = AppConfig::from_application(get_templater()).context("load application config")?;
+    let roots = AllowedRoots::from_config(&config, &get_workspace_dir()?, false)
+        .context("check output paths")?;
+    let mut cache = CacheConfig::from_application().context("load deploy cache")?;
+
+    apply_plan(&DeployPlan::default(), &mut cache, &scope, &roots)
+        .context("remove deployed files")?;
+    cache.save().context("save deploy cache")?;
+
+    Ok(())
+}
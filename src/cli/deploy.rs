@@ -1,40 +1,32 @@
 use anyhow::{Context, Result};
-use serde_yaml::to_value;
 
-use crate::constants::features::{COMMANDS_FEATURE, INSTRUCTION_FEATURE, MCP_FEATURE};
-use crate::schema::command::Command;
-use crate::schema::config::{AppConfig, TomlConfig};
-use crate::schema::instruction::Instruction;
-use crate::schema::mcp::McpConfig;
+use super::options::DeployOptions;
+use crate::core::deployer::Deployer;
+use crate::core::scope::DeployScope;
+use crate::schema::config::AppConfig;
 use crate::templates::helpers::get_templater;
 
-pub(super) fn deploy() -> Result<()> {
-    let templater = get_templater();
-    let app_config =
-        AppConfig::from_application(templater).context("Failed to load application config")?;
-    let variables =
-        to_value(app_config.variables.clone()).context("Failed to extract variables")?;
-
-    if app_config.has_feature(COMMANDS_FEATURE) {
-        let commands = Command::from_application().context("Failed to load commands")?;
-        let providers_with_config = app_config.get_feature_providers(COMMANDS_FEATURE);
-    }
-
-    if app_config.has_feature(MCP_FEATURE) {
-        let mcp = McpConfig::from_application().context("Failed to load mcp config")?;
-        let providers_with_config = app_config.get_feature_providers(MCP_FEATURE);
+impl From<DeployOptions> for DeployScope {
+    fn from(opts: DeployOptions) -> Self {
+        Self {
+            providers: opts.providers.into_iter().collect(),
+            skip_providers: opts.skip_providers.into_iter().collect(),
+            features: opts.features.into_iter().collect(),
+            commands: opts.commands.into_iter().collect(),
+        }
     }
+}
 
-    if app_config.has_feature(INSTRUCTION_FEATURE) {
-        let instruction = Instruction::from_application().context("Failed to load instruction")?;
-        let providers_with_config = app_config.get_feature_providers(INSTRUCTION_FEATURE);
-    }
+pub(super) fn deploy(opts: DeployOptions) -> Result<()> {
+    let templater = get_templater();
+    let app_config = AppConfig::from_application(templater).context("load application config")?;
 
-    println!("# Application Config: \n\n{}\n\n", app_config.to_toml()?);
-    println!(
-        "# Cache Config: \n\n{}\n\n",
-        app_config.to_cache().to_toml()?
-    );
+    let deployer = Deployer::new(templater, app_config)
+        .discover_tools(opts.discover_tools)
+        .allow_outside(opts.allow_outside)
+        .scope(DeployScope::from(opts));
 
-    todo!()
+    deployer.sync_packages()?;
+    let plan = deployer.plan()?;
+    deployer.apply(&plan)
 }
@@ -1,12 +1,30 @@
 use super::options::InitOptions;
+use super::prompt;
+use super::starter::{init_from_starter, update_from_starter};
+use crate::config::catalog::builtin_provider_names;
+use crate::config::detect::{ImportSource, detect_agents, import_sources, read_mcp_servers};
 use crate::config::dummy;
 use crate::constants::{
-    dir::{COMMANDS_DIR, ROOT_DIR},
-    file::{INSTRUCTIONS_FILE, MCP_FILE},
+    dir::{COMMANDS_DIR, INSTRUCTIONS_DIR, ROOT_DIR},
+    file::MCP_FILE,
 };
-use anyhow::{Context, Result};
-use log;
-use std::{fs, path::Path};
+use crate::schema::builder::mcp::McpConfigBuilder;
+use crate::schema::common::Target;
+use crate::utils::did_you_mean;
+use crate::utils::path::get_home_dir;
+use anyhow::{Context, Result, bail};
+use std::{env, fs, path::Path};
+
+/// Targets of a workspace when no agent could be detected.
+const DEFAULT_TARGETS: [(Target, &str); 2] = [(Target::Cli, "copilot"), (Target::Ide, "windsurf")];
+
+/// What `init` sets the workspace up for.
+pub(crate) struct InitSelection {
+    pub targets: Vec<(Target, String)>,
+    pub commands: bool,
+    pub instructions: bool,
+    pub mcp: bool,
+}
 
 fn seed_dummy<F>(skip: bool, name: &str, path: &str, f: F) -> Result<()>
 where
@@ -21,43 +39,268 @@ where
     Ok(())
 }
 
+/// Looks up the built-in providers named in `--targets`.
+fn resolve_targets(names: &[String]) -> Result<Vec<(Target, String)>> {
+    let providers = builtin_provider_names();
+
+    names
+        .iter()
+        .map(|name| {
+            let name = name.trim();
+
+            if let Some((target, _)) = providers.iter().find(|(_, known)| *known == name) {
+                return Ok((*target, name.to_string()));
+            }
+
+            match did_you_mean(name, providers.iter().map(|(_, known)| *known)) {
+                Some(suggestion) => {
+                    bail!("Unknown agent '{}', did you mean '{}'?", name, suggestion)
+                }
+                None => bail!("Unknown agent '{}'", name),
+            }
+        })
+        .collect()
+}
+
+/// Picks targets and features from the flags, asking the user on a terminal.
+fn select(opts: &InitOptions, interactive: bool, home_dir: &Path) -> Result<InitSelection> {
+    let features = [!opts.no_command, !opts.no_instruction, !opts.no_mcp];
+
+    if !opts.targets.is_empty() {
+        let [commands, instructions, mcp] = features;
+        return Ok(InitSelection {
+            targets: resolve_targets(&opts.targets)?,
+            commands,
+            instructions,
+            mcp,
+        });
+    }
+
+    let detected = detect_agents(home_dir);
+
+    for agent in detected.iter().filter(|agent| agent.target.is_none()) {
+        log::info!(
+            "Found {} at {}, but there is no built-in provider for it yet",
+            agent.name,
+            agent.evidence
+        );
+    }
+
+    let supported: Vec<(Target, String)> = detected
+        .iter()
+        .filter_map(|agent| Some((agent.target?, agent.name.to_string())))
+        .collect();
+
+    if !interactive {
+        let targets = if supported.is_empty() {
+            log::info!("No agents detected, targeting the defaults");
+            DEFAULT_TARGETS
+                .iter()
+                .map(|(target, name)| (*target, name.to_string()))
+                .collect()
+        } else {
+            supported
+        };
+
+        let [commands, instructions, mcp] = features;
+        return Ok(InitSelection {
+            targets,
+            commands,
+            instructions,
+            mcp,
+        });
+    }
+
+    let providers = builtin_provider_names();
+    let labels: Vec<String> = providers
+        .iter()
+        .map(
+            |(target, name)| match detected.iter().find(|agent| agent.name == *name) {
+                Some(agent) => format!("{} ({}), found {}", name, target, agent.evidence),
+                None => format!("{} ({})", name, target),
+            },
+        )
+        .collect();
+    let checked = providers
+        .iter()
+        .map(|(_, name)| supported.iter().any(|(_, found)| found == name))
+        .collect();
+
+    let targets: Vec<_> =
+        prompt::multi_select("Which agents should dotagents deploy to?", &labels, checked)?
+            .into_iter()
+            .zip(&providers)
+            .filter(|(checked, _)| *checked)
+            .map(|(_, (target, name))| (*target, name.to_string()))
+            .collect();
+
+    if targets.is_empty() {
+        log::warn!("No agents selected, add them to the targets of config.toml later");
+    }
+
+    let labels = ["commands", "instructions", "mcp"].map(String::from);
+    let [commands, instructions, mcp] = prompt::multi_select(
+        "Which features should be managed?",
+        &labels,
+        features.to_vec(),
+    )?[..] else {
+        bail!("expected one answer per feature");
+    };
+
+    Ok(InitSelection {
+        targets,
+        commands,
+        instructions,
+        mcp,
+    })
+}
+
+/// Existing configuration of the selected agents to bring in, confirmed one by one on a
+/// terminal and taken as a whole with `--yes`.
+fn select_imports(
+    selection: &InitSelection,
+    opts: &InitOptions,
+    interactive: bool,
+    home_dir: &Path,
+) -> Result<Vec<ImportSource>> {
+    if !interactive && !opts.yes {
+        return Ok(Vec::new());
+    }
+
+    let agents: Vec<String> = selection
+        .targets
+        .iter()
+        .map(|(_, name)| name.clone())
+        .collect();
+    // The workspace is the directory being initialized, it has no `.dotagents` yet.
+    let workspace_dir = env::current_dir().context("failed to get current directory")?;
+    let sources = import_sources(&agents, home_dir, &workspace_dir)
+        .into_iter()
+        .filter(|source| match source {
+            ImportSource::Mcp { .. } => selection.mcp,
+            ImportSource::Instructions { .. } => selection.instructions,
+        });
+
+    let mut imports = Vec::new();
+
+    for source in sources {
+        if !interactive || prompt::confirm(&format!("Import {}?", source.describe()), true)? {
+            imports.push(source);
+        }
+    }
+
+    Ok(imports)
+}
+
+/// Writes the imported MCP servers and instruction files, returning which of the two were
+/// seeded so dummies aren't written on top.
+fn import(sources: &[ImportSource]) -> Result<(bool, bool)> {
+    let mut servers = McpConfigBuilder::new().build();
+    let mut has_mcp = false;
+    let mut has_instructions = false;
+
+    for source in sources {
+        match source {
+            ImportSource::Mcp { path, .. } => {
+                for (name, server) in read_mcp_servers(path)?.servers {
+                    if servers.servers.contains_key(&name) {
+                        log::warn!(
+                            "Skipping server {} of {}, it was already imported",
+                            name,
+                            path.display()
+                        );
+                        continue;
+                    }
+                    servers.servers.insert(name, server);
+                }
+                has_mcp = true;
+            }
+            ImportSource::Instructions { path } => {
+                let content = fs::read_to_string(path)
+                    .context(format!("failed to read {}", path.display()))?;
+                let name = path
+                    .file_stem()
+                    .map(|stem| {
+                        stem.to_string_lossy()
+                            .trim_start_matches('.')
+                            .to_lowercase()
+                    })
+                    .unwrap_or_default();
+
+                dummy::set_imported_instruction(&name, &content)?;
+                has_instructions = true;
+            }
+        }
+
+        log::info!("Imported {}", source.describe());
+    }
+
+    if has_mcp {
+        dummy::set_imported_mcp(&servers)?;
+    }
+
+    Ok((has_mcp, has_instructions))
+}
+
 pub(super) fn initialize_agents_dir(opts: InitOptions) -> Result<()> {
     let main_dir = Path::new(ROOT_DIR);
 
-    if main_dir
+    let exists = main_dir
         .try_exists()
-        .context("failed to check if .dotagents directory exists")?
-    {
-        if !opts.force {
-            anyhow::bail!(format!(
-                "Configuration already exists: {}",
-                main_dir.display()
-            ));
-        } else {
-            log::warn!("Overwriting existing configuration");
-            fs::remove_dir_all(main_dir).context("failed to remove .dotagents directory")?;
-        }
+        .context("failed to check if .dotagents directory exists")?;
+
+    if opts.update {
+        return update_from_starter(&opts).context("update from starter");
+    }
+
+    if exists && !opts.force {
+        anyhow::bail!(format!(
+            "Configuration already exists: {}",
+            main_dir.display()
+        ));
+    }
+
+    if let Some(source) = &opts.from {
+        return init_from_starter(source, &opts, main_dir).context("initialize from starter");
+    }
+
+    let home_dir = get_home_dir()?;
+    let interactive = !opts.yes && opts.targets.is_empty() && prompt::is_interactive();
+    let selection = select(&opts, interactive, &home_dir).context("select targets")?;
+    let imports = select_imports(&selection, &opts, interactive, &home_dir)
+        .context("select configuration to import")?;
+
+    // Only replaced once the user is done choosing, so a cancelled wizard keeps it.
+    if exists {
+        log::warn!("Overwriting existing configuration");
+        fs::remove_dir_all(main_dir).context("failed to remove .dotagents directory")?;
     }
 
     fs::create_dir(main_dir).context("failed to create .dotagents directory")?;
 
+    let (has_mcp, has_instructions) = import(&imports).context("import existing configuration")?;
+
     seed_dummy(
-        opts.no_command,
+        !selection.commands,
         "directory",
         COMMANDS_DIR,
         dummy::set_dummy_command,
     )?;
 
-    seed_dummy(
-        opts.no_instruction,
-        "file",
-        INSTRUCTIONS_FILE,
-        dummy::set_dummy_instructions,
-    )?;
+    if !has_instructions {
+        seed_dummy(
+            !selection.instructions,
+            "directory",
+            INSTRUCTIONS_DIR,
+            dummy::set_dummy_instructions,
+        )?;
+    }
 
-    seed_dummy(opts.no_mcp, "file", MCP_FILE, dummy::set_dummy_mcp)?;
+    if !has_mcp {
+        seed_dummy(!selection.mcp, "file", MCP_FILE, dummy::set_dummy_mcp)?;
+    }
 
-    let set_dummy_config = || dummy::set_dummy_config(opts);
+    let set_dummy_config = || dummy::set_dummy_config(&selection);
 
     seed_dummy(false, "", "", set_dummy_config)?;
 
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use super::options::{McpAction, McpAddOptions, McpCheckOptions, McpServerOptions};
+use super::table::print_table;
+use crate::constants::features::MCP_FEATURE;
+use crate::core::handshake::{HandshakeReport, check_servers_parallel, expand_env};
+use crate::schema::builder::mcp::McpConfigBuilder;
+use crate::schema::config::AppConfig;
+use crate::schema::mcp::McpConfig;
+use crate::schema::variable::resolve_variables;
+use crate::templates::helpers::{RenderType, get_templater};
+use crate::utils::did_you_mean;
+use crate::utils::fs::write_atomic;
+use crate::utils::jsonc::JsoncDocument;
+use crate::utils::path::{get_mcp_file, get_workspace_dir};
+
+const SERVERS: &str = "servers";
+
+pub(super) fn run_mcp_action(action: McpAction) -> Result<()> {
+    match action {
+        McpAction::Add(opts) => add_server(opts),
+        McpAction::Remove(opts) => remove_server(opts),
+        McpAction::List => list_servers(),
+        McpAction::Enable(opts) => toggle_server(opts, true),
+        McpAction::Disable(opts) => toggle_server(opts, false),
+        McpAction::Check(opts) => check_servers(opts),
+    }
+}
+
+fn load_document() -> Result<(PathBuf, JsoncDocument)> {
+    let path = get_mcp_file()?;
+    let content =
+        fs::read_to_string(&path).context(format!("failed to read {}", path.display()))?;
+    let document =
+        JsoncDocument::parse(content).context(format!("failed to parse {}", path.display()))?;
+
+    Ok((path, document))
+}
+
+/// Writes the document back after checking it still holds a valid MCP config.
+fn save_document(path: &Path, document: &JsoncDocument) -> Result<()> {
+    McpConfig::from_json(document.text()).context("edited MCP config is invalid")?;
+    write_atomic(path, document.text().as_bytes())
+}
+
+fn check_server_name(document: &JsoncDocument, name: &str) -> Result<()> {
+    let names = document.keys(&[SERVERS])?;
+
+    if names.iter().any(|candidate| candidate == name) {
+        return Ok(());
+    }
+
+    match did_you_mean(name, names.iter().map(String::as_str)) {
+        Some(suggestion) => bail!("Unknown server '{}', did you mean '{}'?", name, suggestion),
+        None => bail!("Unknown server '{}'", name),
+    }
+}
+
+fn add_server(opts: McpAddOptions) -> Result<()> {
+    let (path, mut document) = load_document().context("load mcp config")?;
+
+    if !opts.force && document.keys(&[SERVERS])?.contains(&opts.name) {
+        return Err(anyhow!(
+            "server '{}' already exists, pass --force to replace it",
+            opts.name
+        ))
+        .context("add server");
+    }
+
+    let builder = McpConfigBuilder::new();
+    let builder = match (&opts.command, &opts.url) {
+        (Some(command), _) => builder.add_stdio_server(
+            &opts.name,
+            command,
+            opts.args,
+            opts.cwd.as_deref(),
+            (!opts.env.is_empty()).then(|| opts.env.into_iter().collect::<HashMap<_, _>>()),
+            None,
+        ),
+        (None, Some(url)) => builder.add_http_server(
+            &opts.name,
+            url,
+            (!opts.headers.is_empty()).then(|| opts.headers.into_iter().collect::<HashMap<_, _>>()),
+            None,
+        ),
+        (None, None) => bail!("pass either --command or --url"),
+    };
+
+    let server = builder
+        .build()
+        .servers
+        .remove(&opts.name)
+        .ok_or_else(|| anyhow!("server '{}' was not built", opts.name))
+        .context("add server")?;
+
+    document
+        .set(&[SERVERS], &opts.name, &server)
+        .context("add server")?;
+    save_document(&path, &document).context("save mcp config")?;
+    log::info!("Added {} server {}", server.transport(), opts.name);
+
+    Ok(())
+}
+
+fn remove_server(opts: McpServerOptions) -> Result<()> {
+    let (path, mut document) = load_document().context("load mcp config")?;
+
+    check_server_name(&document, &opts.name).context("remove server")?;
+    document
+        .remove(&[SERVERS], &opts.name)
+        .context("remove server")?;
+    save_document(&path, &document).context("save mcp config")?;
+    log::info!("Removed server {}", opts.name);
+
+    Ok(())
+}
+
+fn toggle_server(opts: McpServerOptions, enabled: bool) -> Result<()> {
+    let (path, mut document) = load_document().context("load mcp config")?;
+    check_server_name(&document, &opts.name).context("select server")?;
+
+    let server_path = [SERVERS, opts.name.as_str()];
+    let result = if enabled {
+        document.remove(&server_path, "disabled").map(|_| ())
+    } else {
+        document.set(&server_path, "disabled", &true)
+    };
+
+    result.context(format!("update server {}", opts.name))?;
+    save_document(&path, &document).context("save mcp config")?;
+
+    Ok(())
+}
+
+fn list_servers() -> Result<()> {
+    let config = AppConfig::from_application(get_templater()).context("load application config")?;
+    let (_, document) = load_document().context("load mcp config")?;
+    let mcp = McpConfig::from_json(document.text()).context("load mcp config")?;
+
+    let providers: Vec<String> = config
+        .get_feature_providers(MCP_FEATURE)
+        .into_iter()
+        .map(|(_, name, _)| name)
+        .collect();
+
+    let rows: Vec<[String; 5]> = document
+        .keys(&[SERVERS])?
+        .into_iter()
+        .filter_map(|name| {
+            let server = mcp.servers.get(&name)?;
+
+            // Disabled servers are either left out or switched off by every agent.
+            let (status, receivers) = if server.is_disabled() {
+                ("disabled", String::new())
+            } else {
+                ("enabled", providers.join(", "))
+            };
+
+            Some([
+                name,
+                server.transport().to_string(),
+                server.endpoint(),
+                status.to_string(),
+                receivers,
+            ])
+        })
+        .collect();
+
+    print_table(
+        ["NAME", "TRANSPORT", "ENDPOINT", "STATUS", "PROVIDERS"],
+        &rows,
+    );
+
+    Ok(())
+}
+
+fn describe_report(report: &HandshakeReport) -> String {
+    let tools = match report.tools.is_empty() {
+        true => "no tools".to_string(),
+        false => format!("tools: {}", report.tools.join(", ")),
+    };
+
+    format!(
+        "protocol {}, {} {}, {}",
+        report.protocol_version, report.server_name, report.server_version, tools
+    )
+}
+
+fn check_servers(opts: McpCheckOptions) -> Result<()> {
+    let templater = get_templater();
+    let config = AppConfig::from_application(templater).context("load application config")?;
+    let (_, document) = load_document().context("load mcp config")?;
+    let mcp = McpConfig::from_json(document.text()).context("load mcp config")?;
+
+    let names: Vec<String> = match &opts.name {
+        Some(name) => {
+            check_server_name(&document, name).context("select server")?;
+            vec![name.clone()]
+        }
+        None => document
+            .keys(&[SERVERS])?
+            .into_iter()
+            .filter(|name| mcp.servers.get(name).is_some_and(|s| !s.is_disabled()))
+            .collect(),
+    };
+
+    let workspace_dir = get_workspace_dir()?;
+    let variables = resolve_variables(config.variables.as_ref(), &workspace_dir)
+        .context("resolve variables")?;
+    let resolve = |value: &str| {
+        let rendered = templater
+            .render_template(RenderType::Content(value.to_string()), Some(&variables))
+            .context(format!("failed to render '{}'", value))?;
+        expand_env(&rendered)
+    };
+    let timeout = Duration::from_secs(opts.timeout);
+
+    let servers: Vec<_> = names
+        .iter()
+        .filter_map(|name| Some((name.as_str(), mcp.servers.get(name)?)))
+        .collect();
+    let results = check_servers_parallel(&servers, &resolve, &workspace_dir, timeout);
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    let rows: Vec<[String; 4]> = results
+        .iter()
+        .map(|(name, result)| {
+            let transport = mcp
+                .servers
+                .get(*name)
+                .map_or("", |server| server.transport());
+            let (status, details) = match result {
+                Ok(report) => ("ok", describe_report(report)),
+                Err(e) => ("failed", format!("{:#}", e)),
+            };
+            [
+                name.to_string(),
+                transport.to_string(),
+                status.to_string(),
+                details,
+            ]
+        })
+        .collect();
+
+    print_table(["NAME", "TRANSPORT", "STATUS", "DETAILS"], &rows);
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} servers failed the handshake",
+            failed,
+            results.len()
+        ))
+        .context("check mcp servers");
+    }
+
+    Ok(())
+}
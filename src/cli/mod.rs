@@ -1,8 +1,18 @@
+mod command;
 mod completions;
 mod deploy;
 mod init;
+mod mcp;
 mod options;
+mod packages;
+mod prompt;
+mod render;
+mod restore;
 mod runner;
+mod starter;
+mod table;
+mod vars;
 
-pub(crate) use options::{InitOptions, get_options};
+pub(crate) use init::InitSelection;
+pub(crate) use options::get_options;
 pub(crate) use runner::run;
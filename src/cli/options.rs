@@ -1,7 +1,9 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
 use std::path::PathBuf;
 
+use crate::constants::features::FEATURES;
+
 #[derive(Parser, Default)]
 #[clap(author, version, about, long_about=None)]
 pub(crate) struct Options {
@@ -35,7 +37,32 @@ pub(crate) enum Action {
         to: Option<PathBuf>,
     },
 
-    Deploy,
+    /// Render the templates of every targeted provider and write them to their targets.
+    Deploy(DeployOptions),
+
+    /// Print the template context along with the source of every value.
+    Vars(VarsOptions),
+
+    /// Render a single template to stdout using the context a deploy would use.
+    Render(RenderOptions),
+
+    /// Create, inspect and remove commands.
+    #[clap(subcommand)]
+    Command(CommandAction),
+
+    /// Add, remove and toggle MCP servers in mcp.jsonc.
+    #[clap(subcommand)]
+    Mcp(McpAction),
+
+    /// Put back files a deploy replaced, from the backups taken before overwriting them.
+    Restore(RestoreOptions),
+
+    /// Move git packages to the latest commit of their `rev` and record it in the lockfile.
+    Update(UpdateOptions),
+
+    /// Check out and lock the packages of config.toml.
+    #[clap(subcommand)]
+    Packages(PackagesAction),
 }
 
 #[derive(Args)]
@@ -58,6 +85,261 @@ pub(crate) struct InitOptions {
     /// Force overwriting existing configuration.
     #[clap(long, short, default_value_t = cfg!(debug_assertions))]
     pub force: bool,
+
+    /// Agents to target, e.g. `windsurf,copilot`. Skips detecting the installed agents.
+    #[clap(long, value_name = "NAME", value_delimiter = ',')]
+    pub targets: Vec<String>,
+
+    /// Don't ask anything: target the detected agents and import their existing
+    /// configuration.
+    #[clap(long, short)]
+    pub yes: bool,
+
+    /// Start from a starter `.dotagents` tree in a directory or git repository instead of
+    /// the built-in examples.
+    #[clap(long, value_name = "PATH_OR_URL", conflicts_with_all = ["targets", "update"])]
+    pub from: Option<String>,
+
+    /// Value of a starter placeholder. Can be repeated.
+    #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub vars: Vec<(String, String)>,
+
+    /// Pull the changes of the starter the workspace was created from, keeping local edits.
+    #[clap(long)]
+    pub update: bool,
+}
+
+#[derive(Args, Default)]
+pub(crate) struct DeployOptions {
+    /// Only deploy to the given provider. Can be repeated.
+    #[clap(long = "provider", value_name = "NAME")]
+    pub providers: Vec<String>,
+
+    /// Skip deploying to the given provider. Can be repeated.
+    #[clap(long = "skip-provider", value_name = "NAME")]
+    pub skip_providers: Vec<String>,
+
+    /// Only deploy the given feature. Can be repeated.
+    #[clap(long = "feature", value_name = "FEATURE", value_parser = FEATURES)]
+    pub features: Vec<String>,
+
+    /// Only deploy the given command. Implies `--feature commands`. Can be repeated.
+    #[clap(long = "command", value_name = "NAME")]
+    pub commands: Vec<String>,
+
+    /// Start MCP servers with tool filters to expand and check their tool patterns.
+    #[clap(long)]
+    pub discover_tools: bool,
+
+    /// Write outputs even when they lead outside the workspace, home, config directory and
+    /// configured `allowed-roots`.
+    #[clap(long)]
+    pub allow_outside: bool,
+}
+
+#[derive(Args, Default)]
+pub(crate) struct ContextOptions {
+    /// Include the variables of the given provider. Requires `--feature`.
+    #[clap(long, value_name = "NAME")]
+    pub provider: Option<String>,
+
+    /// Include the data of the given feature.
+    #[clap(long, value_name = "FEATURE", value_parser = FEATURES)]
+    pub feature: Option<String>,
+
+    /// Include the frontmatter of the given command. Implies `--feature commands`.
+    #[clap(long, value_name = "NAME")]
+    pub command: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub(crate) enum OutputFormat {
+    #[default]
+    Json,
+    Toml,
+}
+
+#[derive(Args, Default)]
+pub(crate) struct VarsOptions {
+    #[clap(flatten)]
+    pub context: ContextOptions,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args, Default)]
+pub(crate) struct RenderOptions {
+    /// Template to render, relative to the application directory. Defaults to the template of
+    /// the selected provider feature.
+    pub template: Option<String>,
+
+    #[clap(flatten)]
+    pub context: ContextOptions,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum CommandAction {
+    /// Scaffold a new command file.
+    New(CommandNewOptions),
+
+    /// List every command along with the providers it is deployed to.
+    List,
+
+    /// Print a command, rendered for a provider when one is given.
+    Show(CommandShowOptions),
+
+    /// Delete a command and the files deployed from it.
+    Rm(CommandRmOptions),
+}
+
+#[derive(Args)]
+pub(crate) struct CommandNewOptions {
+    /// Name of the command. Use `/` to place it in a namespace, e.g. `git/commit`.
+    pub name: String,
+
+    /// Description shown by agents when picking the command.
+    #[clap(long, short, default_value = "")]
+    pub description: String,
+
+    /// Open the new command in `$VISUAL` or `$EDITOR`.
+    #[clap(long, short)]
+    pub editor: bool,
+}
+
+#[derive(Args)]
+pub(crate) struct CommandShowOptions {
+    /// Name of the command, including its namespace.
+    pub name: String,
+
+    /// Render the command with the template of the given provider.
+    #[clap(long, value_name = "NAME")]
+    pub provider: Option<String>,
+}
+
+#[derive(Args)]
+pub(crate) struct CommandRmOptions {
+    /// Name of the command, including its namespace.
+    pub name: String,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum McpAction {
+    /// Add a stdio server with `--command` or an http server with `--url`.
+    Add(McpAddOptions),
+
+    /// Remove a server.
+    #[clap(alias = "rm")]
+    Remove(McpServerOptions),
+
+    /// List every server along with the providers it is deployed to.
+    List,
+
+    /// Enable a disabled server.
+    Enable(McpServerOptions),
+
+    /// Disable a server without removing it.
+    Disable(McpServerOptions),
+
+    /// Start servers and perform the MCP handshake to check they work.
+    Check(McpCheckOptions),
+}
+
+#[derive(Args)]
+pub(crate) struct McpAddOptions {
+    /// Name of the server.
+    pub name: String,
+
+    /// Command that starts a stdio server.
+    #[clap(long, conflicts_with = "url", required_unless_present = "url")]
+    pub command: Option<String>,
+
+    /// Argument passed to the command. Can be repeated.
+    #[clap(
+        long = "arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        requires = "command"
+    )]
+    pub args: Vec<String>,
+
+    /// Environment variable of the command as `KEY=VALUE`. Can be repeated.
+    #[clap(long = "env", value_name = "KEY=VALUE", value_parser = parse_key_value, requires = "command")]
+    pub env: Vec<(String, String)>,
+
+    /// Working directory of the command.
+    #[clap(long, requires = "command")]
+    pub cwd: Option<String>,
+
+    /// URL of an http server.
+    #[clap(long)]
+    pub url: Option<String>,
+
+    /// Header sent to the http server as `KEY=VALUE`. Can be repeated.
+    #[clap(long = "header", value_name = "KEY=VALUE", value_parser = parse_key_value, requires = "url")]
+    pub headers: Vec<(String, String)>,
+
+    /// Replace the server if it already exists.
+    #[clap(long, short)]
+    pub force: bool,
+}
+
+#[derive(Args)]
+pub(crate) struct McpServerOptions {
+    /// Name of the server.
+    pub name: String,
+}
+
+#[derive(Args)]
+pub(crate) struct McpCheckOptions {
+    /// Only check the given server, even when it is disabled. Checks every enabled server
+    /// by default.
+    pub name: Option<String>,
+
+    /// Seconds to wait for each server before giving up.
+    #[clap(long, default_value_t = 10)]
+    pub timeout: u64,
+}
+
+#[derive(Args)]
+pub(crate) struct RestoreOptions {
+    /// List the available backups instead of restoring one.
+    #[clap(long, conflicts_with = "backup")]
+    pub list: bool,
+
+    /// Backup to restore, as shown by `--list`.
+    #[clap(required_unless_present = "list")]
+    pub backup: Option<String>,
+
+    /// Restore files even when they lead outside the workspace, home, config directory and
+    /// configured `allowed-roots`.
+    #[clap(long)]
+    pub allow_outside: bool,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum PackagesAction {
+    /// Check out git packages at the commits of the lockfile, locking those it doesn't list.
+    /// Deploys sync on their own, while `vars` and `render` only read the checkouts.
+    Sync,
+
+    /// Same as `dotagents update`.
+    Update(UpdateOptions),
+}
+
+#[derive(Args)]
+pub(crate) struct UpdateOptions {
+    /// Packages to update, every git package when omitted.
+    pub packages: Vec<String>,
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .filter(|(key, _)| !key.is_empty())
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))
 }
 
 pub fn get_options() -> Options {
@@ -0,0 +1,25 @@
+use anyhow::{Context, Result};
+
+use super::options::{PackagesAction, UpdateOptions};
+use crate::core::packages::{sync_packages, update_packages};
+use crate::schema::config::AppConfig;
+use crate::templates::helpers::get_templater;
+
+pub(super) fn run_packages_action(action: PackagesAction) -> Result<()> {
+    match action {
+        PackagesAction::Sync => sync(),
+        PackagesAction::Update(opts) => update(opts),
+    }
+}
+
+pub(super) fn update(opts: UpdateOptions) -> Result<()> {
+    let config = AppConfig::from_application(get_templater()).context("load application config")?;
+
+    update_packages(&config, &opts.packages).context("update packages")
+}
+
+fn sync() -> Result<()> {
+    let config = AppConfig::from_application(get_templater()).context("load application config")?;
+
+    sync_packages(&config).context("sync packages")
+}
@@ -0,0 +1,151 @@
+use std::io::{self, IsTerminal, Write};
+
+use anyhow::{Context, Result, bail};
+use crossterm::cursor::{Hide, MoveToColumn, MoveUp, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Stylize;
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{execute, queue};
+
+/// Whether the user can answer prompts.
+pub(super) fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Restores the terminal when the prompt ends, however it ends.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode().context("failed to enable raw mode")?;
+        execute!(io::stdout(), Hide).context("failed to hide the cursor")?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// A list of options toggled with space and confirmed with enter. Returns which options are
+/// checked, starting from `checked`.
+pub(super) fn multi_select(
+    question: &str,
+    options: &[String],
+    mut checked: Vec<bool>,
+) -> Result<Vec<bool>> {
+    if options.is_empty() {
+        return Ok(checked);
+    }
+
+    let mut stdout = io::stdout();
+    let mut cursor = 0;
+
+    println!(
+        "{} {}",
+        question.bold(),
+        "(↑/↓ to move, space to toggle, a for all, enter to confirm)".dim()
+    );
+
+    let _raw = RawMode::enable()?;
+    let draw = |stdout: &mut io::Stdout, cursor: usize, checked: &[bool]| -> Result<()> {
+        for (index, option) in options.iter().enumerate() {
+            let pointer = if index == cursor { ">" } else { " " };
+            let mark = if checked[index] { "[x]" } else { "[ ]" };
+            let line = format!("{} {} {}", pointer, mark, option);
+
+            queue!(stdout, Clear(ClearType::CurrentLine))?;
+            match index == cursor {
+                true => write!(stdout, "{}\r\n", line.cyan())?,
+                false => write!(stdout, "{}\r\n", line)?,
+            }
+        }
+
+        stdout.flush()?;
+        Ok(())
+    };
+
+    draw(&mut stdout, cursor, &checked)?;
+
+    loop {
+        let Event::Key(key) = event::read().context("failed to read key")? else {
+            continue;
+        };
+
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => cursor = cursor.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => cursor = (cursor + 1).min(options.len() - 1),
+            KeyCode::Char(' ') => checked[cursor] = !checked[cursor],
+            KeyCode::Char('a') => {
+                let all = checked.iter().all(|checked| *checked);
+                checked.iter_mut().for_each(|checked| *checked = !all);
+            }
+            KeyCode::Enter => return Ok(checked),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                bail!("cancelled")
+            }
+            KeyCode::Esc => bail!("cancelled"),
+            _ => continue,
+        }
+
+        queue!(stdout, MoveUp(options.len() as u16), MoveToColumn(0))?;
+        draw(&mut stdout, cursor, &checked)?;
+    }
+}
+
+/// A yes or no question answered on its own line, `default` being used for an empty answer.
+pub(super) fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "[Y/n]" } else { "[y/N]" };
+
+    loop {
+        print!("{} {} ", question.bold(), hint.dim());
+        io::stdout().flush()?;
+
+        match read_answer()?.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer y or n."),
+        }
+    }
+}
+
+/// A free-form answer on its own line, `default` being used for an empty answer.
+pub(super) fn input(question: &str, default: Option<&str>) -> Result<String> {
+    loop {
+        match default {
+            Some(default) => print!("{} {} ", question.bold(), format!("[{}]", default).dim()),
+            None => print!("{} ", question.bold()),
+        }
+        io::stdout().flush()?;
+
+        match (read_answer()?.as_str(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => println!("Please enter a value."),
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+/// Reads one line of stdin, trimmed. Stdin closing before the user answered cancels the
+/// prompt, as asking again would never get an answer either.
+fn read_answer() -> Result<String> {
+    let mut answer = String::new();
+    let read = io::stdin()
+        .read_line(&mut answer)
+        .context("failed to read answer")?;
+
+    if read == 0 {
+        println!();
+        bail!("cancelled");
+    }
+
+    Ok(answer.trim().to_string())
+}
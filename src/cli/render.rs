@@ -0,0 +1,24 @@
+use anyhow::{Context, Result, anyhow};
+
+use super::options::RenderOptions;
+use super::vars::load_context;
+use crate::templates::helpers::{RenderType, get_templater, load_template};
+
+pub(super) fn render(opts: RenderOptions) -> Result<()> {
+    let (context, provider_template) = load_context(opts.context)?;
+
+    let template = opts
+        .template
+        .or(provider_template)
+        .ok_or_else(|| anyhow!("pick a template or a provider with --provider and --feature"))
+        .context("select template")?;
+
+    let source = load_template(&template).context("load template")?;
+    let content = get_templater()
+        .render_template(RenderType::Content(source), Some(&context.merged()))
+        .context(format!("render {}", template))?;
+
+    print!("{}", content);
+
+    Ok(())
+}
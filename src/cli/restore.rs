@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+
+use super::options::RestoreOptions;
+use super::table::print_table;
+use crate::core::backup::{list_backups, restore_backup};
+use crate::core::roots::AllowedRoots;
+use crate::schema::config::{AppConfig, CacheConfig};
+use crate::templates::helpers::get_templater;
+use crate::utils::path::get_workspace_dir;
+
+pub(super) fn restore(opts: RestoreOptions) -> Result<()> {
+    let Some(backup) = opts.backup else {
+        return print_backups();
+    };
+
+    let config = AppConfig::from_application(get_templater()).context("load application config")?;
+    let roots = AllowedRoots::from_config(&config, &get_workspace_dir()?, opts.allow_outside)
+        .context("check restored paths")?;
+    let mut cache = CacheConfig::from_application().context("load deploy cache")?;
+
+    for path in restore_backup(&backup, &mut cache, &roots).context("restore backup")? {
+        log::info!("Restored {}", path);
+    }
+
+    cache.save().context("save deploy cache")?;
+
+    Ok(())
+}
+
+fn print_backups() -> Result<()> {
+    let rows: Vec<[String; 3]> = list_backups()
+        .context("list backups")?
+        .into_iter()
+        .map(|(id, manifest)| {
+            let files: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+            [id, manifest.created.clone(), files.join(", ")]
+        })
+        .collect();
+
+    print_table(["BACKUP", "CREATED", "FILES"], &rows);
+
+    Ok(())
+}
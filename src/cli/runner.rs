@@ -1,7 +1,13 @@
+use super::command::run_command_action;
 use super::completions::generate_cli_completions;
 use super::deploy::deploy;
 use super::init::initialize_agents_dir;
+use super::mcp::run_mcp_action;
 use super::options::{Action, Options};
+use super::packages::{run_packages_action, update};
+use super::render::render;
+use super::restore::restore;
+use super::vars::print_variables;
 use anyhow::Result;
 use clap::CommandFactory;
 
@@ -14,7 +20,14 @@ pub(crate) fn run(opts: Options) -> Result<bool> {
     match opts.action.unwrap_or_else(default_action) {
         Action::Init(opts) => initialize_agents_dir(opts),
         Action::GenCompletions { shell, to } => generate_cli_completions(shell, to),
-        Action::Deploy => deploy(),
+        Action::Deploy(opts) => deploy(opts),
+        Action::Vars(opts) => print_variables(opts),
+        Action::Render(opts) => render(opts),
+        Action::Command(action) => run_command_action(action),
+        Action::Mcp(action) => run_mcp_action(action),
+        Action::Restore(opts) => restore(opts),
+        Action::Update(opts) => update(opts),
+        Action::Packages(action) => run_packages_action(action),
     }?;
 
     Ok(true)
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use super::options::InitOptions;
+use super::prompt;
+use crate::config::dummy;
+use crate::constants::file::STARTER_ORIGIN_FILE;
+use crate::core::starter::{self, Starter};
+use crate::schema::starter::StarterOrigin;
+use crate::utils::path::{get_application_dir, get_starter_origin_file};
+
+/// Values for the placeholders of `starter`: `recorded` ones from a previous run, overridden
+/// by `--var`, with the rest asked for or taken from their defaults.
+fn collect_values(
+    starter: &Starter,
+    opts: &InitOptions,
+    recorded: BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>> {
+    let interactive = !opts.yes && prompt::is_interactive();
+    let mut values = recorded;
+    values.extend(opts.vars.iter().cloned());
+
+    let missing: Vec<_> = starter
+        .missing_variables(&values)
+        .into_iter()
+        .map(|(name, variable)| (name.to_string(), variable.clone()))
+        .collect();
+
+    for (name, variable) in missing {
+        let value = match (interactive, &variable.default) {
+            (true, default) => prompt::input(
+                variable.description.as_deref().unwrap_or(&name),
+                default.as_deref(),
+            )?,
+            (false, Some(default)) => default.clone(),
+            (false, None) => bail!(
+                "starter variable '{}' has no value, pass --var {}=VALUE",
+                name,
+                name
+            ),
+        };
+
+        values.insert(name, value);
+    }
+
+    Ok(values)
+}
+
+/// Fills `app_dir` with the files of the starter at `source`. An existing directory is only
+/// replaced once the starter was fetched and rendered.
+pub(super) fn init_from_starter(source: &str, opts: &InitOptions, app_dir: &Path) -> Result<()> {
+    let starter = Starter::fetch(source).context("failed to fetch starter")?;
+    let values =
+        collect_values(&starter, opts, BTreeMap::new()).context("failed to fill in starter")?;
+    let files = starter
+        .render(&values)
+        .context("failed to render starter")?;
+
+    if app_dir.exists() {
+        log::warn!("Overwriting existing configuration");
+        fs::remove_dir_all(app_dir).context("failed to remove .dotagents directory")?;
+    }
+
+    fs::create_dir(app_dir).context("failed to create .dotagents directory")?;
+    let hashes = starter::install(&files, app_dir).context("failed to copy starter")?;
+
+    if !app_dir.join(".gitignore").exists() {
+        dummy::set_gitignore()?;
+    }
+
+    StarterOrigin {
+        source: starter.source.clone(),
+        revision: starter.revision.clone(),
+        variables: values,
+        files: hashes,
+    }
+    .save(&app_dir.join(STARTER_ORIGIN_FILE))
+    .context("failed to record starter")?;
+
+    log::info!("Initialized {} files from {}", files.len(), starter.source);
+
+    Ok(())
+}
+
+/// Pulls the current files of the starter the workspace was initialized from.
+pub(super) fn update_from_starter(opts: &InitOptions) -> Result<()> {
+    let origin_file = get_starter_origin_file()?;
+
+    if !origin_file.is_file() {
+        bail!("this workspace wasn't initialized from a starter, use `init --from` first");
+    }
+
+    let origin = StarterOrigin::from_file(&origin_file).context("failed to load starter origin")?;
+    let starter = Starter::fetch(&origin.source).context("failed to fetch starter")?;
+    let values = collect_values(&starter, opts, origin.variables.clone())
+        .context("failed to fill in starter")?;
+    let files = starter
+        .render(&values)
+        .context("failed to render starter")?;
+
+    let (hashes, summary) = starter::update(&files, &get_application_dir()?, &origin)
+        .context("failed to update starter files")?;
+
+    StarterOrigin {
+        source: origin.source,
+        revision: starter.revision.clone(),
+        variables: values,
+        files: hashes,
+    }
+    .save(&origin_file)
+    .context("failed to record starter")?;
+
+    log::info!(
+        "Updated from {}: {} added, {} updated, {} removed, {} kept with local changes",
+        starter.source,
+        summary.added,
+        summary.updated,
+        summary.removed,
+        summary.kept
+    );
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+
+use super::options::{ContextOptions, OutputFormat, VarsOptions};
+use crate::core::template::{ContextSelection, TemplateContext};
+use crate::schema::config::AppConfig;
+use crate::templates::helpers::get_templater;
+
+impl From<ContextOptions> for ContextSelection {
+    fn from(opts: ContextOptions) -> Self {
+        Self {
+            provider: opts.provider,
+            feature: opts.feature,
+            command: opts.command,
+        }
+    }
+}
+
+/// Loads the template context selected by `opts` along with the selected provider's template.
+pub(super) fn load_context(opts: ContextOptions) -> Result<(TemplateContext, Option<String>)> {
+    let templater = get_templater();
+    let selection = ContextSelection::from(opts);
+    selection.validate().context("validate context selection")?;
+
+    let (global, local) = AppConfig::load_layers(templater).context("load application config")?;
+    let config = AppConfig::from_configs(&global, &local);
+    let settings = selection
+        .provider_settings(&config)
+        .context("select provider")?;
+
+    let provider = settings
+        .as_ref()
+        .map(|(target, settings)| (*target, settings));
+    let context = TemplateContext::build(templater, &global, &local, provider, &selection)
+        .context("build template context")?;
+
+    Ok((context, settings.and_then(|(_, s)| s.template)))
+}
+
+/// TOML has no null, so unset values are dropped rather than failing the whole output.
+fn strip_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, strip_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .filter(|value| !value.is_null())
+                .map(strip_nulls)
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+pub(super) fn print_variables(opts: VarsOptions) -> Result<()> {
+    let (context, _) = load_context(opts.context)?;
+    let output = json!({
+        "context": context.merged(),
+        "sources": context.sources(),
+    });
+
+    let content = match opts.format {
+        OutputFormat::Json => {
+            serde_json::to_string_pretty(&output).context("serialize variables to JSON")?
+        }
+        OutputFormat::Toml => {
+            toml::to_string_pretty(&strip_nulls(output)).context("serialize variables to TOML")?
+        }
+    };
+
+    println!("{}", content);
+
+    Ok(())
+}
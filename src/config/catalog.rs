@@ -0,0 +1,36 @@
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+use crate::schema::common::Target;
+use crate::schema::config::Providers;
+
+const BUILTIN_PROVIDERS: &str = include_str!("../../public/providers.toml");
+
+static CATALOG: OnceLock<Providers> = OnceLock::new();
+
+#[derive(Deserialize)]
+struct Catalog {
+    providers: Providers,
+}
+
+/// Providers shipped with dotagents, as published in `public/providers.toml`.
+pub(crate) fn builtin_providers() -> &'static Providers {
+    CATALOG.get_or_init(|| {
+        toml::from_str::<Catalog>(BUILTIN_PROVIDERS)
+            .expect("built-in provider catalog is invalid")
+            .providers
+    })
+}
+
+/// Names of every built-in provider along with the target it belongs to.
+pub(crate) fn builtin_provider_names() -> Vec<(Target, &'static str)> {
+    let catalog = builtin_providers();
+    let mut names: Vec<_> = Target::ALL
+        .into_iter()
+        .filter_map(|target| catalog.get(target).map(|map| (target, map)))
+        .flat_map(|(target, map)| map.keys().map(move |name| (target, name.as_str())))
+        .collect();
+
+    names.sort();
+    names
+}
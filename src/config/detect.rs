@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use super::catalog::builtin_provider_names;
+use crate::schema::builder::mcp::McpConfigBuilder;
+use crate::schema::common::Target;
+use crate::schema::mcp::McpConfig;
+use crate::utils::jsonc::strip_comments;
+
+/// Where an agent leaves traces on a machine and where its existing configuration lives.
+struct AgentHint {
+    name: &'static str,
+    /// Directories below the home directory the agent creates.
+    dirs: &'static [&'static str],
+    /// Executables the agent installs on `PATH`.
+    binaries: &'static [&'static str],
+    /// MCP configuration below the home directory, in the agent's own format.
+    mcp: Option<&'static str>,
+    /// Instruction files below the workspace the agent reads.
+    instructions: &'static [&'static str],
+}
+
+const AGENT_HINTS: &[AgentHint] = &[
+    AgentHint {
+        name: "copilot",
+        dirs: &[".copilot"],
+        binaries: &["copilot"],
+        mcp: Some(".copilot/mcp-config.json"),
+        instructions: &[".github/copilot-instructions.md"],
+    },
+    AgentHint {
+        name: "windsurf",
+        dirs: &[".codeium/windsurf"],
+        binaries: &["windsurf"],
+        mcp: Some(".codeium/windsurf/mcp_config.json"),
+        instructions: &[".windsurfrules"],
+    },
+    AgentHint {
+        name: "cursor",
+        dirs: &[".cursor"],
+        binaries: &["cursor"],
+        mcp: Some(".cursor/mcp.json"),
+        instructions: &[".cursorrules"],
+    },
+    AgentHint {
+        name: "claude",
+        dirs: &[".claude"],
+        binaries: &["claude"],
+        mcp: None,
+        instructions: &["CLAUDE.md"],
+    },
+    AgentHint {
+        name: "gemini",
+        dirs: &[".gemini"],
+        binaries: &["gemini"],
+        mcp: None,
+        instructions: &["GEMINI.md"],
+    },
+];
+
+/// Instruction files shared by several agents.
+const SHARED_INSTRUCTIONS: &[&str] = &["AGENTS.md"];
+
+/// An agent found on this machine.
+pub(crate) struct DetectedAgent {
+    pub name: &'static str,
+    /// The kind of built-in provider deploying to the agent, `None` without one.
+    pub target: Option<Target>,
+    /// What gave the agent away, e.g. its config directory.
+    pub evidence: String,
+}
+
+/// Configuration an agent already has, which `init` can bring into the workspace.
+pub(crate) enum ImportSource {
+    Mcp { agent: &'static str, path: PathBuf },
+    Instructions { path: PathBuf },
+}
+
+impl ImportSource {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Mcp { agent, path } => {
+                format!("MCP servers of {} from {}", agent, path.display())
+            }
+            Self::Instructions { path } => format!("instructions from {}", path.display()),
+        }
+    }
+}
+
+/// Every known agent with a config directory in `home_dir` or an executable on `PATH`.
+pub(crate) fn detect_agents(home_dir: &Path) -> Vec<DetectedAgent> {
+    let providers = builtin_provider_names();
+
+    AGENT_HINTS
+        .iter()
+        .filter_map(|hint| {
+            let evidence = hint
+                .dirs
+                .iter()
+                .map(|dir| home_dir.join(dir))
+                .find(|dir| dir.is_dir())
+                .or_else(|| {
+                    hint.binaries
+                        .iter()
+                        .find_map(|binary| find_executable(binary))
+                })?;
+
+            Some(DetectedAgent {
+                name: hint.name,
+                target: providers
+                    .iter()
+                    .find(|(_, name)| *name == hint.name)
+                    .map(|(target, _)| *target),
+                evidence: evidence.display().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Existing configuration of the `agents` that can be imported.
+pub(crate) fn import_sources(
+    agents: &[String],
+    home_dir: &Path,
+    workspace_dir: &Path,
+) -> Vec<ImportSource> {
+    let hints: Vec<&AgentHint> = AGENT_HINTS
+        .iter()
+        .filter(|hint| agents.iter().any(|agent| agent == hint.name))
+        .collect();
+
+    let mcp = hints.iter().filter_map(|hint| {
+        let path = home_dir.join(hint.mcp?);
+        path.is_file().then_some(ImportSource::Mcp {
+            agent: hint.name,
+            path,
+        })
+    });
+
+    let instructions = SHARED_INSTRUCTIONS
+        .iter()
+        .chain(hints.iter().flat_map(|hint| hint.instructions))
+        .map(|file| workspace_dir.join(file))
+        .filter(|path| path.is_file())
+        .map(|path| ImportSource::Instructions { path });
+
+    mcp.chain(instructions).collect()
+}
+
+/// Reads the servers of an agent's MCP config, whether listed under `mcpServers` or
+/// `servers`. Entries dotagents can't express are skipped with a warning.
+pub(crate) fn read_mcp_servers(path: &Path) -> Result<McpConfig> {
+    let text = fs::read_to_string(path).context(format!("failed to read {}", path.display()))?;
+    let config: Value = serde_json::from_str(&strip_comments(&text))
+        .context(format!("failed to parse {}", path.display()))?;
+
+    let servers = config
+        .get("mcpServers")
+        .or_else(|| config.get("servers"))
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+
+    let string_map = |value: Option<&Value>| -> Option<HashMap<String, String>> {
+        let map: HashMap<_, _> = value?
+            .as_object()?
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+            .collect();
+        (!map.is_empty()).then_some(map)
+    };
+
+    let mut builder = McpConfigBuilder::new();
+
+    for (name, server) in &servers {
+        let url = server
+            .get("url")
+            .or_else(|| server.get("serverUrl"))
+            .and_then(Value::as_str);
+
+        builder = match (server.get("command").and_then(Value::as_str), url) {
+            (Some(command), _) => builder.add_stdio_server(
+                name,
+                command,
+                server
+                    .get("args")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|arg| arg.as_str().map(String::from))
+                    .collect(),
+                server.get("cwd").and_then(Value::as_str),
+                string_map(server.get("env")),
+                None,
+            ),
+            (None, Some(url)) => {
+                builder.add_http_server(name, url, string_map(server.get("headers")), None)
+            }
+            (None, None) => {
+                log::warn!(
+                    "Skipping server {} of {}, it has no command or url",
+                    name,
+                    path.display()
+                );
+                continue;
+            }
+        };
+    }
+
+    Ok(builder.build())
+}
+
+/// Looks up `name` in the directories of `PATH`.
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+
+    env::split_paths(&path).find_map(|dir| {
+        let candidate = dir.join(name);
+
+        #[cfg(windows)]
+        let candidate = ["exe", "cmd", "bat"]
+            .iter()
+            .map(|extension| candidate.with_extension(extension))
+            .find(|candidate| candidate.is_file())?;
+
+        candidate.is_file().then_some(candidate)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, to_value};
+
+    use super::*;
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn detects_agents_from_their_home_directories() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir_all(home.path().join(".copilot")).unwrap();
+        fs::create_dir_all(home.path().join(".codeium/windsurf")).unwrap();
+        // A file where the directory would be gives nothing away.
+        write(home.path(), ".cursor", "");
+
+        let agents = detect_agents(home.path());
+        let find = |name: &str| agents.iter().find(|agent| agent.name == name);
+
+        let copilot = find("copilot").unwrap();
+        assert_eq!(copilot.target, Some(Target::Cli));
+        assert_eq!(
+            copilot.evidence,
+            home.path().join(".copilot").display().to_string()
+        );
+        assert_eq!(find("windsurf").unwrap().target, Some(Target::Ide));
+
+        // Agents may still be found on PATH, which the test doesn't control.
+        for name in ["cursor", "gemini"] {
+            if find_executable(name).is_none() {
+                assert!(find(name).is_none(), "{} was detected", name);
+            }
+        }
+    }
+
+    #[test]
+    fn lists_the_configuration_of_the_chosen_agents() {
+        let home = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+        write(home.path(), ".copilot/mcp-config.json", "{}");
+        write(home.path(), ".cursor/mcp.json", "{}");
+        write(workspace.path(), "AGENTS.md", "shared");
+        write(
+            workspace.path(),
+            ".github/copilot-instructions.md",
+            "copilot",
+        );
+        write(workspace.path(), "CLAUDE.md", "claude");
+
+        let sources = import_sources(&["copilot".into()], home.path(), workspace.path());
+        let described: Vec<String> = sources.iter().map(ImportSource::describe).collect();
+
+        assert_eq!(
+            described,
+            [
+                format!(
+                    "MCP servers of copilot from {}",
+                    home.path().join(".copilot/mcp-config.json").display()
+                ),
+                format!(
+                    "instructions from {}",
+                    workspace.path().join("AGENTS.md").display()
+                ),
+                format!(
+                    "instructions from {}",
+                    workspace
+                        .path()
+                        .join(".github/copilot-instructions.md")
+                        .display()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_stdio_and_http_servers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcp.json");
+        fs::write(
+            &path,
+            r#"{
+                // Comments and trailing commas, as agents allow them.
+                "mcpServers": {
+                    "files": { "command": "npx", "args": ["-y", "files", 3], "env": { "ROOT": "/", "DEBUG": true } },
+                    "web": { "url": "https://example.com/mcp", "headers": { "Authorization": "Bearer x" } },
+                    "windsurf": { "serverUrl": "https://example.com/sse" },
+                    "broken": { "disabled": true },
+                },
+            }"#,
+        )
+        .unwrap();
+
+        let config = read_mcp_servers(&path).unwrap();
+
+        assert_eq!(config.servers.len(), 3);
+        assert_eq!(
+            to_value(&config.servers["files"]).unwrap(),
+            json!({ "type": "stdio", "command": "npx", "args": ["-y", "files"], "env": { "ROOT": "/" } })
+        );
+        assert_eq!(
+            to_value(&config.servers["web"]).unwrap(),
+            json!({ "type": "http", "url": "https://example.com/mcp", "headers": { "Authorization": "Bearer x" } })
+        );
+        assert_eq!(
+            config.servers["windsurf"].endpoint(),
+            "https://example.com/sse"
+        );
+    }
+
+    #[test]
+    fn reads_vscode_style_servers_and_rejects_malformed_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mcp.json");
+
+        fs::write(
+            &path,
+            r#"{ "servers": { "git": { "command": "git-mcp" } } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            read_mcp_servers(&path).unwrap().servers["git"].endpoint(),
+            "git-mcp"
+        );
+
+        fs::write(&path, r#"{ "mcpServers": { "git": "#).unwrap();
+        let error = read_mcp_servers(&path).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("failed to parse {}", path.display())
+        );
+
+        let missing = dir.path().join("missing.json");
+        let error = read_mcp_servers(&missing).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!("failed to read {}", missing.display())
+        );
+    }
+}
@@ -1,22 +1,18 @@
 use anyhow::{Context, Result};
-use std::collections::HashMap;
-use std::fmt::format;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::cli::InitOptions;
+use crate::cli::InitSelection;
 use crate::constants::dir::CACHE_DIR;
 use crate::constants::{
-    dir::{COMMANDS_DIR, ROOT_DIR},
-    file::{GLOBAL_CONFIG_FILE, INSTRUCTIONS_FILE, LOCAL_CONFIG_FILE, MCP_FILE},
+    dir::{COMMANDS_DIR, INSTRUCTIONS_DIR, ROOT_DIR},
+    file::{GLOBAL_CONFIG_FILE, LOCAL_CONFIG_FILE, MCP_FILE},
 };
 use crate::schema::builder::{
     command::CommandBuilder, config::ApplicationConfigBuilder, mcp::McpConfigBuilder,
 };
-use crate::schema::{
-    common::Target,
-    config::{ConfigAgentAbilitySettings, ConfigAgentSettings, TomlConfig},
-};
+use crate::schema::{command::CommandArgument, common::Target, config::TomlConfig, mcp::McpConfig};
 
 fn get_root_relative_path<P: AsRef<Path>>(relative_path: P) -> PathBuf {
     let main_dir = Path::new(ROOT_DIR);
@@ -24,9 +20,10 @@ fn get_root_relative_path<P: AsRef<Path>>(relative_path: P) -> PathBuf {
 }
 
 fn set_dummy_data(filename: &str, content: &str, dir_name: Option<&str>) -> Result<()> {
-    let path = if dir_name.is_some() {
-        let absolute_dir = get_root_relative_path(dir_name.unwrap());
-        fs::create_dir_all(&absolute_dir).context("unable to create commands directory")?;
+    let path = if let Some(dir_name) = dir_name {
+        let absolute_dir = get_root_relative_path(dir_name);
+        fs::create_dir_all(&absolute_dir)
+            .context(format!("unable to create {} directory", dir_name))?;
         absolute_dir.join(filename)
     } else {
         get_root_relative_path(filename)
@@ -39,12 +36,16 @@ fn set_dummy_data(filename: &str, content: &str, dir_name: Option<&str>) -> Resu
 
 pub(crate) fn set_dummy_command() -> Result<()> {
     let commands = CommandBuilder::new("hello", "A Hello Command to greet the User.")
+        .add_argument(CommandArgument {
+            name: "name".into(),
+            description: Some("Name of the user".into()),
+            required: false,
+            default: None,
+        })
         .add_content(
             r#"# Hello Command
 
-Greet the User with his name if present, else greet user as stranger.
-
-Context: $USER_INPUT"#,
+Greet the User by ${arg:name} if present, else greet user as stranger."#,
         )
         .build();
 
@@ -58,7 +59,7 @@ Context: $USER_INPUT"#,
 pub(crate) fn set_dummy_instructions() -> Result<()> {
     let content = "# Instructions for {{ agent_name }}\n\nThis is a custom instructions for {{ agent_name }} for a given repository.\n";
 
-    set_dummy_data(INSTRUCTIONS_FILE, content, None)?;
+    set_dummy_data("general.md", content, Some(INSTRUCTIONS_DIR))?;
 
     Ok(())
 }
@@ -78,7 +79,8 @@ pub(crate) fn set_dummy_mcp() -> Result<()> {
             "server-stdio",
             "python",
             vec![],
-            Some("{{ workspace_folder }}"),
+            Some("{{ workspace_dir }}"),
+            None,
             None,
         )
         .build();
@@ -90,41 +92,30 @@ pub(crate) fn set_dummy_mcp() -> Result<()> {
     Ok(())
 }
 
-pub(crate) fn set_dummy_config(opts: InitOptions) -> Result<()> {
-    let config_builder = ApplicationConfigBuilder::new()
-        .add_features(!opts.no_command, !opts.no_instruction, !opts.no_mcp)
-        .add_targets(
-            ["gemini".into()].into_iter().collect(),
-            ["vscode".into(), "windsurf".into()].into_iter().collect(),
-            [].into_iter().collect(),
-        );
-
-    let global_config = config_builder.clone().build();
-
-    let local_config = config_builder
-        .add_target(Target::Custom, ["opencode".into()].into_iter().collect())
-        .add_provider(
-            Target::Custom,
-            "opencode",
-            ConfigAgentAbilitySettings {
-                mcp: Some(ConfigAgentSettings {
-                    template: Some("templates/opencode".into()),
-                    target: Some("{{ workspace_dir }}/.opencode/mcp.json".into()),
-                    ..Default::default()
-                }),
-                instructions: Some(ConfigAgentSettings {
-                    template: Some("templates/INSTRUCTION.md".into()),
-                    target: Some("{{ workspace_dir }}/.opencode/instructions.md".into()),
-                    ..Default::default()
-                }),
-                commands: Some(ConfigAgentSettings {
-                    template: Some("templates/commands-template".into()),
-                    target: Some("{{ workspace_dir }}/.opencode/commands".into()),
-                    ..Default::default()
-                }),
-            },
-        )
-        .build_local();
+pub(crate) fn set_dummy_config(selection: &InitSelection) -> Result<()> {
+    let mut config_builder = ApplicationConfigBuilder::new().add_features(
+        selection.commands,
+        selection.instructions,
+        selection.mcp,
+    );
+
+    for target in Target::ALL {
+        let names: HashSet<String> = selection
+            .targets
+            .iter()
+            .filter(|(kind, _)| *kind == target)
+            .map(|(_, name)| name.clone())
+            .collect();
+
+        if !names.is_empty() {
+            config_builder = config_builder.add_target(target, names);
+        }
+    }
+
+    let global_config = config_builder.build();
+    // Local settings override the global ones, so repeating the selection there would only
+    // shadow later edits of config.toml.
+    let local_config = ApplicationConfigBuilder::new().build_local();
 
     let local_content = local_config.to_toml()?;
     let global_content = global_config.to_toml()?;
@@ -135,6 +126,24 @@ pub(crate) fn set_dummy_config(opts: InitOptions) -> Result<()> {
     Ok(())
 }
 
+/// Seeds an instruction fragment from an agent's existing instructions. Handlebars
+/// expressions are escaped, the file wasn't written as a template.
+pub(crate) fn set_imported_instruction(name: &str, content: &str) -> Result<()> {
+    let content = content.replace("{{", "\\{{");
+
+    set_dummy_data(&format!("{}.md", name), &content, Some(INSTRUCTIONS_DIR))?;
+
+    Ok(())
+}
+
+pub(crate) fn set_imported_mcp(config: &McpConfig) -> Result<()> {
+    let content = config.to_json()?;
+
+    set_dummy_data(MCP_FILE, &content, None)?;
+
+    Ok(())
+}
+
 pub(crate) fn set_gitignore() -> Result<()> {
     let content = format!("{}/\n{}", CACHE_DIR, LOCAL_CONFIG_FILE);
 
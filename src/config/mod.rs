@@ -1 +1,3 @@
+pub(crate) mod catalog;
+pub(crate) mod detect;
 pub(crate) mod dummy;
@@ -1,7 +1,12 @@
+/// Directory marking a workspace and holding its sources, separate for debug builds so they
+/// don't touch real workspaces.
 #[cfg(debug_assertions)]
-pub(crate) const ROOT_DIR: &str = ".dotagents-debug";
+pub const ROOT_DIR: &str = ".dotagents-debug";
+/// Directory marking a workspace and holding its sources.
 #[cfg(not(debug_assertions))]
-pub(crate) const ROOT_DIR: &str = ".dotagents";
+pub const ROOT_DIR: &str = ".dotagents";
 pub(crate) const COMMANDS_DIR: &str = "commands";
+pub(crate) const INSTRUCTIONS_DIR: &str = "instructions";
 pub(crate) const CACHE_DIR: &str = "cache";
-pub(crate) const TEMPLATE_DIR: &str = "templates";
+pub(crate) const BACKUPS_DIR: &str = "backups";
+pub(crate) const PACKAGES_DIR: &str = "packages";
@@ -1,3 +1,4 @@
 pub(crate) const COMMANDS_FEATURE: &str = "commands";
 pub(crate) const MCP_FEATURE: &str = "mcp";
 pub(crate) const INSTRUCTION_FEATURE: &str = "instructions";
+pub(crate) const FEATURES: [&str; 3] = [COMMANDS_FEATURE, MCP_FEATURE, INSTRUCTION_FEATURE];
@@ -3,3 +3,7 @@ pub(crate) const MCP_FILE: &str = "mcp.jsonc";
 pub(crate) const GLOBAL_CONFIG_FILE: &str = "config.toml";
 pub(crate) const LOCAL_CONFIG_FILE: &str = "local.config.toml";
 pub(crate) const CACHE_CONFIG_FILE: &str = "cache.toml";
+pub(crate) const BACKUP_MANIFEST_FILE: &str = "manifest.toml";
+pub(crate) const STARTER_MANIFEST_FILE: &str = "starter.toml";
+pub(crate) const STARTER_ORIGIN_FILE: &str = "origin.toml";
+pub(crate) const PACKAGES_LOCK_FILE: &str = "packages.lock";
@@ -1,4 +1,3 @@
 pub(crate) const CONFIG_SCHEMA: &str = "https://dotagents.soorya-u.dev/schemas/config.schema.json";
 pub(crate) const MCP_SCHEMA: &str = "https://dotagents.soorya-u.dev/json/schemas/mcp.schema.json";
-pub(crate) const PROVIDER_SCHEMA: &str =
-    "https://dotagents.soorya-u.dev/json/schemas/provider.schema.json";
+pub(crate) const TEMPLATES_URL: &str = "https://dotagents.soorya-u.dev/templates/";
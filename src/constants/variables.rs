@@ -1,3 +1,8 @@
 pub const WORKSPACE_DIR: &str = "workspace_dir";
 pub const CONFIG_DIR: &str = "config_dir";
 pub const APPLICATION_DIR: &str = "application_dir";
+pub const HOME_DIR: &str = "home_dir";
+pub const GIT: &str = "git";
+pub const PROJECT: &str = "project";
+pub const AGENT_NAME: &str = "agent_name";
+pub const AGENT_KIND: &str = "agent_kind";
@@ -0,0 +1,366 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use super::roots::AllowedRoots;
+use crate::schema::backup::{BackedUpFile, BackupManifest};
+use crate::schema::config::CacheConfig;
+use crate::utils::fs::write_atomic;
+use crate::utils::path::get_backups_dir;
+use crate::utils::{did_you_mean, utc_now};
+
+/// Saves the files a deploy is about to replace when dotagents didn't create them. The backup
+/// directory is only created once there is something to save.
+pub(crate) struct BackupSession {
+    manifest: BackupManifest,
+    dir: Option<PathBuf>,
+}
+
+impl BackupSession {
+    pub fn new() -> Self {
+        Self {
+            manifest: BackupManifest::new(&utc_now()),
+            dir: None,
+        }
+    }
+
+    /// Copies `path` into the backup unless it is missing or a previous deploy wrote it.
+    pub fn save(&mut self, path: &Path, cache: &CacheConfig) -> Result<()> {
+        if !path.is_file() || cache.owns(&path.to_string_lossy()) {
+            return Ok(());
+        }
+
+        let dir = self.dir()?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let backup = format!("{}-{}", self.manifest.files.len(), name);
+
+        fs::copy(path, dir.join(&backup))
+            .context(format!("failed to back up {}", path.display()))?;
+
+        self.manifest.files.push(BackedUpFile {
+            path: path.to_string_lossy().to_string(),
+            backup,
+        });
+        self.manifest.save(&dir)?;
+
+        log::info!("Backed up {} to {}", path.display(), dir.display());
+
+        Ok(())
+    }
+
+    /// Removes the backup of a deploy that was rolled back, as its files are back in place.
+    pub fn discard(self) -> Result<()> {
+        match &self.dir {
+            Some(dir) => {
+                fs::remove_dir_all(dir).context(format!("failed to remove {}", dir.display()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn dir(&mut self) -> Result<PathBuf> {
+        if let Some(dir) = &self.dir {
+            return Ok(dir.clone());
+        }
+
+        let backups = get_backups_dir()?;
+        let id = self.manifest.created.replace(['-', ':'], "");
+        let mut dir = backups.join(&id);
+
+        // Two deploys within the same second get their own directories.
+        for attempt in 1.. {
+            if !dir.exists() {
+                break;
+            }
+            dir = backups.join(format!("{}-{}", id, attempt));
+        }
+
+        fs::create_dir_all(&dir).context(format!("failed to create {}", dir.display()))?;
+        self.dir = Some(dir.clone());
+
+        Ok(dir)
+    }
+}
+
+/// Every backup along with its manifest, oldest first.
+pub(crate) fn list_backups() -> Result<Vec<(String, BackupManifest)>> {
+    let dir = get_backups_dir()?;
+
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+
+    for entry in fs::read_dir(&dir).context(format!("failed to read {}", dir.display()))? {
+        let path = entry?.path();
+
+        if !path.is_dir() {
+            continue;
+        }
+
+        let id = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        backups.push((id, BackupManifest::from_dir(&path)?));
+    }
+
+    backups.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok(backups)
+}
+
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+/// Puts the files of backup `id` back where they were. Restored files are dropped from
+/// `cache`, so the next deploy backs them up again instead of overwriting them silently.
+pub(crate) fn restore_backup(
+    id: &str,
+    cache: &mut CacheConfig,
+    roots: &AllowedRoots,
+) -> Result<Vec<String>> {
+    let backups = list_backups()?;
+
+    let Some((_, manifest)) = backups.iter().find(|(candidate, _)| candidate == id) else {
+        match did_you_mean(id, backups.iter().map(|(candidate, _)| candidate.as_str())) {
+            Some(suggestion) => bail!("Unknown backup '{}', did you mean '{}'?", id, suggestion),
+            None => bail!("Unknown backup '{}'", id),
+        }
+    };
+
+    // Manifests are plain files anyone can edit, so they get the same checks as a deploy and
+    // may only name copies inside the backup directory.
+    if let Some(file) = manifest
+        .files
+        .iter()
+        .find(|file| !is_file_name(&file.backup))
+    {
+        bail!(
+            "Backup '{}' of {} is not a file name in the backup directory",
+            file.backup,
+            file.path
+        );
+    }
+
+    let refused: Vec<String> = manifest
+        .files
+        .iter()
+        .filter_map(|file| {
+            let reason = roots.refusal(Path::new(&file.path))?;
+            Some(format!("      - {} {}", file.path, reason))
+        })
+        .collect();
+
+    if !refused.is_empty() {
+        bail!(
+            "{} file(s) would be restored outside the allowed roots, pass --allow-outside to restore them anyway:\n{}",
+            refused.len(),
+            refused.join("\n")
+        );
+    }
+
+    let dir = get_backups_dir()?.join(id);
+    let mut restored = Vec::new();
+
+    for file in &manifest.files {
+        let path = Path::new(&file.path);
+        let content = fs::read(dir.join(&file.backup))
+            .context(format!("failed to read backup of {}", file.path))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("failed to create directory {}", parent.display()))?;
+        }
+
+        write_atomic(path, &content)?;
+        cache.forget(&file.path);
+        restored.push(file.path.clone());
+    }
+
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::common::{DeployMode, Target};
+    use crate::schema::config::{AppConfig, ConfigAgentSettings, DeployedOutput};
+    use crate::utils::testing::TestWorkspace;
+
+    fn roots(workspace: &TestWorkspace) -> AllowedRoots {
+        AllowedRoots::from_config(&AppConfig::new(), &workspace.path(), false).unwrap()
+    }
+
+    /// A cache recording that a deploy wrote `path`.
+    fn owning(path: &Path) -> CacheConfig {
+        let mut cache = CacheConfig::new();
+        let settings = ConfigAgentSettings {
+            outputs: Some(vec![DeployedOutput {
+                path: path.to_string_lossy().to_string(),
+                hash: "hash".into(),
+                item: None,
+                mode: Some(DeployMode::Template),
+            }]),
+            ..Default::default()
+        };
+        cache.set_settings(Target::Cli, "copilot", "instructions", Some(settings));
+        cache
+    }
+
+    #[test]
+    fn saves_only_files_dotagents_did_not_write() {
+        let workspace = TestWorkspace::new();
+        let user = workspace.write("AGENTS.md", "mine");
+        let owned = workspace.write("owned.md", "deployed");
+        let cache = owning(&owned);
+
+        workspace.run(|| {
+            let mut empty = BackupSession::new();
+            empty
+                .save(&workspace.path().join("missing.md"), &cache)
+                .unwrap();
+            empty.save(&owned, &cache).unwrap();
+
+            // Nothing to save, so not even a directory.
+            assert!(list_backups().unwrap().is_empty());
+            assert!(!get_backups_dir().unwrap().exists());
+
+            let mut session = BackupSession::new();
+            session.save(&user, &cache).unwrap();
+
+            let backups = list_backups().unwrap();
+            let [(id, manifest)] = backups.as_slice() else {
+                panic!("expected one backup, got {:?}", backups);
+            };
+
+            assert_eq!(manifest.files.len(), 1);
+            assert_eq!(manifest.files[0].path, user.to_string_lossy());
+            assert_eq!(manifest.files[0].backup, "0-AGENTS.md");
+            assert_eq!(
+                fs::read_to_string(get_backups_dir().unwrap().join(id).join("0-AGENTS.md"))
+                    .unwrap(),
+                "mine"
+            );
+        });
+    }
+
+    #[test]
+    fn keeps_backups_of_the_same_second_apart() {
+        let workspace = TestWorkspace::new();
+        let user = workspace.write("AGENTS.md", "mine");
+
+        workspace.run(|| {
+            for _ in 0..2 {
+                BackupSession::new()
+                    .save(&user, &CacheConfig::new())
+                    .unwrap();
+            }
+
+            let ids: Vec<String> = list_backups()
+                .unwrap()
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+
+            assert_eq!(ids.len(), 2);
+            assert_ne!(ids[0], ids[1]);
+        });
+    }
+
+    #[test]
+    fn restores_files_and_forgets_them_in_the_cache() {
+        let workspace = TestWorkspace::new();
+        let user = workspace.write("AGENTS.md", "mine");
+
+        workspace.run(|| {
+            BackupSession::new()
+                .save(&user, &CacheConfig::new())
+                .unwrap();
+            let (id, _) = list_backups().unwrap().remove(0);
+
+            fs::remove_file(&user).unwrap();
+            let mut cache = owning(&user);
+
+            let restored = restore_backup(&id, &mut cache, &roots(&workspace)).unwrap();
+
+            assert_eq!(restored, [user.to_string_lossy().to_string()]);
+            assert_eq!(workspace.read("AGENTS.md"), "mine");
+            assert!(!cache.owns(&user.to_string_lossy()));
+
+            let error =
+                restore_backup(&format!("{}x", id), &mut cache, &roots(&workspace)).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("Unknown backup '{}x', did you mean '{}'?", id, id)
+            );
+        });
+    }
+
+    #[test]
+    fn rejects_manifests_naming_files_outside_the_backup() {
+        let workspace = TestWorkspace::new();
+        let target = workspace.write("AGENTS.md", "mine");
+
+        workspace.run(|| {
+            let dir = get_backups_dir().unwrap().join("20240101T000000Z");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("0-AGENTS.md"), "backed up").unwrap();
+
+            for backup in ["../../config.toml", "nested/0-AGENTS.md", "..", ""] {
+                let mut manifest = BackupManifest::new("2024-01-01T00:00:00Z");
+                manifest.files = vec![
+                    BackedUpFile {
+                        path: target.to_string_lossy().to_string(),
+                        backup: "0-AGENTS.md".into(),
+                    },
+                    BackedUpFile {
+                        path: target.to_string_lossy().to_string(),
+                        backup: backup.into(),
+                    },
+                ];
+                manifest.save(&dir).unwrap();
+
+                let error = restore_backup(
+                    "20240101T000000Z",
+                    &mut CacheConfig::new(),
+                    &roots(&workspace),
+                )
+                .unwrap_err();
+
+                assert!(
+                    error
+                        .to_string()
+                        .starts_with(&format!("Backup '{}' of", backup)),
+                    "{}",
+                    error
+                );
+                // Refused before anything was restored.
+                assert_eq!(workspace.read("AGENTS.md"), "mine");
+            }
+        });
+    }
+
+    #[test]
+    fn discards_the_backup_of_a_rolled_back_deploy() {
+        let workspace = TestWorkspace::new();
+        let user = workspace.write("AGENTS.md", "mine");
+
+        workspace.run(|| {
+            BackupSession::new().discard().unwrap();
+
+            let mut session = BackupSession::new();
+            session.save(&user, &CacheConfig::new()).unwrap();
+            assert_eq!(list_backups().unwrap().len(), 1);
+
+            session.discard().unwrap();
+            assert!(list_backups().unwrap().is_empty());
+        });
+    }
+}
@@ -0,0 +1,751 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use super::backup::BackupSession;
+use super::journal::DeployJournal;
+use super::plan::{DeployPlan, OutputStrategy, PlannedUnit};
+use super::roots::AllowedRoots;
+use super::scope::DeployScope;
+use crate::schema::common::DeployMode;
+use crate::schema::config::{CacheConfig, DeployedOutput};
+use crate::utils::fs::{relative_path, symlink_atomic, write_atomic};
+use crate::utils::hash_content;
+
+/// Writes a rendered plan to disk, prunes outputs that are no longer produced and records the
+/// result in `cache`. Cache entries outside `scope` are left untouched.
+///
+/// Runs as a transaction: if any change fails, the files already changed are restored and
+/// `cache` is left as it was.
+pub(crate) fn apply_plan(
+    plan: &DeployPlan,
+    cache: &mut CacheConfig,
+    scope: &DeployScope,
+    roots: &AllowedRoots,
+) -> Result<()> {
+    let mut staged = cache.clone();
+    let mut journal = DeployJournal::new();
+    let mut backups = BackupSession::new();
+
+    if let Err(e) = apply_changes(plan, &mut staged, scope, roots, &mut backups, &mut journal) {
+        let changes = journal.len();
+
+        // The backup is only kept when the rollback left files behind it could recover.
+        match journal.rollback() {
+            Ok(()) => {
+                log::warn!("Rolled back {} change(s) after the deploy failed", changes);
+
+                if let Err(discard) = backups.discard() {
+                    log::warn!("Failed to remove the backup of the deploy: {:#}", discard);
+                }
+            }
+            Err(rollback) => log::error!("Failed to roll back the deploy: {:#}", rollback),
+        }
+
+        return Err(e);
+    }
+
+    *cache = staged;
+    Ok(())
+}
+
+fn apply_changes(
+    plan: &DeployPlan,
+    cache: &mut CacheConfig,
+    scope: &DeployScope,
+    roots: &AllowedRoots,
+    backups: &mut BackupSession,
+    journal: &mut DeployJournal,
+) -> Result<()> {
+    let mut deployed = HashSet::new();
+
+    for unit in &plan.units {
+        apply_unit(unit, cache, scope, roots, backups, journal)?;
+        deployed.insert((unit.target, unit.provider.clone(), unit.feature));
+    }
+
+    for (target, provider, feature) in cache.entries() {
+        if deployed.contains(&(target, provider.clone(), feature))
+            || !scope.includes_provider(&provider)
+            || !scope.includes_feature(feature)
+        {
+            continue;
+        }
+
+        let Some(mut settings) = cache.get_settings(target, &provider, feature) else {
+            continue;
+        };
+
+        let (stale, kept): (Vec<_>, Vec<_>) = settings
+            .outputs
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|output| scope.includes_item(feature, output.item.as_deref()));
+
+        for output in &stale {
+            prune_output(output, roots, journal)?;
+        }
+
+        let settings = if kept.is_empty() {
+            None
+        } else {
+            settings.hash = Some(combined_hash(&kept));
+            settings.outputs = Some(kept);
+            Some(settings)
+        };
+
+        cache.set_settings(target, &provider, feature, settings);
+    }
+
+    Ok(())
+}
+
+fn apply_unit(
+    unit: &PlannedUnit,
+    cache: &mut CacheConfig,
+    scope: &DeployScope,
+    roots: &AllowedRoots,
+    backups: &mut BackupSession,
+    journal: &mut DeployJournal,
+) -> Result<()> {
+    let previous = cache
+        .get_settings(unit.target, &unit.provider, unit.feature)
+        .and_then(|settings| settings.outputs)
+        .unwrap_or_default();
+
+    let mut outputs = Vec::new();
+
+    let mode = unit.settings.mode.unwrap_or_default();
+
+    for output in &unit.outputs {
+        let (hash, mode) = match &output.strategy {
+            OutputStrategy::Link(source) => {
+                let link = write_link(&output.path, source, cache, backups, journal)?;
+                let hash = hash_content(link.to_string_lossy().as_bytes());
+                (hash, DeployMode::Symlink)
+            }
+            OutputStrategy::Write => {
+                write_output(&output.path, &output.content, mode, cache, backups, journal)?;
+                let hash = hash_content(output.content.as_bytes());
+                // A renderer may write files for a provider in symlink mode, which pruning
+                // must not take for links.
+                match mode {
+                    DeployMode::Symlink => (hash, DeployMode::Copy),
+                    mode => (hash, mode),
+                }
+            }
+        };
+
+        outputs.push(DeployedOutput {
+            path: output.path.to_string_lossy().to_string(),
+            hash,
+            item: output.item.clone(),
+            mode: Some(mode),
+        });
+    }
+
+    for old in previous {
+        if outputs.iter().any(|output| output.path == old.path) {
+            continue;
+        }
+
+        if scope.includes_item(unit.feature, old.item.as_deref()) {
+            prune_output(&old, roots, journal)?;
+        } else {
+            outputs.push(old);
+        }
+    }
+
+    outputs.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut settings = unit.settings.clone();
+    settings.hash = Some(combined_hash(&outputs));
+    settings.outputs = Some(outputs);
+
+    cache.set_settings(unit.target, &unit.provider, unit.feature, Some(settings));
+
+    Ok(())
+}
+
+fn write_output(
+    path: &Path,
+    content: &str,
+    mode: DeployMode,
+    cache: &CacheConfig,
+    backups: &mut BackupSession,
+    journal: &mut DeployJournal,
+) -> Result<()> {
+    let own_link = is_symlink(path) && cache.owns_link(&path.to_string_lossy());
+    // Copy mode promises a file of its own, never one shared through somebody's link.
+    let replace_link = own_link || (is_symlink(path) && mode == DeployMode::Copy);
+
+    if !replace_link && fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+        log::debug!("Up to date {}", path.display());
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        journal.create_dir_all(parent)?;
+    }
+
+    // A link of an earlier symlink deploy gives way to the file, as does any link in copy
+    // mode after a backup of what it pointed at. Other links dotagents didn't create are
+    // written through, so the file they point at is what gets backed up.
+    let target = if replace_link {
+        if !own_link {
+            log::warn!("Replacing foreign symlink {} with a copy", path.display());
+            backups.save(path, cache)?;
+        }
+        journal.record(path)?;
+        fs::remove_file(path).context(format!("failed to remove {}", path.display()))?;
+        path.to_path_buf()
+    } else if is_symlink(path) {
+        fs::canonicalize(path).context(format!(
+            "failed to resolve symlink {}, remove it to deploy a file in its place",
+            path.display()
+        ))?
+    } else {
+        path.to_path_buf()
+    };
+
+    backups.save(&target, cache)?;
+    journal.record(&target)?;
+    write_atomic(&target, content.as_bytes())?;
+
+    match target == path {
+        true => log::info!("Deployed {}", path.display()),
+        false => log::info!(
+            "Deployed {} through its symlink to {}",
+            path.display(),
+            target.display()
+        ),
+    }
+
+    Ok(())
+}
+
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Links `path` to `source` with a relative symlink, returning the link target. Broken links
+/// and links dotagents didn't create are replaced, the latter after a backup.
+fn write_link(
+    path: &Path,
+    source: &Path,
+    cache: &CacheConfig,
+    backups: &mut BackupSession,
+    journal: &mut DeployJournal,
+) -> Result<PathBuf> {
+    let parent = path
+        .parent()
+        .context(format!("{} has no parent directory", path.display()))?;
+    let target = relative_path(parent, source);
+
+    match fs::read_link(path) {
+        Ok(existing) if existing == target => {
+            log::debug!("Up to date {}", path.display());
+            return Ok(target);
+        }
+        Ok(existing) if !parent.join(&existing).exists() => {
+            log::warn!(
+                "Repairing broken symlink {} -> {}",
+                path.display(),
+                existing.display()
+            );
+        }
+        Ok(existing) if !cache.owns(&path.to_string_lossy()) => {
+            log::warn!(
+                "Replacing foreign symlink {} -> {}",
+                path.display(),
+                existing.display()
+            );
+            backups.save(path, cache)?;
+        }
+        Ok(_) => {}
+        Err(_) => backups.save(path, cache)?,
+    }
+
+    journal.create_dir_all(parent)?;
+    journal.record(path)?;
+    symlink_atomic(&target, path)?;
+    log::info!("Linked {} -> {}", path.display(), target.display());
+
+    Ok(target)
+}
+
+/// Removes a previously deployed file unless it was edited after dotagents wrote it. Links
+/// are only removed while they still point where dotagents left them, and nothing outside
+/// `roots` is removed, whatever the cache says.
+fn prune_output(
+    output: &DeployedOutput,
+    roots: &AllowedRoots,
+    journal: &mut DeployJournal,
+) -> Result<()> {
+    let path = Path::new(&output.path);
+
+    if let Some(reason) = roots.refusal(path) {
+        log::warn!(
+            "Leaving {} in place as it {}, pass --allow-outside to remove it",
+            path.display(),
+            reason
+        );
+        return Ok(());
+    }
+
+    if output.mode == Some(DeployMode::Symlink) {
+        let Ok(link) = fs::read_link(path) else {
+            if path.exists() {
+                log::warn!(
+                    "Leaving {} in place as it was replaced since the last deploy",
+                    path.display()
+                );
+            }
+            return Ok(());
+        };
+
+        if hash_content(link.to_string_lossy().as_bytes()) != output.hash {
+            log::warn!(
+                "Leaving {} in place as it was relinked since the last deploy",
+                path.display()
+            );
+            return Ok(());
+        }
+
+        journal.record(path)?;
+        fs::remove_file(path).context(format!("failed to remove {}", path.display()))?;
+        log::info!("Removed {}", path.display());
+        return Ok(());
+    }
+
+    // Whatever a link points at, the file dotagents wrote is gone.
+    if is_symlink(path) {
+        log::warn!(
+            "Leaving {} in place as it was replaced since the last deploy",
+            path.display()
+        );
+        return Ok(());
+    }
+
+    let Ok(existing) = fs::read(path) else {
+        return Ok(());
+    };
+
+    if hash_content(&existing) != output.hash {
+        log::warn!(
+            "Leaving {} in place as it was modified since the last deploy",
+            path.display()
+        );
+        return Ok(());
+    }
+
+    journal.record(path)?;
+    fs::remove_file(path).context(format!("failed to remove {}", path.display()))?;
+    log::info!("Removed {}", path.display());
+
+    Ok(())
+}
+
+fn combined_hash(outputs: &[DeployedOutput]) -> String {
+    let digest: String = outputs
+        .iter()
+        .map(|output| format!("{}\0{}\n", output.path, output.hash))
+        .collect();
+
+    hash_content(digest.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::features::{COMMANDS_FEATURE, INSTRUCTION_FEATURE};
+    #[cfg(unix)]
+    use crate::core::backup::list_backups;
+    use crate::core::plan::PlannedOutput;
+    use crate::schema::common::Target;
+    use crate::schema::config::{AppConfig, ConfigAgentSettings};
+    use crate::utils::testing::TestWorkspace;
+
+    fn roots(workspace: &TestWorkspace) -> AllowedRoots {
+        AllowedRoots::from_config(&AppConfig::new(), &workspace.path(), false).unwrap()
+    }
+
+    /// Writes `content` to `path` as a previous deploy of `item` would have.
+    fn deployed(
+        workspace: &TestWorkspace,
+        path: &str,
+        content: &str,
+        item: Option<&str>,
+    ) -> DeployedOutput {
+        DeployedOutput {
+            path: workspace.write(path, content).to_string_lossy().to_string(),
+            hash: hash_content(content.as_bytes()),
+            item: item.map(String::from),
+            mode: Some(DeployMode::Template),
+        }
+    }
+
+    fn record(
+        cache: &mut CacheConfig,
+        target: Target,
+        provider: &str,
+        feature: &str,
+        outputs: Vec<DeployedOutput>,
+    ) {
+        let settings = ConfigAgentSettings {
+            hash: Some(combined_hash(&outputs)),
+            outputs: Some(outputs),
+            ..Default::default()
+        };
+        cache.set_settings(target, provider, feature, Some(settings));
+    }
+
+    fn unit(
+        workspace: &TestWorkspace,
+        provider: &str,
+        feature: &'static str,
+        outputs: &[(&str, &str, Option<&str>)],
+    ) -> PlannedUnit {
+        PlannedUnit {
+            target: Target::Cli,
+            provider: provider.to_string(),
+            feature,
+            settings: ConfigAgentSettings::default(),
+            outputs: outputs
+                .iter()
+                .map(|(path, content, item)| PlannedOutput {
+                    item: item.map(String::from),
+                    path: workspace.path().join(path),
+                    content: content.to_string(),
+                    strategy: OutputStrategy::Write,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn partial_deploys_keep_providers_and_features_outside_the_scope() {
+        let workspace = TestWorkspace::new();
+        let mut cache = CacheConfig::new();
+
+        let windsurf = deployed(&workspace, ".windsurf/workflows/hello.md", "hi", None);
+        record(
+            &mut cache,
+            Target::Ide,
+            "windsurf",
+            COMMANDS_FEATURE,
+            vec![windsurf.clone()],
+        );
+        let instructions = deployed(
+            &workspace,
+            ".github/copilot-instructions.md",
+            "be nice",
+            None,
+        );
+        record(
+            &mut cache,
+            Target::Cli,
+            "copilot",
+            INSTRUCTION_FEATURE,
+            vec![instructions.clone()],
+        );
+        let old = deployed(
+            &workspace,
+            ".github/prompts/old.prompt.md",
+            "old",
+            Some("old"),
+        );
+        record(
+            &mut cache,
+            Target::Cli,
+            "copilot",
+            COMMANDS_FEATURE,
+            vec![old.clone()],
+        );
+        let before = cache.clone();
+
+        let scope = DeployScope {
+            providers: ["copilot".to_string()].into_iter().collect(),
+            features: [COMMANDS_FEATURE.to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        let plan = DeployPlan {
+            units: vec![unit(
+                &workspace,
+                "copilot",
+                COMMANDS_FEATURE,
+                &[(".github/prompts/hello.prompt.md", "hello", Some("hello"))],
+            )],
+        };
+
+        workspace
+            .run(|| apply_plan(&plan, &mut cache, &scope, &roots(&workspace)))
+            .unwrap();
+
+        // Out of scope: the windsurf commands and copilot instructions.
+        for (target, provider, feature) in [
+            (Target::Ide, "windsurf", COMMANDS_FEATURE),
+            (Target::Cli, "copilot", INSTRUCTION_FEATURE),
+        ] {
+            assert_eq!(
+                cache.get_settings(target, provider, feature),
+                before.get_settings(target, provider, feature)
+            );
+        }
+        assert_eq!(workspace.read(".windsurf/workflows/hello.md"), "hi");
+        assert_eq!(workspace.read(".github/copilot-instructions.md"), "be nice");
+
+        // In scope: the copilot commands, where the old command is pruned.
+        let outputs = cache
+            .get_settings(Target::Cli, "copilot", COMMANDS_FEATURE)
+            .and_then(|settings| settings.outputs)
+            .unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].item.as_deref(), Some("hello"));
+        assert_eq!(workspace.read(".github/prompts/hello.prompt.md"), "hello");
+        assert!(!Path::new(&old.path).exists());
+    }
+
+    #[test]
+    fn command_deploys_keep_the_other_commands() {
+        let workspace = TestWorkspace::new();
+        let mut cache = CacheConfig::new();
+
+        let other = deployed(
+            &workspace,
+            ".github/prompts/other.prompt.md",
+            "other",
+            Some("other"),
+        );
+        let hello = deployed(
+            &workspace,
+            ".github/prompts/hello.prompt.md",
+            "hi",
+            Some("hello"),
+        );
+        record(
+            &mut cache,
+            Target::Cli,
+            "copilot",
+            COMMANDS_FEATURE,
+            vec![hello, other.clone()],
+        );
+
+        let scope = DeployScope {
+            commands: ["hello".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        let plan = DeployPlan {
+            units: vec![unit(
+                &workspace,
+                "copilot",
+                COMMANDS_FEATURE,
+                &[(".github/prompts/hello.prompt.md", "hello", Some("hello"))],
+            )],
+        };
+
+        workspace
+            .run(|| apply_plan(&plan, &mut cache, &scope, &roots(&workspace)))
+            .unwrap();
+
+        let outputs = cache
+            .get_settings(Target::Cli, "copilot", COMMANDS_FEATURE)
+            .and_then(|settings| settings.outputs)
+            .unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert!(outputs.contains(&other));
+        assert_eq!(workspace.read(".github/prompts/other.prompt.md"), "other");
+        assert_eq!(workspace.read(".github/prompts/hello.prompt.md"), "hello");
+    }
+
+    #[cfg(unix)]
+    fn link(workspace: &TestWorkspace, path: &str, target: &str) -> PathBuf {
+        let path = workspace.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(target, &path).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_replace_broken_and_foreign_symlinks() {
+        let workspace = TestWorkspace::new();
+        let mut cache = CacheConfig::new();
+
+        workspace.write(".agents/a.md", "a");
+        workspace.write(".agents/b.md", "b");
+        workspace.write(".cursor/elsewhere.md", "someone else's");
+        let broken = link(&workspace, ".cursor/rules/a.md", "missing.md");
+        let foreign = link(&workspace, ".cursor/rules/b.md", "../elsewhere.md");
+
+        let mut plan_unit = unit(&workspace, "cursor", INSTRUCTION_FEATURE, &[]);
+        plan_unit.settings.mode = Some(DeployMode::Symlink);
+        plan_unit.outputs = ["a", "b"]
+            .into_iter()
+            .map(|name| PlannedOutput {
+                item: Some(name.to_string()),
+                path: workspace.path().join(format!(".cursor/rules/{}.md", name)),
+                content: String::new(),
+                strategy: OutputStrategy::Link(
+                    workspace.path().join(format!(".agents/{}.md", name)),
+                ),
+            })
+            .collect();
+        let plan = DeployPlan {
+            units: vec![plan_unit],
+        };
+
+        workspace.run(|| {
+            apply_plan(
+                &plan,
+                &mut cache,
+                &DeployScope::default(),
+                &roots(&workspace),
+            )
+            .unwrap();
+
+            assert_eq!(
+                fs::read_link(&broken).unwrap(),
+                Path::new("../../.agents/a.md")
+            );
+            assert_eq!(
+                fs::read_link(&foreign).unwrap(),
+                Path::new("../../.agents/b.md")
+            );
+            assert_eq!(workspace.read(".cursor/rules/b.md"), "b");
+            assert_eq!(workspace.read(".cursor/elsewhere.md"), "someone else's");
+
+            // Only the foreign link had something worth keeping.
+            let backups = list_backups().unwrap();
+            assert_eq!(backups.len(), 1);
+            assert_eq!(backups[0].1.files[0].path, foreign.to_string_lossy());
+        });
+
+        let outputs = cache
+            .get_settings(Target::Cli, "cursor", INSTRUCTION_FEATURE)
+            .and_then(|settings| settings.outputs)
+            .unwrap();
+        assert!(
+            outputs
+                .iter()
+                .all(|output| output.mode == Some(DeployMode::Symlink))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn prunes_only_the_links_it_created() {
+        let workspace = TestWorkspace::new();
+        let mut cache = CacheConfig::new();
+        workspace.write(".agents/rule.md", "rule");
+
+        let linked = |path: &str, target: &str| DeployedOutput {
+            path: link(&workspace, path, target).to_string_lossy().to_string(),
+            hash: hash_content(target.as_bytes()),
+            item: None,
+            mode: Some(DeployMode::Symlink),
+        };
+        let own = linked(".cursor/rules/own.md", "../../.agents/rule.md");
+        let relinked = linked(".cursor/rules/relinked.md", "../../.agents/rule.md");
+        fs::remove_file(&relinked.path).unwrap();
+        link(
+            &workspace,
+            ".cursor/rules/relinked.md",
+            "../../.agents/other.md",
+        );
+
+        // A file dotagents wrote, which the user has since made a link to the same content.
+        let mut written = deployed(&workspace, ".cursor/rules/written.md", "rule", None);
+        fs::remove_file(&written.path).unwrap();
+        link(
+            &workspace,
+            ".cursor/rules/written.md",
+            "../../.agents/rule.md",
+        );
+        written.mode = Some(DeployMode::Copy);
+
+        record(
+            &mut cache,
+            Target::Cli,
+            "cursor",
+            INSTRUCTION_FEATURE,
+            vec![own.clone(), relinked.clone(), written.clone()],
+        );
+
+        let plan = DeployPlan {
+            units: vec![unit(&workspace, "cursor", INSTRUCTION_FEATURE, &[])],
+        };
+        workspace
+            .run(|| {
+                apply_plan(
+                    &plan,
+                    &mut cache,
+                    &DeployScope::default(),
+                    &roots(&workspace),
+                )
+            })
+            .unwrap();
+
+        assert!(!is_symlink(Path::new(&own.path)));
+        assert!(is_symlink(Path::new(&relinked.path)));
+        assert!(is_symlink(Path::new(&written.path)));
+        assert_eq!(workspace.read(".agents/rule.md"), "rule");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_replace_foreign_symlinks_instead_of_writing_through() {
+        let workspace = TestWorkspace::new();
+        let mut cache = CacheConfig::new();
+        workspace.write("shared/AGENTS.md", "shared");
+        let copied = link(&workspace, "AGENTS.md", "shared/AGENTS.md");
+        workspace.write("docs/shared.md", "shared");
+        let rendered = link(&workspace, "docs/AGENTS.md", "shared.md");
+
+        let mut copy = unit(
+            &workspace,
+            "codex",
+            INSTRUCTION_FEATURE,
+            &[("AGENTS.md", "copied", None)],
+        );
+        copy.settings.mode = Some(DeployMode::Copy);
+        let template = unit(
+            &workspace,
+            "gemini",
+            INSTRUCTION_FEATURE,
+            &[("docs/AGENTS.md", "rendered", None)],
+        );
+        let plan = DeployPlan {
+            units: vec![copy, template],
+        };
+
+        workspace.run(|| {
+            apply_plan(
+                &plan,
+                &mut cache,
+                &DeployScope::default(),
+                &roots(&workspace),
+            )
+            .unwrap();
+
+            assert!(!is_symlink(&copied));
+            assert_eq!(workspace.read("AGENTS.md"), "copied");
+            assert_eq!(workspace.read("shared/AGENTS.md"), "shared");
+
+            // Rendered outputs still go where the link points.
+            assert!(is_symlink(&rendered));
+            assert_eq!(workspace.read("docs/shared.md"), "rendered");
+
+            let backups = list_backups().unwrap();
+            let paths: Vec<&str> = backups
+                .iter()
+                .flat_map(|(_, manifest)| &manifest.files)
+                .map(|file| file.path.as_str())
+                .collect();
+            assert!(paths.contains(&copied.to_string_lossy().as_ref()));
+        });
+    }
+}
@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use super::deploy::apply_plan;
+use super::packages::sync_packages;
+use super::plan::DeployPlan;
+use super::renderer::{ProviderRenderer, RendererRegistry};
+use super::roots::AllowedRoots;
+use super::scope::DeployScope;
+use crate::schema::config::{AppConfig, CacheConfig};
+use crate::templates::helpers::Templater;
+use crate::utils::path::{get_workspace_dir, in_workspace};
+
+/// Deploys a workspace the way `dotagents deploy` does, the one containing the current
+/// directory unless [`workspace_dir`](Self::workspace_dir) is set. [`plan`](Self::plan)
+/// renders the outputs without touching the filesystem, so they can be inspected before
+/// [`apply`](Self::apply) writes them.
+pub struct Deployer<'a> {
+    templater: &'a Templater,
+    config: AppConfig,
+    renderers: RendererRegistry,
+    scope: DeployScope,
+    workspace_dir: Option<PathBuf>,
+    discover_tools: bool,
+    allow_outside: bool,
+}
+
+impl<'a> Deployer<'a> {
+    pub fn new(templater: &'a Templater, config: AppConfig) -> Self {
+        Self {
+            templater,
+            config,
+            renderers: RendererRegistry::new(),
+            scope: DeployScope::default(),
+            workspace_dir: None,
+            discover_tools: false,
+            allow_outside: false,
+        }
+    }
+
+    /// Renders `provider` with `renderer` instead of its template.
+    pub fn renderer(
+        mut self,
+        provider: impl Into<String>,
+        renderer: impl ProviderRenderer + 'static,
+    ) -> Self {
+        self.renderers.register(provider, renderer);
+        self
+    }
+
+    /// Restricts the deploy to some providers, features or commands.
+    pub fn scope(mut self, scope: DeployScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Deploys the workspace at `dir`. `templater` and `config` should be loaded from it too,
+    /// see [`Templater::for_workspace`].
+    pub fn workspace_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.workspace_dir = Some(dir.into());
+        self
+    }
+
+    /// Starts servers with tool filters to learn their tools while planning.
+    pub fn discover_tools(mut self, discover_tools: bool) -> Self {
+        self.discover_tools = discover_tools;
+        self
+    }
+
+    /// Lets outputs be written outside the allowed roots.
+    pub fn allow_outside(mut self, allow_outside: bool) -> Self {
+        self.allow_outside = allow_outside;
+        self
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Checks out the git packages of the config at the commits of the lockfile, locking those
+    /// it doesn't list yet. [`plan`](Self::plan) only reads the checkouts this leaves.
+    pub fn sync_packages(&self) -> Result<()> {
+        self.in_workspace(|| sync_packages(&self.config).context("sync packages"))
+    }
+
+    /// Renders every output of the deploy and checks where they would be written. Errors
+    /// carry the step that failed as their outermost context, as the CLI prints them.
+    pub fn plan(&self) -> Result<DeployPlan> {
+        self.in_workspace(|| self.build_plan())
+    }
+
+    /// Writes `plan` and records it in the deploy cache. Written files are rolled back when
+    /// any output fails.
+    pub fn apply(&self, plan: &DeployPlan) -> Result<()> {
+        self.in_workspace(|| self.apply_plan(plan))
+    }
+
+    fn build_plan(&self) -> Result<DeployPlan> {
+        self.scope
+            .validate(&self.config)
+            .context("validate deploy filters")?;
+
+        let plan = DeployPlan::build(
+            self.templater,
+            &self.config,
+            &self.scope,
+            &self.renderers,
+            self.discover_tools,
+        )
+        .context("render deploy plan")?;
+
+        self.roots()
+            .and_then(|roots| roots.check_plan(&plan))
+            .context("check output paths")?;
+
+        Ok(plan)
+    }
+
+    fn apply_plan(&self, plan: &DeployPlan) -> Result<()> {
+        let mut cache = CacheConfig::from_application().context("load deploy cache")?;
+        let roots = self.roots().context("check output paths")?;
+
+        apply_plan(plan, &mut cache, &self.scope, &roots).context("write deployed files")?;
+        cache.save().context("save deploy cache")
+    }
+
+    fn in_workspace<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        match &self.workspace_dir {
+            Some(dir) => in_workspace(dir, f),
+            None => f(),
+        }
+    }
+
+    fn roots(&self) -> Result<AllowedRoots> {
+        AllowedRoots::from_config(&self.config, &get_workspace_dir()?, self.allow_outside)
+    }
+}
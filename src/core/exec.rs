@@ -0,0 +1,530 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::plan::{OutputStrategy, PlannedOutput};
+use super::renderer::{ProviderRenderer, RenderInput, normalize_content};
+use crate::schema::command::ArgumentStyle;
+use crate::schema::common::DeployMode;
+use crate::schema::config::ConfigAgentSettings;
+use crate::schema::instruction::InstructionLayout;
+use crate::schema::mcp::ToolFilter;
+use crate::schema::variable::Variable;
+
+/// Sent with every request, so renderers can refuse versions they don't understand. Replies
+/// may state the version they were written for, which then has to match.
+const EXEC_PROTOCOL_VERSION: u32 = 1;
+const EXEC_PREFIX: &str = "exec:";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const STDERR_LIMIT: usize = 4096;
+
+/// Renders a provider with an external program. The program reads an `ExecRequest` as JSON
+/// on stdin and answers on stdout with a JSON list of `ExecOutput`, or an `ExecReply` object
+/// holding one, which is checked like any other render before the deploy writes it.
+pub(crate) struct ExecRenderer {
+    spec: String,
+    program: PathBuf,
+    timeout: Duration,
+}
+
+#[derive(Serialize)]
+struct ExecRequest<'a> {
+    version: u32,
+    provider: &'a str,
+    kind: &'static str,
+    feature: &'static str,
+    workspace: &'a Path,
+    settings: ExecSettings<'a>,
+    variables: &'a Value,
+    items: Vec<ExecItem<'a>>,
+}
+
+/// The provider settings a renderer gets to see. Bookkeeping such as the deploy hash and
+/// recorded outputs stays out of the protocol, so it can change without breaking renderers.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ExecSettings<'a> {
+    template: Option<&'a str>,
+    target: Option<&'a str>,
+    /// Definitions of the provider variables, whose values are in `ExecRequest::variables`.
+    variables: Option<&'a HashMap<String, Variable>>,
+    mode: Option<&'a DeployMode>,
+    layout: Option<&'a InstructionLayout>,
+    arguments: Option<&'a ArgumentStyle>,
+    namespace_separator: Option<&'a str>,
+    tool_filter: Option<&'a ToolFilter>,
+}
+
+impl<'a> From<&'a ConfigAgentSettings> for ExecSettings<'a> {
+    fn from(settings: &'a ConfigAgentSettings) -> Self {
+        Self {
+            template: settings.template.as_deref(),
+            target: settings.target.as_deref(),
+            variables: settings.variables.as_ref(),
+            mode: settings.mode.as_ref(),
+            layout: settings.layout.as_ref(),
+            arguments: settings.arguments.as_ref(),
+            namespace_separator: settings.namespace_separator.as_deref(),
+            tool_filter: settings.tool_filter.as_ref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExecItem<'a> {
+    id: Option<&'a str>,
+    data: &'a Value,
+    source: Option<&'a Path>,
+}
+
+/// A reply stating the protocol version it was written for.
+#[derive(Deserialize)]
+struct ExecReply {
+    #[serde(default)]
+    version: Option<u32>,
+    outputs: Vec<ExecOutput>,
+}
+
+#[derive(Deserialize)]
+struct ExecOutput {
+    /// Relative paths start at the workspace.
+    path: String,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    mode: ExecMode,
+    /// File to link to in `symlink` mode, relative to the workspace.
+    #[serde(default)]
+    source: Option<String>,
+    /// Source item the output belongs to, so deploys of single commands can prune it.
+    #[serde(default)]
+    item: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum ExecMode {
+    #[default]
+    Write,
+    Symlink,
+}
+
+impl ExecRenderer {
+    /// The renderer `spec` names, as set in the `renderer` of a provider.
+    pub fn from_settings(
+        spec: &str,
+        settings: &ConfigAgentSettings,
+        workspace_dir: &Path,
+    ) -> Result<Self> {
+        let Some(program) = spec.strip_prefix(EXEC_PREFIX).map(str::trim) else {
+            bail!(
+                "unknown renderer '{}', expected {}<program>",
+                spec,
+                EXEC_PREFIX
+            );
+        };
+
+        if program.is_empty() {
+            bail!("renderer '{}' names no program", spec);
+        }
+
+        // Bare names are looked up on PATH, like any command.
+        let program = match program.contains('/') {
+            true => workspace_dir.join(program),
+            false => PathBuf::from(program),
+        };
+
+        Ok(Self {
+            spec: spec.to_string(),
+            program,
+            timeout: settings
+                .renderer_timeout
+                .map_or(DEFAULT_TIMEOUT, Duration::from_secs),
+        })
+    }
+
+    /// Runs the program with `request` on stdin, returning what it printed once it exited.
+    fn run(&self, request: Vec<u8>, workspace_dir: &Path) -> Result<Vec<u8>> {
+        let deadline = Instant::now() + self.timeout;
+        let mut child = Command::new(&self.program)
+            .current_dir(workspace_dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(format!("failed to start {}", self.program.display()))?;
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .context("failed to open renderer stdin")?;
+        let mut stdout = child
+            .stdout
+            .take()
+            .context("failed to open renderer stdout")?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("failed to open renderer stderr")?;
+
+        // Renderers that don't read their input close the pipe early, which is fine.
+        thread::spawn(move || {
+            let _ = stdin.write_all(&request);
+        });
+
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            let _ = sender.send(stdout.read_to_end(&mut buffer).map(|_| buffer));
+        });
+
+        let errors = thread::spawn(move || {
+            let mut buffer = Vec::new();
+            let _ = stderr.take(STDERR_LIMIT as u64).read_to_end(&mut buffer);
+            String::from_utf8_lossy(&buffer).trim().to_string()
+        });
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let stdout = match output.recv_timeout(remaining) {
+            Ok(stdout) => stdout.context("failed to read renderer output")?,
+            Err(RecvTimeoutError::Timeout) => {
+                stop(&mut child);
+                bail!("timed out after {}s", self.timeout.as_secs());
+            }
+            Err(RecvTimeoutError::Disconnected) => bail!("failed to read renderer output"),
+        };
+
+        let status = loop {
+            match child.try_wait().context("failed to wait for renderer")? {
+                Some(status) => break status,
+                None if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+                None => {
+                    stop(&mut child);
+                    bail!("timed out after {}s", self.timeout.as_secs());
+                }
+            }
+        };
+
+        let stderr = errors.join().unwrap_or_default();
+
+        if !status.success() {
+            return Err(match stderr.is_empty() {
+                true => anyhow!("exited with {}", status),
+                false => anyhow!("exited with {}: {}", status, stderr),
+            });
+        }
+
+        for line in stderr.lines() {
+            log::debug!("{}: {}", self.spec, line);
+        }
+
+        Ok(stdout)
+    }
+}
+
+impl ProviderRenderer for ExecRenderer {
+    fn render(&self, input: &RenderInput) -> Result<Vec<PlannedOutput>> {
+        let request = ExecRequest {
+            version: EXEC_PROTOCOL_VERSION,
+            provider: input.provider,
+            kind: input.target.as_str(),
+            feature: input.feature,
+            workspace: input.workspace_dir,
+            settings: ExecSettings::from(input.settings),
+            variables: input.variables,
+            items: input
+                .items
+                .iter()
+                .map(|item| ExecItem {
+                    id: item.id.as_deref(),
+                    data: &item.data,
+                    source: item.source.as_deref(),
+                })
+                .collect(),
+        };
+        let request = serde_json::to_vec(&request).context("failed to serialize request")?;
+
+        let stdout = self
+            .run(request, input.workspace_dir)
+            .context(format!("failed to run renderer {}", self.spec))?;
+        let outputs = parse_reply(&stdout).context(format!(
+            "renderer {} did not print a valid reply",
+            self.spec
+        ))?;
+
+        let mut planned = Vec::new();
+        let mut seen = HashSet::new();
+        let mut errors = Vec::new();
+
+        for (index, output) in outputs.into_iter().enumerate() {
+            let label = match output.path.is_empty() {
+                true => format!("output {}", index + 1),
+                false => output.path.clone(),
+            };
+
+            if !output.path.is_empty() && !seen.insert(output.path.clone()) {
+                errors.push(format!("{}: is returned more than once", label));
+                continue;
+            }
+
+            match check_output(output, input.workspace_dir) {
+                Ok(output) => planned.push(output),
+                Err(e) => errors.push(format!("{}: {:#}", label, e)),
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!(
+                "renderer {} returned {} invalid output(s):\n{}",
+                self.spec,
+                errors.len(),
+                errors
+                    .iter()
+                    .map(|error| format!("      - {}", error))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
+        Ok(planned)
+    }
+}
+
+/// Reads the outputs of a reply, either a bare list or an `ExecReply`.
+fn parse_reply(stdout: &[u8]) -> Result<Vec<ExecOutput>> {
+    let reply: Value = serde_json::from_slice(stdout).context("failed to parse output as JSON")?;
+
+    if reply.is_array() {
+        return serde_json::from_value(reply).context("failed to parse list of outputs");
+    }
+
+    let reply: ExecReply = serde_json::from_value(reply).context("failed to parse reply object")?;
+
+    match reply.version {
+        Some(version) if version != EXEC_PROTOCOL_VERSION => bail!(
+            "reply is for protocol version {}, expected {}",
+            version,
+            EXEC_PROTOCOL_VERSION
+        ),
+        _ => Ok(reply.outputs),
+    }
+}
+
+/// Turns an output of a renderer into a planned one, rejecting what a deploy could not write.
+fn check_output(output: ExecOutput, workspace_dir: &Path) -> Result<PlannedOutput> {
+    if output.path.trim().is_empty() {
+        bail!("has no path");
+    }
+
+    let path = workspace_dir.join(output.path.trim());
+
+    match output.mode {
+        ExecMode::Write => {
+            let Some(content) = output.content else {
+                bail!("write mode needs a content");
+            };
+
+            Ok(PlannedOutput {
+                item: output.item,
+                content: normalize_content(&path, content)?,
+                path,
+                strategy: OutputStrategy::Write,
+            })
+        }
+        ExecMode::Symlink => {
+            let Some(source) = output.source else {
+                bail!("symlink mode needs a source");
+            };
+
+            let source = workspace_dir.join(source);
+            let content = fs::read_to_string(&source)
+                .context(format!("failed to read {}", source.display()))?;
+
+            Ok(PlannedOutput {
+                item: output.item,
+                path,
+                content,
+                strategy: OutputStrategy::Link(source),
+            })
+        }
+    }
+}
+
+fn stop(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::constants::dir::ROOT_DIR;
+    use crate::schema::common::{FeatureItem, Target};
+    use crate::templates::helpers::Templater;
+
+    /// Runs the shell `script` as the renderer of a command provider in a fresh workspace,
+    /// returning the outputs and the workspace the request was written to.
+    fn render_with(
+        script: &str,
+        settings: ConfigAgentSettings,
+    ) -> (Result<Vec<PlannedOutput>>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let app_dir = dir.path().join(ROOT_DIR);
+        fs::create_dir(&app_dir).unwrap();
+        fs::write(app_dir.join("config.toml"), "").unwrap();
+        fs::write(app_dir.join("local.config.toml"), "").unwrap();
+
+        let script_path = dir.path().join("render.sh");
+        fs::write(&script_path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let settings = ConfigAgentSettings {
+            renderer: Some("exec:./render.sh".into()),
+            ..settings
+        };
+        let renderer =
+            ExecRenderer::from_settings("exec:./render.sh", &settings, dir.path()).unwrap();
+
+        let templater = Templater::for_workspace(dir.path()).unwrap();
+        let items = [FeatureItem {
+            id: Some("greet".into()),
+            data: json!({ "name": "greet" }),
+            source: None,
+        }];
+        let variables = json!({ "agent_name": "custom" });
+
+        let result = renderer.render(&RenderInput {
+            provider: "custom",
+            target: Target::Custom,
+            feature: "commands",
+            settings: &settings,
+            items: &items,
+            variables: &variables,
+            workspace_dir: dir.path(),
+            templater: &templater,
+        });
+
+        (result, dir)
+    }
+
+    fn render(script: &str) -> Result<Vec<PlannedOutput>> {
+        render_with(script, ConfigAgentSettings::default()).0
+    }
+
+    #[test]
+    fn sends_the_documented_settings_and_reads_a_versioned_reply() {
+        let settings = ConfigAgentSettings {
+            template: Some("templates/command.hbs".into()),
+            target: Some("{{ workspace_dir }}/.custom/commands".into()),
+            hash: Some("abc".into()),
+            ..Default::default()
+        };
+        let (result, dir) = render_with(
+            r#"cat > request.json
+printf '%s' '{"version": 1, "outputs": [{"path": "out/greet.md", "content": "hello", "item": "greet"}]}'
+"#,
+            settings,
+        );
+
+        let outputs = result.unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].path, dir.path().join("out/greet.md"));
+        assert_eq!(outputs[0].content, "hello");
+        assert_eq!(outputs[0].item.as_deref(), Some("greet"));
+
+        let request: Value =
+            serde_json::from_str(&fs::read_to_string(dir.path().join("request.json")).unwrap())
+                .unwrap();
+        assert_eq!(request["version"], EXEC_PROTOCOL_VERSION);
+        assert_eq!(request["items"][0]["id"], "greet");
+        assert_eq!(
+            request["settings"],
+            json!({
+                "template": "templates/command.hbs",
+                "target": "{{ workspace_dir }}/.custom/commands",
+                "variables": null,
+                "mode": null,
+                "layout": null,
+                "arguments": null,
+                "namespace-separator": null,
+                "tool-filter": null,
+            })
+        );
+    }
+
+    #[test]
+    fn accepts_a_bare_list_of_outputs() {
+        let outputs = render(r#"printf '%s' '[{"path": "out.md", "content": "hello"}]'"#).unwrap();
+
+        assert_eq!(outputs.len(), 1);
+    }
+
+    #[test]
+    fn stops_renderers_running_past_the_timeout() {
+        let settings = ConfigAgentSettings {
+            renderer_timeout: Some(1),
+            ..Default::default()
+        };
+        let (result, _dir) = render_with("exec sleep 10\n", settings);
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(error.contains("timed out after 1s"), "{}", error);
+    }
+
+    #[test]
+    fn reports_failures_with_their_stderr() {
+        let error = format!(
+            "{:#}",
+            render("echo 'no template' >&2\nexit 3\n").unwrap_err()
+        );
+
+        assert!(error.contains("no template"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_invalid_replies() {
+        let error = format!("{:#}", render("echo 'not json'\n").unwrap_err());
+        assert!(error.contains("did not print a valid reply"), "{}", error);
+
+        let error = format!(
+            "{:#}",
+            render(r#"printf '%s' '{"version": 2, "outputs": []}'"#).unwrap_err()
+        );
+        assert!(
+            error.contains("protocol version 2, expected 1"),
+            "{}",
+            error
+        );
+
+        let error = format!(
+            "{:#}",
+            render(r#"printf '%s' '[{"path": "a.md"}, {"path": "b.md", "content": "b"}, {"path": "b.md", "content": "b"}, {"path": "", "content": "c"}]'"#)
+                .unwrap_err()
+        );
+        assert!(error.contains("3 invalid output(s)"), "{}", error);
+        assert!(
+            error.contains("a.md: write mode needs a content"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("b.md: is returned more than once"),
+            "{}",
+            error
+        );
+        assert!(error.contains("output 4: has no path"), "{}", error);
+    }
+}
@@ -0,0 +1,744 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Value, json};
+
+use crate::schema::mcp::ServerConfig;
+
+/// Protocol version dotagents asks for. Servers answer with the version they settle on.
+const PROTOCOL_VERSION: &str = "2025-06-18";
+const SESSION_HEADER: &str = "Mcp-Session-Id";
+const STDERR_LIMIT: usize = 4096;
+
+/// What a server reported during a successful handshake.
+pub(crate) struct HandshakeReport {
+    pub protocol_version: String,
+    pub server_name: String,
+    pub server_version: String,
+    pub tools: Vec<String>,
+}
+
+/// A JSON-RPC connection to an MCP server.
+trait Transport {
+    fn request(&mut self, id: u64, method: &str, params: Value) -> Result<Value>;
+    fn notify(&mut self, method: &str) -> Result<()>;
+}
+
+/// Expands the `{{ }}` templates and `${VAR}` references of a config value.
+pub(crate) type Resolver<'a> = dyn Fn(&str) -> Result<String> + Sync + 'a;
+
+/// Connects to `server`, performs the `initialize` handshake and lists its tools. Every read
+/// gives up once `timeout` has passed since the check started.
+pub(crate) fn check_server(
+    server: &ServerConfig,
+    resolve: &Resolver,
+    workspace_dir: &Path,
+    timeout: Duration,
+) -> Result<HandshakeReport> {
+    let deadline = Instant::now() + timeout;
+
+    match server {
+        ServerConfig::Stdio {
+            command,
+            args,
+            cwd,
+            env,
+            env_file,
+            ..
+        } => {
+            let mut process = Command::new(resolve(command)?);
+
+            for arg in args {
+                process.arg(resolve(arg)?);
+            }
+
+            let cwd = match cwd {
+                Some(cwd) => workspace_dir.join(resolve(cwd)?),
+                None => workspace_dir.to_path_buf(),
+            };
+            process.current_dir(cwd);
+
+            if let Some(env_file) = env_file {
+                let path = workspace_dir.join(resolve(env_file)?);
+                process.envs(read_env_file(&path)?);
+            }
+
+            for (key, value) in env.iter().flatten() {
+                process.env(key, resolve(value)?);
+            }
+
+            let mut transport = StdioTransport::spawn(process, command, deadline)?;
+            handshake(&mut transport)
+        }
+        ServerConfig::Http { url, headers, .. } => {
+            let headers = headers
+                .iter()
+                .flatten()
+                .map(|(key, value)| Ok((key.clone(), resolve(value)?)))
+                .collect::<Result<_>>()?;
+
+            let mut transport = HttpTransport {
+                agent: ureq::Agent::new(),
+                url: resolve(url)?,
+                headers,
+                session: None,
+                deadline,
+            };
+            handshake(&mut transport)
+        }
+    }
+}
+
+/// Checks every server at once, returning the results in the order of `servers`.
+pub(crate) fn check_servers_parallel<'a>(
+    servers: &[(&'a str, &ServerConfig)],
+    resolve: &Resolver,
+    workspace_dir: &Path,
+    timeout: Duration,
+) -> Vec<(&'a str, Result<HandshakeReport>)> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = servers
+            .iter()
+            .map(|(name, server)| {
+                let handle =
+                    scope.spawn(move || check_server(server, resolve, workspace_dir, timeout));
+                (*name, handle)
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|(name, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow!("check panicked")));
+                (name, result)
+            })
+            .collect()
+    })
+}
+
+/// Replaces `${VAR}` and `${env:VAR}` with the value of the environment variable, the way
+/// agents expand them when starting a server.
+pub(crate) fn expand_env(value: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+
+        let name = &rest[start + 2..start + end];
+        let name = name.strip_prefix("env:").unwrap_or(name);
+        let value =
+            std::env::var(name).map_err(|_| anyhow!("environment variable {} is not set", name))?;
+
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn handshake(transport: &mut dyn Transport) -> Result<HandshakeReport> {
+    let initialized = transport
+        .request(
+            1,
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }),
+        )
+        .context("initialize failed")?;
+
+    transport
+        .notify("notifications/initialized")
+        .context("failed to send initialized notification")?;
+
+    let mut tools = Vec::new();
+
+    if initialized["capabilities"].get("tools").is_some() {
+        let mut cursor = None;
+
+        for id in 2.. {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let page = transport
+                .request(id, "tools/list", params)
+                .context("tools/list failed")?;
+
+            tools.extend(
+                page["tools"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|tool| tool["name"].as_str())
+                    .map(str::to_string),
+            );
+
+            cursor = page["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                break;
+            }
+        }
+    }
+
+    let text = |value: &Value| value.as_str().unwrap_or("unknown").to_string();
+
+    Ok(HandshakeReport {
+        protocol_version: text(&initialized["protocolVersion"]),
+        server_name: text(&initialized["serverInfo"]["name"]),
+        server_version: text(&initialized["serverInfo"]["version"]),
+        tools,
+    })
+}
+
+fn rpc_request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn rpc_notification(method: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method })
+}
+
+/// Returns the result of `message` if it is the response to request `id`.
+fn rpc_result(message: &Value, id: u64) -> Option<Result<Value>> {
+    if message["id"].as_u64() != Some(id) {
+        return None;
+    }
+
+    if let Some(error) = message.get("error") {
+        return Some(Err(anyhow!(
+            "server returned error {}: {}",
+            error["code"],
+            error["message"].as_str().unwrap_or("unknown error")
+        )));
+    }
+
+    Some(Ok(message["result"].clone()))
+}
+
+/// Reads `KEY=VALUE` lines of a dotenv file.
+fn read_env_file(path: &Path) -> Result<HashMap<String, String>> {
+    let content = fs::read_to_string(path).context(format!("failed to read {}", path.display()))?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.trim_start_matches("export ").split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect())
+}
+
+/// A server spoken to over newline-delimited JSON on its stdin and stdout.
+struct StdioTransport {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    stderr: Arc<Mutex<String>>,
+    deadline: Instant,
+}
+
+impl StdioTransport {
+    fn spawn(mut process: Command, command: &str, deadline: Instant) -> Result<Self> {
+        let mut child = process
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(format!("failed to start `{}`", command))?;
+
+        let stdin = child.stdin.take().context("failed to open server stdin")?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("failed to open server stdout")?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("failed to open server stderr")?;
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Kept so a server that crashes on start can explain why.
+        let output = Arc::new(Mutex::new(String::new()));
+        let collected = Arc::clone(&output);
+        thread::spawn(move || {
+            let mut buffer = [0; 512];
+            let mut stderr = stderr;
+
+            while let Ok(read) = stderr.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                if let Ok(mut output) = collected.lock()
+                    && output.len() < STDERR_LIMIT
+                {
+                    output.push_str(&String::from_utf8_lossy(&buffer[..read]));
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+            stderr: output,
+            deadline,
+        })
+    }
+
+    fn send(&mut self, message: &Value) -> Result<()> {
+        writeln!(self.stdin, "{}", message)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| self.exited().unwrap_or_else(|| e.into()))
+    }
+
+    /// Describes how the server exited, if it did so within a moment.
+    fn exited(&mut self) -> Option<anyhow::Error> {
+        let started = Instant::now();
+
+        let status = loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if started.elapsed() < Duration::from_millis(500) => {
+                    thread::sleep(Duration::from_millis(20))
+                }
+                _ => return None,
+            }
+        };
+
+        // Give the stderr reader a moment to collect the last words of the server.
+        thread::sleep(Duration::from_millis(50));
+        let stderr = self.stderr.lock().map(|s| s.trim().to_string()).ok()?;
+
+        Some(match stderr.is_empty() {
+            true => anyhow!("server exited with {}", status),
+            false => anyhow!("server exited with {}: {}", status, stderr),
+        })
+    }
+}
+
+impl Transport for StdioTransport {
+    fn request(&mut self, id: u64, method: &str, params: Value) -> Result<Value> {
+        self.send(&rpc_request(id, method, params))?;
+
+        loop {
+            let remaining = self.deadline.saturating_duration_since(Instant::now());
+
+            let line = match self.lines.recv_timeout(remaining) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => bail!("timed out waiting for a response"),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(self
+                        .exited()
+                        .unwrap_or_else(|| anyhow!("server closed its output")));
+                }
+            };
+
+            // Servers occasionally log to stdout, skip anything that is not a message.
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+
+            if let Some(result) = rpc_result(&message, id) {
+                return result;
+            }
+        }
+    }
+
+    fn notify(&mut self, method: &str) -> Result<()> {
+        self.send(&rpc_notification(method))
+    }
+}
+
+impl Drop for StdioTransport {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A server spoken to over the streamable HTTP transport.
+struct HttpTransport {
+    agent: ureq::Agent,
+    url: String,
+    headers: Vec<(String, String)>,
+    session: Option<String>,
+    deadline: Instant,
+}
+
+impl HttpTransport {
+    fn post(&mut self, message: &Value) -> Result<ureq::Response> {
+        // Every request only gets what is left of the overall timeout, so a slow initialize
+        // leaves less time for tools/list instead of restarting the clock.
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            bail!("timed out waiting for a response");
+        }
+
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .timeout(remaining)
+            .set("Accept", "application/json, text/event-stream");
+
+        for (key, value) in &self.headers {
+            request = request.set(key, value);
+        }
+
+        if let Some(session) = &self.session {
+            request = request
+                .set(SESSION_HEADER, session)
+                .set("MCP-Protocol-Version", PROTOCOL_VERSION);
+        }
+
+        let response = match request
+            .set("Content-Type", "application/json")
+            .send_string(&message.to_string())
+        {
+            Ok(response) => response,
+            Err(ureq::Error::Status(code, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                bail!("server responded with HTTP {}: {}", code, body.trim());
+            }
+            // The transport error already names the URL and its cause.
+            Err(e) => bail!("{}", e),
+        };
+
+        if let Some(session) = response.header(SESSION_HEADER) {
+            self.session = Some(session.to_string());
+        }
+
+        Ok(response)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn request(&mut self, id: u64, method: &str, params: Value) -> Result<Value> {
+        let response = self.post(&rpc_request(id, method, params))?;
+
+        if response.content_type() != "text/event-stream" {
+            let body = response.into_string().context("failed to read response")?;
+            let message: Value =
+                serde_json::from_str(&body).context("server sent an invalid JSON response")?;
+
+            return rpc_result(&message, id)
+                .unwrap_or_else(|| Err(anyhow!("server answered a different request")));
+        }
+
+        // Each event carries one message, the response may follow other notifications.
+        let mut data = String::new();
+
+        for line in BufReader::new(response.into_reader()).lines() {
+            let line = line.context("failed to read event stream")?;
+
+            if let Some(chunk) = line.strip_prefix("data:") {
+                data.push_str(chunk.trim_start());
+                continue;
+            }
+
+            if !line.is_empty() || data.is_empty() {
+                continue;
+            }
+
+            if let Ok(message) = serde_json::from_str::<Value>(&data)
+                && let Some(result) = rpc_result(&message, id)
+            {
+                return result;
+            }
+
+            data.clear();
+        }
+
+        bail!("event stream ended without a response")
+    }
+
+    fn notify(&mut self, method: &str) -> Result<()> {
+        self.post(&rpc_notification(method)).map(|_| ())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Starts a stand-in server running `script` with `sh`.
+    fn check_script(script: &str, timeout: Duration) -> Result<HandshakeReport> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.sh");
+        fs::write(&path, script).unwrap();
+
+        let server: ServerConfig = serde_json::from_value(json!({
+            "type": "stdio",
+            "command": "sh",
+            "args": [path],
+            "envFile": ".env",
+        }))
+        .unwrap();
+        fs::write(dir.path().join(".env"), "GREETING=\"hello there\"\n").unwrap();
+
+        check_server(&server, &|value| Ok(value.to_string()), dir.path(), timeout)
+    }
+
+    /// A stand-in HTTP server answering each connection with the next of `replies`, after
+    /// waiting its delay. Joining the handle returns the requests it received.
+    fn serve(replies: Vec<(Duration, String)>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+
+            for (delay, reply) in replies {
+                let Ok((stream, _)) = listener.accept() else {
+                    break;
+                };
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+
+                while reader.read_line(&mut request).unwrap() > 2 {}
+
+                let length = request
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("content-length:")?
+                            .trim()
+                            .parse()
+                            .ok()
+                    })
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8_lossy(&body));
+                requests.push(request);
+
+                thread::sleep(delay);
+                let _ = reader.get_mut().write_all(reply.as_bytes());
+            }
+
+            requests
+        });
+
+        (url, handle)
+    }
+
+    fn reply(status: &str, headers: &[&str], body: &str) -> (Duration, String) {
+        let headers: String = headers
+            .iter()
+            .map(|header| format!("{}\r\n", header))
+            .collect();
+        let reply = format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        );
+
+        (Duration::ZERO, reply)
+    }
+
+    fn check_url(url: &str, timeout: Duration) -> Result<HandshakeReport> {
+        let server: ServerConfig = serde_json::from_value(json!({
+            "type": "http",
+            "url": url,
+            "headers": { "Authorization": "Bearer token" },
+        }))
+        .unwrap();
+
+        check_server(
+            &server,
+            &|value| Ok(value.to_string()),
+            Path::new("."),
+            timeout,
+        )
+    }
+
+    const INITIALIZED: &str = r#"{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"web","version":"2.0.0"}}}"#;
+
+    #[test]
+    fn performs_the_handshake_and_lists_tools() {
+        let report = check_script(
+            r#"
+read initialize
+echo 'starting up'
+echo '{"jsonrpc":"2.0","id":1,"result":{"protocolVersion":"2025-03-26","capabilities":{"tools":{}},"serverInfo":{"name":"stub","version":"'"$GREETING"'"}}}'
+read initialized
+read list
+echo '{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"a"}],"nextCursor":"next"}}'
+read list
+echo '{"jsonrpc":"2.0","id":3,"result":{"tools":[{"name":"b"}]}}'
+"#,
+            Duration::from_secs(10),
+        )
+        .unwrap();
+
+        assert_eq!(report.protocol_version, "2025-03-26");
+        assert_eq!(report.server_name, "stub");
+        assert_eq!(report.server_version, "hello there");
+        assert_eq!(report.tools, ["a", "b"]);
+    }
+
+    #[test]
+    fn gives_up_after_the_timeout() {
+        let started = Instant::now();
+        let error = check_script("exec sleep 10\n", Duration::from_millis(300))
+            .err()
+            .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(format!("{:#}", error).contains("timed out waiting for a response"));
+    }
+
+    #[test]
+    fn reports_the_stderr_of_a_crashing_server() {
+        let error = check_script(
+            "echo 'missing API key' >&2\nexit 3\n",
+            Duration::from_secs(10),
+        )
+        .err()
+        .unwrap();
+        let error = format!("{:#}", error);
+
+        assert!(error.contains("exit status: 3"), "{}", error);
+        assert!(error.contains("missing API key"), "{}", error);
+    }
+
+    #[test]
+    fn speaks_json_over_http_and_keeps_the_session() {
+        let (url, server) = serve(vec![
+            reply(
+                "200 OK",
+                &["Content-Type: application/json", "Mcp-Session-Id: abc123"],
+                INITIALIZED,
+            ),
+            reply("202 Accepted", &[], ""),
+            reply(
+                "200 OK",
+                &["Content-Type: application/json"],
+                r#"{"jsonrpc":"2.0","id":2,"result":{"tools":[{"name":"search"}]}}"#,
+            ),
+        ]);
+
+        let report = check_url(&url, Duration::from_secs(10)).unwrap();
+        let requests = server.join().unwrap();
+
+        assert_eq!(report.server_name, "web");
+        assert_eq!(report.server_version, "2.0.0");
+        assert_eq!(report.tools, ["search"]);
+
+        assert!(requests[0].contains(r#""method":"initialize""#));
+        assert!(requests[0].contains("Bearer token"));
+        assert!(!requests[0].to_lowercase().contains("mcp-session-id"));
+
+        for request in &requests[1..] {
+            assert!(
+                request.to_lowercase().contains("mcp-session-id: abc123"),
+                "{}",
+                request
+            );
+        }
+        assert!(requests[1].contains("notifications/initialized"));
+        assert!(requests[2].contains(r#""method":"tools/list""#));
+    }
+
+    #[test]
+    fn reads_responses_from_event_streams() {
+        let stream = format!(
+            "event: message\ndata: {}\n\ndata: {}\n\n",
+            r#"{"jsonrpc":"2.0","method":"notifications/message","params":{}}"#, INITIALIZED
+        );
+        let (url, server) = serve(vec![
+            reply("200 OK", &["Content-Type: text/event-stream"], &stream),
+            reply("202 Accepted", &[], ""),
+            reply(
+                "200 OK",
+                &["Content-Type: text/event-stream"],
+                "data: {\"jsonrpc\":\"2.0\",\"id\":2,\"result\":{\"tools\":[]}}\n\n",
+            ),
+        ]);
+
+        let report = check_url(&url, Duration::from_secs(10)).unwrap();
+        server.join().unwrap();
+
+        assert_eq!(report.protocol_version, "2025-06-18");
+        assert!(report.tools.is_empty());
+    }
+
+    #[test]
+    fn reports_http_errors_with_their_body() {
+        let (url, server) = serve(vec![reply("401 Unauthorized", &[], "invalid token\n")]);
+
+        let error = check_url(&url, Duration::from_secs(10)).err().unwrap();
+        server.join().unwrap();
+
+        assert_eq!(
+            format!("{:#}", error),
+            "initialize failed: server responded with HTTP 401: invalid token"
+        );
+    }
+
+    #[test]
+    fn shares_one_deadline_across_http_requests() {
+        let delay = Duration::from_millis(300);
+        let slow = |(_, reply): (Duration, String)| (delay, reply);
+        let (url, _server) = serve(vec![
+            slow(reply(
+                "200 OK",
+                &["Content-Type: application/json"],
+                INITIALIZED,
+            )),
+            reply("202 Accepted", &[], ""),
+            slow(reply(
+                "200 OK",
+                &["Content-Type: application/json"],
+                r#"{"jsonrpc":"2.0","id":2,"result":{"tools":[]}}"#,
+            )),
+        ]);
+
+        // Each reply alone fits in the timeout, both together do not.
+        let error = check_url(&url, Duration::from_millis(500)).err().unwrap();
+
+        assert!(
+            format!("{:#}", error).starts_with("tools/list failed"),
+            "{:#}",
+            error
+        );
+    }
+}
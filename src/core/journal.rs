@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::utils::fs::{symlink_atomic, write_atomic};
+
+/// What a path held before the deploy first changed it.
+enum PreviousState {
+    Missing,
+    File {
+        content: Vec<u8>,
+        permissions: fs::Permissions,
+    },
+    Link(PathBuf),
+}
+
+/// Remembers the original state of every file a deploy changes, so a failed deploy can put
+/// the workspace back the way it found it.
+pub(crate) struct DeployJournal {
+    entries: Vec<(PathBuf, PreviousState)>,
+    recorded: HashSet<PathBuf>,
+    created_dirs: Vec<PathBuf>,
+}
+
+impl DeployJournal {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            recorded: HashSet::new(),
+            created_dirs: Vec::new(),
+        }
+    }
+
+    /// Captures `path` before it is written, linked or removed. Only the first call for a
+    /// path counts, later ones would capture the deploy's own changes.
+    pub fn record(&mut self, path: &Path) -> Result<()> {
+        if !self.recorded.insert(path.to_path_buf()) {
+            return Ok(());
+        }
+
+        let state = match fs::symlink_metadata(path) {
+            Err(_) => PreviousState::Missing,
+            Ok(metadata) if metadata.file_type().is_symlink() => PreviousState::Link(
+                fs::read_link(path).context(format!("failed to read {}", path.display()))?,
+            ),
+            Ok(metadata) if metadata.is_file() => PreviousState::File {
+                content: fs::read(path).context(format!("failed to read {}", path.display()))?,
+                permissions: metadata.permissions(),
+            },
+            Ok(_) => bail!("{} is not a file", path.display()),
+        };
+
+        self.entries.push((path.to_path_buf(), state));
+        Ok(())
+    }
+
+    /// Creates `dir` and its missing parents, remembering which ones didn't exist.
+    pub fn create_dir_all(&mut self, dir: &Path) -> Result<()> {
+        let missing: Vec<PathBuf> = dir
+            .ancestors()
+            .take_while(|ancestor| !ancestor.exists())
+            .map(Path::to_path_buf)
+            .collect();
+
+        fs::create_dir_all(dir).context(format!("failed to create directory {}", dir.display()))?;
+
+        // Outermost first, so a rollback walking backwards removes children before parents.
+        self.created_dirs.extend(missing.into_iter().rev());
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Restores every recorded file in reverse order and removes the directories the deploy
+    /// created. Keeps going past failures and reports them all at the end.
+    pub fn rollback(self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        for (path, state) in self.entries.iter().rev() {
+            if let Err(e) = restore(path, state) {
+                errors.push(format!("{}: {:#}", path.display(), e));
+            }
+        }
+
+        for dir in self.created_dirs.iter().rev() {
+            // Left alone when something else put files there in the meantime.
+            let _ = fs::remove_dir(dir);
+        }
+
+        if !errors.is_empty() {
+            bail!(
+                "failed to restore {} file(s):\n{}",
+                errors.len(),
+                errors
+                    .iter()
+                    .map(|error| format!("      - {}", error))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn restore(path: &Path, state: &PreviousState) -> Result<()> {
+    match state {
+        PreviousState::Missing => match fs::symlink_metadata(path) {
+            Ok(_) => fs::remove_file(path).context("failed to remove"),
+            Err(_) => Ok(()),
+        },
+        PreviousState::File {
+            content,
+            permissions,
+        } => {
+            write_atomic(path, content)?;
+            fs::set_permissions(path, permissions.clone()).context("failed to set permissions")
+        }
+        PreviousState::Link(target) => symlink_atomic(target, path),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn rolls_back_files_links_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("script.sh");
+        let link = dir.path().join("link.md");
+        let created = dir.path().join("new/nested");
+        fs::write(&file, b"#!/bin/sh\n\xff").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("script.sh", &link).unwrap();
+
+        let mut journal = DeployJournal::new();
+        journal.record(&file).unwrap();
+        fs::write(&file, "changed").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o644)).unwrap();
+        // Only the first record of a path counts.
+        journal.record(&file).unwrap();
+
+        journal.record(&link).unwrap();
+        fs::remove_file(&link).unwrap();
+        fs::write(&link, "a file now").unwrap();
+
+        journal.create_dir_all(&created).unwrap();
+        journal.record(&created.join("rule.md")).unwrap();
+        fs::write(created.join("rule.md"), "new").unwrap();
+
+        assert_eq!(journal.len(), 3);
+        journal.rollback().unwrap();
+
+        assert_eq!(fs::read(&file).unwrap(), b"#!/bin/sh\n\xff");
+        assert_eq!(
+            fs::metadata(&file).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("script.sh"));
+        assert!(!dir.path().join("new").exists());
+    }
+}
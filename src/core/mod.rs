@@ -1 +1,13 @@
-mod template;
+pub(crate) mod backup;
+pub(crate) mod deploy;
+pub(crate) mod deployer;
+pub(crate) mod exec;
+pub(crate) mod handshake;
+pub(crate) mod journal;
+pub(crate) mod packages;
+pub(crate) mod plan;
+pub(crate) mod renderer;
+pub(crate) mod roots;
+pub(crate) mod scope;
+pub(crate) mod starter;
+pub(crate) mod template;
@@ -0,0 +1,583 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::constants::dir::{COMMANDS_DIR, INSTRUCTIONS_DIR, ROOT_DIR};
+use crate::constants::file::MCP_FILE;
+use crate::schema::command::Command;
+use crate::schema::config::AppConfig;
+use crate::schema::instruction::Instruction;
+use crate::schema::mcp::McpConfig;
+use crate::schema::package::{LockedPackage, PackageLock};
+use crate::utils::did_you_mean;
+use crate::utils::git::run_git;
+use crate::utils::path::{get_packages_dir, get_packages_lock_file, get_workspace_dir};
+
+/// A package available on disk, whose sources are merged into the workspace's own.
+pub(crate) struct Package {
+    pub name: String,
+    /// Directory holding the package's `commands/`, `mcp.jsonc` and `instructions/`.
+    pub root: PathBuf,
+}
+
+impl Package {
+    fn new(name: &str, dir: PathBuf) -> Self {
+        // A repository may keep its sources under `.dotagents` next to other files.
+        let root = match dir.join(ROOT_DIR).is_dir() {
+            true => dir.join(ROOT_DIR),
+            false => dir,
+        };
+
+        Self {
+            name: name.to_string(),
+            root,
+        }
+    }
+
+    fn commands(&self) -> Result<Vec<Command>> {
+        let dir = self.root.join(COMMANDS_DIR);
+
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        Command::from_dir(&dir).context(format!("failed to load commands of package {}", self.name))
+    }
+
+    fn mcp(&self) -> Result<Option<McpConfig>> {
+        let path = self.root.join(MCP_FILE);
+
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        McpConfig::from_file(&path).map(Some).context(format!(
+            "failed to load mcp config of package {}",
+            self.name
+        ))
+    }
+
+    fn instruction(&self) -> Result<Option<Instruction>> {
+        let dir = self.root.join(INSTRUCTIONS_DIR);
+
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+
+        Instruction::from_dir(&dir).map(Some).context(format!(
+            "failed to load instructions of package {}",
+            self.name
+        ))
+    }
+}
+
+/// Makes every package of `config` available, git packages at the commit of the lockfile.
+/// Git packages missing from the lockfile, or whose `git` or `rev` changed since, are
+/// resolved and locked.
+pub(crate) fn sync_packages(config: &AppConfig) -> Result<()> {
+    let lock = sync(config, |_| false)?;
+
+    for locked in &lock.packages {
+        log::debug!("Checked out {} at {}", locked.name, short(&locked.revision));
+    }
+
+    Ok(())
+}
+
+/// The packages of `config` as the last sync left them. Nothing is cloned or locked here, so
+/// git packages whose checkout doesn't match the lockfile ask for a sync instead.
+pub(crate) fn load_packages(config: &AppConfig) -> Result<Vec<Package>> {
+    if config.packages.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let workspace_dir = get_workspace_dir()?;
+    let checkouts = get_packages_dir()?;
+    let lock =
+        PackageLock::from_file(&get_packages_lock_file()?).context("failed to load lockfile")?;
+    let mut packages = Vec::new();
+    let mut unsynced = Vec::new();
+
+    for (name, source) in &config.packages {
+        let dir = match (&source.path, &source.git) {
+            (Some(path), None) => local_dir(name, &workspace_dir.join(path))?,
+            (None, Some(git)) => {
+                let dir = checkouts.join(name);
+                let synced = lock
+                    .get(name)
+                    .filter(|locked| locked.git == *git && locked.rev == source.rev)
+                    .is_some_and(|locked| {
+                        dir.join(".git").is_dir()
+                            && run_git(Some(&dir), &["rev-parse", "HEAD"])
+                                .is_ok_and(|head| head == locked.revision)
+                    });
+
+                if !synced {
+                    unsynced.push(name.as_str());
+                    continue;
+                }
+
+                dir
+            }
+            _ => bail!("package '{}' needs exactly one of `path` or `git`", name),
+        };
+
+        packages.push(Package::new(name, dir));
+    }
+
+    if !unsynced.is_empty() {
+        bail!(
+            "package(s) {} are not checked out as config.toml asks, run `dotagents packages sync`",
+            unsynced.join(", ")
+        );
+    }
+
+    Ok(packages)
+}
+
+/// Resolves the `rev` of the named git packages again, or of every one when `names` is
+/// empty, and locks the commits they point at now.
+pub(crate) fn update_packages(config: &AppConfig, names: &[String]) -> Result<()> {
+    for name in names {
+        if config.packages.contains_key(name) {
+            continue;
+        }
+
+        match did_you_mean(name, config.packages.keys().map(String::as_str)) {
+            Some(suggestion) => bail!("Unknown package '{}', did you mean '{}'?", name, suggestion),
+            None => bail!("Unknown package '{}'", name),
+        }
+    }
+
+    let selected = |name: &str| names.is_empty() || names.iter().any(|n| n == name);
+    let previous = PackageLock::from_file(&get_packages_lock_file()?)?;
+    let lock = sync(config, selected)?;
+
+    for (name, source) in &config.packages {
+        if !selected(name) {
+            continue;
+        }
+
+        if source.git.is_none() {
+            if !names.is_empty() {
+                log::warn!(
+                    "Package {} is a local path, there is nothing to update",
+                    name
+                );
+            }
+            continue;
+        }
+
+        let before = previous.get(name).map(|locked| short(&locked.revision));
+        let Some(after) = lock.get(name).map(|locked| short(&locked.revision)) else {
+            continue;
+        };
+
+        match before {
+            Some(before) if before == after => log::info!("{} is up to date at {}", name, after),
+            Some(before) => log::info!("Updated {} from {} to {}", name, before, after),
+            None => log::info!("Locked {} at {}", name, after),
+        }
+    }
+
+    Ok(())
+}
+
+/// Brings the checkouts of `config` in line with the lockfile, resolving the packages missing
+/// from it and those `refresh` asks for, and returns the new lockfile.
+fn sync(config: &AppConfig, refresh: impl Fn(&str) -> bool) -> Result<PackageLock> {
+    let lock_file = get_packages_lock_file()?;
+    let previous = PackageLock::from_file(&lock_file).context("failed to load lockfile")?;
+
+    if config.packages.is_empty() && previous.packages.is_empty() {
+        return Ok(previous);
+    }
+
+    let workspace_dir = get_workspace_dir()?;
+    let checkouts = get_packages_dir()?;
+    let mut lock = PackageLock::default();
+
+    for (name, source) in &config.packages {
+        match (&source.path, &source.git) {
+            (Some(path), None) => {
+                local_dir(name, &workspace_dir.join(path))?;
+            }
+            (None, Some(git)) => {
+                let dir = checkouts.join(name);
+                let locked = previous.get(name).filter(|locked| {
+                    locked.git == *git && locked.rev == source.rev && !refresh(name)
+                });
+
+                let revision = checkout(
+                    &dir,
+                    &clone_url(git, &workspace_dir),
+                    source.rev.as_deref(),
+                    locked.map(|locked| locked.revision.as_str()),
+                )
+                .context(format!("failed to check out package {}", name))?;
+
+                lock.packages.push(LockedPackage {
+                    name: name.clone(),
+                    git: git.clone(),
+                    rev: source.rev.clone(),
+                    revision,
+                });
+            }
+            _ => bail!("package '{}' needs exactly one of `path` or `git`", name),
+        }
+    }
+
+    if lock.packages.is_empty() && lock_file.exists() {
+        fs::remove_file(&lock_file).context("failed to remove lockfile")?;
+    } else if lock != previous {
+        lock.save(&lock_file).context("failed to write lockfile")?;
+    }
+
+    Ok(lock)
+}
+
+/// `dir` of the local package `name`, which must exist.
+fn local_dir(name: &str, dir: &Path) -> Result<PathBuf> {
+    if !dir.is_dir() {
+        bail!(
+            "package '{}' points to {}, which is not a directory",
+            name,
+            dir.display()
+        );
+    }
+
+    Ok(dir.to_path_buf())
+}
+
+/// Local repositories are cloned by their absolute path, so a clone can be matched against
+/// its source from any directory.
+fn clone_url(git: &str, workspace_dir: &Path) -> String {
+    match fs::canonicalize(workspace_dir.join(git)) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(_) => git.to_string(),
+    }
+}
+
+/// Checks out `locked` in the clone of `url` at `dir`, or what `rev` points at when nothing
+/// is locked, and returns the commit checked out.
+fn checkout(dir: &Path, url: &str, rev: Option<&str>, locked: Option<&str>) -> Result<String> {
+    let cloned = dir.join(".git").is_dir()
+        && run_git(Some(dir), &["remote", "get-url", "origin"]).is_ok_and(|origin| origin == url);
+
+    if !cloned {
+        let _ = fs::remove_dir_all(dir);
+
+        if let Some(parent) = dir.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("failed to create directory {}", parent.display()))?;
+        }
+
+        run_git(
+            None,
+            &["clone", "--quiet", "--", url, &dir.to_string_lossy()],
+        )
+        .context(format!("failed to clone {}", url))?;
+    }
+
+    let revision = match locked {
+        Some(revision) => {
+            let commit = format!("{}^{{commit}}", revision);
+
+            if run_git(Some(dir), &["cat-file", "-e", &commit]).is_err() {
+                fetch(dir)?;
+            }
+
+            revision.to_string()
+        }
+        None => {
+            if cloned {
+                fetch(dir)?;
+            }
+
+            resolve_rev(dir, rev)?
+        }
+    };
+
+    if run_git(Some(dir), &["rev-parse", "HEAD"]).ok().as_deref() != Some(revision.as_str()) {
+        run_git(
+            Some(dir),
+            &["checkout", "--quiet", "--force", "--detach", &revision],
+        )
+        .context(format!("failed to check out {}", revision))?;
+    }
+
+    Ok(revision)
+}
+
+fn fetch(dir: &Path) -> Result<()> {
+    run_git(
+        Some(dir),
+        &["fetch", "--quiet", "--tags", "--force", "origin"],
+    )
+    .context("failed to fetch")?;
+
+    Ok(())
+}
+
+/// The commit `rev` names in the clone at `dir`, looked up as a branch of the remote first
+/// and then as a tag or commit. Without `rev`, the default branch of the remote.
+fn resolve_rev(dir: &Path, rev: Option<&str>) -> Result<String> {
+    let candidates = match rev {
+        Some(rev) => vec![format!("origin/{}", rev), rev.to_string()],
+        None => vec!["origin/HEAD".to_string()],
+    };
+
+    for candidate in &candidates {
+        let commit = format!("{}^{{commit}}", candidate);
+
+        if let Ok(revision) = run_git(Some(dir), &["rev-parse", "--verify", "--quiet", &commit]) {
+            return Ok(revision);
+        }
+    }
+
+    match rev {
+        Some(rev) => bail!("'{}' is not a branch, tag or commit of the repository", rev),
+        None => bail!("the repository has no default branch"),
+    }
+}
+
+fn short(revision: &str) -> &str {
+    &revision[..revision.len().min(7)]
+}
+
+/// Adds what packages define to the workspace's own `items`, matched by `key`. Definitions
+/// of the workspace win over those of packages, while two packages defining the same name
+/// fail the merge, as neither can be preferred over the other.
+fn merge<T>(
+    kind: &str,
+    items: Vec<T>,
+    packages: Vec<(&str, Vec<T>)>,
+    key: impl Fn(&T) -> String,
+) -> Result<Vec<T>> {
+    let workspace: HashSet<String> = items.iter().map(&key).collect();
+    let mut defined_by = HashMap::<String, &str>::new();
+    let mut merged = items;
+    let mut errors = Vec::new();
+
+    for (package, package_items) in packages {
+        for item in package_items {
+            let name = key(&item);
+
+            if workspace.contains(&name) {
+                log::debug!(
+                    "Using the workspace's {} '{}' over the one of package {}",
+                    kind,
+                    name,
+                    package
+                );
+                continue;
+            }
+
+            if let Some(first) = defined_by.get(&name) {
+                errors.push(format!(
+                    "{} '{}' is defined by packages {} and {}",
+                    kind, name, first, package
+                ));
+                continue;
+            }
+
+            defined_by.insert(name, package);
+            merged.push(item);
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "found {} conflicting {}(s) in packages, define them in the workspace to choose one:\n{}",
+            errors.len(),
+            kind,
+            errors
+                .iter()
+                .map(|error| format!("      - {}", error))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    Ok(merged)
+}
+
+pub(crate) fn merge_commands(commands: Vec<Command>, packages: &[Package]) -> Result<Vec<Command>> {
+    let package_commands = packages
+        .iter()
+        .map(|package| Ok((package.name.as_str(), package.commands()?)))
+        .collect::<Result<_>>()?;
+
+    merge(
+        "command",
+        commands,
+        package_commands,
+        Command::qualified_name,
+    )
+}
+
+pub(crate) fn merge_servers(mut mcp: McpConfig, packages: &[Package]) -> Result<McpConfig> {
+    let mut package_servers = Vec::new();
+
+    for package in packages {
+        if let Some(package_mcp) = package.mcp()? {
+            package_servers.push((
+                package.name.as_str(),
+                package_mcp.servers.into_iter().collect(),
+            ));
+        }
+    }
+
+    let servers = mcp.servers.drain().collect();
+    mcp.servers = merge("server", servers, package_servers, |(name, _)| name.clone())?
+        .into_iter()
+        .collect();
+
+    Ok(mcp)
+}
+
+pub(crate) fn merge_instruction(
+    instruction: Instruction,
+    packages: &[Package],
+) -> Result<Instruction> {
+    let mut package_fragments = Vec::new();
+
+    for package in packages {
+        if let Some(package_instruction) = package.instruction()? {
+            package_fragments.push((package.name.as_str(), package_instruction.fragments));
+        }
+    }
+
+    let mut fragments = merge(
+        "instruction",
+        instruction.fragments,
+        package_fragments,
+        |fragment| fragment.name.clone(),
+    )?;
+
+    // Stable, so fragments sharing an order keep the workspace's before those of packages.
+    fragments.sort_by_key(|fragment| fragment.metadata.order);
+
+    Ok(Instruction { fragments })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::package::PackageSource;
+    use crate::utils::testing::TestWorkspace;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let identity = [
+            "-c",
+            "user.name=dotagents",
+            "-c",
+            "user.email=dotagents@localhost",
+        ];
+        run_git(Some(dir), &[&identity[..], args].concat()).unwrap();
+    }
+
+    /// A workspace with a local package and a git one, committed in `library`.
+    fn workspace() -> (TestWorkspace, AppConfig) {
+        let workspace = TestWorkspace::new();
+        workspace.write(
+            "team/commands/local.md",
+            "---\ndescription: Local\n---\nLocal.\n",
+        );
+        workspace.write(
+            &format!("library/{}/commands/shared.md", ROOT_DIR),
+            "---\ndescription: Shared\n---\nShared.\n",
+        );
+
+        let library = workspace.path().join("library");
+        git(&library, &["init", "--quiet"]);
+        git(&library, &["add", "-A"]);
+        git(&library, &["commit", "--quiet", "-m", "init"]);
+
+        let mut config = AppConfig::new();
+        config.packages.insert(
+            "team".into(),
+            PackageSource {
+                path: Some("team".into()),
+                git: None,
+                rev: None,
+            },
+        );
+        config.packages.insert(
+            "shared".into(),
+            PackageSource {
+                path: None,
+                git: Some("library".into()),
+                rev: None,
+            },
+        );
+
+        (workspace, config)
+    }
+
+    fn names(packages: &[Package]) -> Vec<(&str, PathBuf)> {
+        packages
+            .iter()
+            .map(|package| (package.name.as_str(), package.root.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn loads_only_what_a_sync_checked_out() {
+        let (workspace, config) = workspace();
+
+        workspace.run(|| {
+            let error = load_packages(&config).err().unwrap();
+            assert_eq!(
+                error.to_string(),
+                "package(s) shared are not checked out as config.toml asks, run `dotagents packages sync`"
+            );
+
+            // Loading left no trace.
+            assert!(!get_packages_lock_file().unwrap().exists());
+            assert!(!get_packages_dir().unwrap().exists());
+
+            sync_packages(&config).unwrap();
+            let packages = load_packages(&config).unwrap();
+            let checkout = get_packages_dir().unwrap().join("shared");
+
+            assert_eq!(
+                names(&packages),
+                [
+                    ("shared", checkout.join(ROOT_DIR)),
+                    ("team", workspace.path().join("team")),
+                ]
+            );
+            assert_eq!(
+                merge_commands(Vec::new(), &packages)
+                    .unwrap()
+                    .iter()
+                    .map(Command::qualified_name)
+                    .collect::<Vec<_>>(),
+                ["shared", "local"]
+            );
+
+            // A removed checkout needs another sync.
+            fs::remove_dir_all(&checkout).unwrap();
+            assert!(load_packages(&config).is_err());
+        });
+    }
+
+    #[test]
+    fn asks_for_a_sync_when_the_config_changed() {
+        let (workspace, mut config) = workspace();
+
+        workspace.run(|| {
+            sync_packages(&config).unwrap();
+
+            if let Some(source) = config.packages.get_mut("shared") {
+                source.rev = Some("main".into());
+            }
+            let error = load_packages(&config).err().unwrap();
+
+            assert!(error.to_string().contains("run `dotagents packages sync`"));
+        });
+    }
+}
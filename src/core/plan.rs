@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Value, json};
+
+use super::exec::ExecRenderer;
+use super::handshake::{check_servers_parallel, expand_env};
+use super::packages::{Package, load_packages, merge_commands, merge_instruction, merge_servers};
+use super::renderer::{ProviderRenderer, RenderInput, RendererRegistry};
+use super::scope::DeployScope;
+use crate::constants::features::{COMMANDS_FEATURE, FEATURES, INSTRUCTION_FEATURE, MCP_FEATURE};
+use crate::constants::variables::{AGENT_KIND, AGENT_NAME};
+use crate::schema::command::Command;
+use crate::schema::common::{FeatureItem, Target};
+use crate::schema::config::{AppConfig, ConfigAgentSettings};
+use crate::schema::instruction::Instruction;
+use crate::schema::mcp::McpConfig;
+use crate::schema::variable::VariableResolver;
+use crate::templates::helpers::{RenderType, Templater};
+use crate::utils::path::get_workspace_dir;
+use crate::utils::{did_you_mean, merge_json};
+
+/// How long a server may take to answer when its tools are discovered during a deploy.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A single file produced for a provider.
+#[derive(Debug)]
+pub struct PlannedOutput {
+    /// Name of the source item, e.g. a command, used to scope deploys and prune outputs.
+    pub item: Option<String>,
+    pub path: PathBuf,
+    /// For linked outputs, the content of the source.
+    pub content: String,
+    pub strategy: OutputStrategy,
+}
+
+/// How an output reaches its path.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputStrategy {
+    /// Write the content, backing up files dotagents didn't write.
+    Write,
+    /// Link the path to a source file, so edits show up without a deploy.
+    Link(PathBuf),
+}
+
+/// Everything one provider produces for one feature.
+#[derive(Debug)]
+pub struct PlannedUnit {
+    pub target: Target,
+    pub provider: String,
+    pub feature: &'static str,
+    pub settings: ConfigAgentSettings,
+    pub outputs: Vec<PlannedOutput>,
+}
+
+#[derive(Debug, Default)]
+pub struct DeployPlan {
+    pub units: Vec<PlannedUnit>,
+}
+
+/// Source data of a feature, split into the items each rendered output is built from.
+pub(crate) enum FeatureSource {
+    Commands(Vec<Command>),
+    Mcp(McpConfig),
+    Instruction(Instruction),
+}
+
+impl FeatureSource {
+    /// Loads `feature` from the workspace, with what `packages` define merged in.
+    pub fn load(feature: &str, scope: &DeployScope, packages: &[Package]) -> Result<Self> {
+        match feature {
+            COMMANDS_FEATURE => {
+                let commands = Command::from_application().context("failed to load commands")?;
+                let commands = merge_commands(commands, packages)?;
+                Self::check_commands(&commands, scope)?;
+
+                Ok(Self::Commands(
+                    commands
+                        .into_iter()
+                        .filter(|command| scope.includes_command(&command.qualified_name()))
+                        .collect(),
+                ))
+            }
+            MCP_FEATURE => {
+                let mcp = McpConfig::from_application().context("failed to load mcp config")?;
+                Ok(Self::Mcp(merge_servers(mcp, packages)?))
+            }
+            INSTRUCTION_FEATURE => {
+                let instruction =
+                    Instruction::from_application().context("failed to load instructions")?;
+                Ok(Self::Instruction(merge_instruction(instruction, packages)?))
+            }
+            _ => bail!("unknown feature {}", feature),
+        }
+    }
+
+    fn check_commands(commands: &[Command], scope: &DeployScope) -> Result<()> {
+        let names: Vec<String> = commands.iter().map(Command::qualified_name).collect();
+
+        for name in &scope.commands {
+            if names.contains(name) {
+                continue;
+            }
+
+            match did_you_mean(name, names.iter().map(String::as_str)) {
+                Some(suggestion) => {
+                    bail!("Unknown command '{}', did you mean '{}'?", name, suggestion)
+                }
+                None => bail!("Unknown command '{}'", name),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Template data of every item, shaped for `provider` when one is given. Sources written as
+    /// templates themselves are rendered with `context`.
+    pub fn items(
+        &self,
+        provider: Option<(&str, &ConfigAgentSettings)>,
+        templater: &Templater,
+        context: &Value,
+    ) -> Result<Vec<FeatureItem>> {
+        let items = match self {
+            Self::Commands(commands) => commands
+                .iter()
+                .map(|command| {
+                    Ok(FeatureItem {
+                        id: Some(command.qualified_name()),
+                        data: json!({ "command": command.to_template_value(provider)? }),
+                        source: command.source.clone(),
+                    })
+                })
+                .collect::<Result<_>>()?,
+            Self::Mcp(mcp) => vec![FeatureItem {
+                id: None,
+                data: json!({ "mcp": mcp.to_template_value(provider)? }),
+                source: None,
+            }],
+            Self::Instruction(instruction) => instruction
+                .render(|text| {
+                    templater.render_template(RenderType::Content(text.to_string()), Some(context))
+                })?
+                .items(provider)?,
+        };
+
+        Ok(items)
+    }
+}
+
+impl DeployPlan {
+    /// Renders every output selected by `scope` without touching the filesystem.
+    /// With `discover_tools`, servers with tool filters are started to learn their tools, so
+    /// patterns can be expanded and checked.
+    pub fn build(
+        templater: &Templater,
+        config: &AppConfig,
+        scope: &DeployScope,
+        renderers: &RendererRegistry,
+        discover_tools: bool,
+    ) -> Result<Self> {
+        let workspace_dir = get_workspace_dir()?;
+        let resolver = VariableResolver::new(&workspace_dir);
+        let variables = resolver
+            .resolve(config.variables.as_ref())
+            .context("failed to resolve variables")?;
+        let packages = load_packages(config).context("failed to load packages")?;
+        let mut units = Vec::new();
+        let mut errors = Vec::new();
+
+        for feature in FEATURES {
+            if !scope.includes_feature(feature) {
+                continue;
+            }
+
+            let providers: Vec<_> = config
+                .get_feature_providers(feature)
+                .into_iter()
+                .filter(|(_, name, _)| scope.includes_provider(name))
+                .collect();
+
+            if providers.is_empty() {
+                continue;
+            }
+
+            let mut source = FeatureSource::load(feature, scope, &packages)?;
+
+            if let FeatureSource::Mcp(mcp) = &mut source
+                && discover_tools
+            {
+                discover_server_tools(mcp, templater, &variables, &workspace_dir)
+                    .context("failed to discover mcp tools")?;
+            }
+
+            for (target, provider, settings) in providers {
+                let outputs = provider_context(target, &provider, &settings, &resolver)
+                    .and_then(|provider_context| {
+                        let context = merge_json(&variables, &provider_context);
+                        let items =
+                            source.items(Some((&provider, &settings)), templater, &context)?;
+
+                        // A renderer program set in the config wins over registered ones.
+                        let exec;
+                        let renderer = match &settings.renderer {
+                            Some(spec) => {
+                                exec =
+                                    ExecRenderer::from_settings(spec, &settings, &workspace_dir)?;
+                                &exec as &dyn ProviderRenderer
+                            }
+                            None => renderers.get(&provider),
+                        };
+
+                        let outputs = renderer.render(&RenderInput {
+                            provider: &provider,
+                            target,
+                            feature,
+                            settings: &settings,
+                            items: &items,
+                            variables: &context,
+                            workspace_dir: &workspace_dir,
+                            templater,
+                        })?;
+
+                        check_unique_paths(&outputs)?;
+                        Ok(outputs)
+                    })
+                    .context(format!("failed to render {} for {}", feature, provider));
+
+                match outputs {
+                    Ok(outputs) => units.push(PlannedUnit {
+                        target,
+                        provider,
+                        feature,
+                        settings,
+                        outputs,
+                    }),
+                    Err(e) => errors.push(format!("{:#}", e)),
+                }
+            }
+        }
+
+        // Every failure is reported at once, as nothing gets written unless all outputs render.
+        if !errors.is_empty() {
+            bail!(
+                "{} of {} provider feature(s) failed to render:\n{}",
+                errors.len(),
+                errors.len() + units.len(),
+                errors
+                    .iter()
+                    .map(|error| format!("      - {}", error))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
+        Ok(Self { units })
+    }
+}
+
+/// Fails when two outputs of a unit share a path, e.g. `git/commit` and `git-commit` once a
+/// provider joins namespaces with `-`, as the last one would silently win.
+fn check_unique_paths(outputs: &[PlannedOutput]) -> Result<()> {
+    let mut written_by = HashMap::<&Path, Option<&str>>::new();
+    let mut errors = Vec::new();
+
+    for output in outputs {
+        let item = output.item.as_deref();
+
+        match written_by.get(output.path.as_path()) {
+            Some(first) => errors.push(format!(
+                "{} is rendered from both {} and {}",
+                output.path.display(),
+                first.unwrap_or("the feature"),
+                item.unwrap_or("the feature")
+            )),
+            None => {
+                written_by.insert(&output.path, item);
+            }
+        }
+    }
+
+    // Nested one level deeper, as it is reported within the failures of the whole plan.
+    if !errors.is_empty() {
+        bail!(
+            "found {} conflicting output path(s):\n{}",
+            errors.len(),
+            errors
+                .iter()
+                .map(|error| format!("        - {}", error))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+/// Fills in the tools of every enabled server with tool filters and checks their patterns.
+fn discover_server_tools(
+    mcp: &mut McpConfig,
+    templater: &Templater,
+    variables: &Value,
+    workspace_dir: &Path,
+) -> Result<()> {
+    let resolve = |value: &str| {
+        let rendered = templater
+            .render_template(RenderType::Content(value.to_string()), Some(variables))
+            .context(format!("failed to render '{}'", value))?;
+        expand_env(&rendered)
+    };
+
+    let mut servers: Vec<_> = mcp
+        .servers
+        .iter()
+        .filter(|(_, server)| !server.is_disabled() && server.has_tool_filters())
+        .map(|(name, server)| (name.as_str(), server))
+        .collect();
+    servers.sort_by_key(|(name, _)| *name);
+
+    let mut discovered = HashMap::new();
+
+    for (name, result) in
+        check_servers_parallel(&servers, &resolve, workspace_dir, DISCOVERY_TIMEOUT)
+    {
+        let report = result.context(format!("failed to list the tools of server {}", name))?;
+        discovered.insert(name.to_string(), report.tools);
+    }
+
+    mcp.discovered_tools = discovered;
+    mcp.check_tool_patterns()
+}
+
+/// Variables of a provider along with the `agent_name` and `agent_kind` describing it.
+pub(crate) fn provider_context(
+    target: Target,
+    provider: &str,
+    settings: &ConfigAgentSettings,
+    resolver: &VariableResolver,
+) -> Result<Value> {
+    let variables = resolver
+        .resolve(settings.variables.as_ref())
+        .context("failed to resolve provider variables")?;
+
+    Ok(merge_json(
+        &variables,
+        &json!({ AGENT_NAME: provider, AGENT_KIND: target.as_str() }),
+    ))
+}
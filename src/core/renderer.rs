@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+
+use super::plan::{OutputStrategy, PlannedOutput};
+use crate::schema::common::{DeployMode, FeatureItem, Target};
+use crate::schema::config::ConfigAgentSettings;
+use crate::templates::helpers::{RenderType, Templater, load_template};
+use crate::utils::merge_json;
+
+/// Everything a renderer gets to produce the outputs of one feature for one provider.
+pub struct RenderInput<'a> {
+    pub provider: &'a str,
+    pub target: Target,
+    pub feature: &'static str,
+    pub settings: &'a ConfigAgentSettings,
+    /// Data of the feature, one item per output a template provider would render.
+    pub items: &'a [FeatureItem],
+    /// Resolved variables of the workspace and the provider, `agent_name` and `agent_kind`
+    /// included.
+    pub variables: &'a Value,
+    pub workspace_dir: &'a Path,
+    pub templater: &'a Templater,
+}
+
+impl RenderInput<'_> {
+    /// What `path` holds before the deploy, for renderers merging into files they don't own.
+    pub fn existing_content(&self, path: &Path) -> Result<Option<String>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(format!("failed to read {}", path.display())),
+        }
+    }
+}
+
+/// Turns the sources of a feature into the files a provider reads. Deploys check, write,
+/// back up and prune whatever a renderer returns the same way for every provider.
+pub trait ProviderRenderer {
+    fn render(&self, input: &RenderInput) -> Result<Vec<PlannedOutput>>;
+}
+
+/// Renders the `template` of a provider into its `target` once per item, or copies and links
+/// the source of every item as its `mode` asks. Built-in agents and providers configured in
+/// `config.toml` are rendered with it.
+pub struct TemplateRenderer;
+
+impl ProviderRenderer for TemplateRenderer {
+    fn render(&self, input: &RenderInput) -> Result<Vec<PlannedOutput>> {
+        let RenderInput {
+            provider,
+            settings,
+            templater,
+            ..
+        } = input;
+        let mode = settings.mode.unwrap_or_default();
+
+        let (template, Some(target)) = (&settings.template, &settings.target) else {
+            bail!("provider '{}' needs a target", provider);
+        };
+
+        let template = match (mode, template) {
+            (DeployMode::Template, Some(template)) => Some(load_template(template)?),
+            (DeployMode::Template, None) => {
+                bail!("provider '{}' needs both a template and a target", provider)
+            }
+            _ => None,
+        };
+
+        input
+            .items
+            .iter()
+            .map(|item| {
+                let data = merge_json(input.variables, &item.data);
+                let path = templater
+                    .render_template(RenderType::Content(target.clone()), Some(&data))
+                    .context("failed to render target path")?;
+                let path = input.workspace_dir.join(path.trim());
+
+                if let Some(template) = &template {
+                    let content = templater
+                        .render_template(RenderType::Content(template.clone()), Some(&data))?;
+
+                    return Ok(PlannedOutput {
+                        item: item.id.clone(),
+                        content: normalize_content(&path, content)?,
+                        path,
+                        strategy: OutputStrategy::Write,
+                    });
+                }
+
+                let Some(source) = &item.source else {
+                    bail!(
+                        "{} mode needs one source file per output, {} has none",
+                        mode.as_str(),
+                        item.id.as_deref().unwrap_or("this feature")
+                    );
+                };
+                let content = fs::read_to_string(source)
+                    .context(format!("failed to read {}", source.display()))?;
+
+                Ok(PlannedOutput {
+                    item: item.id.clone(),
+                    path,
+                    content,
+                    strategy: match mode {
+                        DeployMode::Symlink => OutputStrategy::Link(source.clone()),
+                        _ => OutputStrategy::Write,
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+/// Renderers of providers implemented in Rust, by provider name. Providers without one are
+/// rendered from their template. A registered name still needs an entry in `targets` and
+/// under `providers` enabling the features it deploys, and replaces the built-in agent of
+/// the same name.
+#[derive(Default)]
+pub struct RendererRegistry {
+    renderers: HashMap<String, Box<dyn ProviderRenderer>>,
+}
+
+impl RendererRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &mut self,
+        provider: impl Into<String>,
+        renderer: impl ProviderRenderer + 'static,
+    ) {
+        self.renderers.insert(provider.into(), Box::new(renderer));
+    }
+
+    pub fn get(&self, provider: &str) -> &dyn ProviderRenderer {
+        match self.renderers.get(provider) {
+            Some(renderer) => renderer.as_ref(),
+            None => &TemplateRenderer,
+        }
+    }
+}
+
+/// Re-indents JSON outputs so templates don't have to care about whitespace, and rejects
+/// templates that render invalid JSON before anything is written.
+pub(super) fn normalize_content(path: &Path, content: String) -> Result<String> {
+    if path.extension().is_none_or(|ext| ext != "json") {
+        return Ok(content);
+    }
+
+    let value: Value = serde_json::from_str(&content)
+        .context(format!("rendered {} is not valid JSON", path.display()))?;
+    let mut json = serde_json::to_string_pretty(&value).context("failed to format JSON")?;
+    json.push('\n');
+
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::constants::dir::ROOT_DIR;
+    use crate::utils::testing::TestWorkspace;
+
+    /// Renders a single `note.md` saying which renderer wrote it.
+    struct Named(&'static str);
+
+    impl ProviderRenderer for Named {
+        fn render(&self, input: &RenderInput) -> Result<Vec<PlannedOutput>> {
+            Ok(vec![PlannedOutput {
+                item: None,
+                path: input.workspace_dir.join("note.md"),
+                content: self.0.to_string(),
+                strategy: OutputStrategy::Write,
+            }])
+        }
+    }
+
+    /// What the renderer `registry` picks for `provider` writes, rendering one command.
+    fn render(workspace: &TestWorkspace, registry: &RendererRegistry, provider: &str) -> String {
+        let templater = Templater::for_workspace(&workspace.path()).unwrap();
+        let settings = ConfigAgentSettings {
+            template: Some("templates/note.hbs".into()),
+            target: Some("{{ workspace_dir }}/note.md".into()),
+            ..Default::default()
+        };
+        let items = [FeatureItem {
+            id: Some("hello".into()),
+            data: json!({ "command": { "name": "hello" } }),
+            source: None,
+        }];
+        let variables = json!({ "workspace_dir": workspace.path(), "agent_name": provider });
+
+        let outputs = workspace.run(|| {
+            registry.get(provider).render(&RenderInput {
+                provider,
+                target: Target::Cli,
+                feature: "commands",
+                settings: &settings,
+                items: &items,
+                variables: &variables,
+                workspace_dir: &workspace.path(),
+                templater: &templater,
+            })
+        });
+
+        let [output] = outputs.unwrap().try_into().unwrap();
+        assert_eq!(output.path, workspace.path().join("note.md"));
+        output.content
+    }
+
+    #[test]
+    fn looks_up_renderers_by_provider() {
+        let workspace = TestWorkspace::new();
+        workspace.write(
+            &format!("{}/templates/note.hbs", ROOT_DIR),
+            "{{ command.name }} from the template of {{ agent_name }}",
+        );
+        let mut registry = RendererRegistry::new();
+
+        assert_eq!(
+            render(&workspace, &registry, "copilot"),
+            "hello from the template of copilot"
+        );
+
+        registry.register("copilot", Named("first"));
+        assert_eq!(render(&workspace, &registry, "copilot"), "first");
+
+        // Registering a provider again replaces its renderer.
+        registry.register("copilot", Named("second"));
+        assert_eq!(render(&workspace, &registry, "copilot"), "second");
+
+        // Other providers keep their template.
+        assert_eq!(
+            render(&workspace, &registry, "claude"),
+            "hello from the template of claude"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Result, bail};
+
+use super::plan::DeployPlan;
+use crate::schema::config::AppConfig;
+use crate::utils::fs::{normalize_path, resolve_existing};
+use crate::utils::path::{get_config_dir, get_home_dir};
+
+/// Directories a deploy may write to: the workspace, the home and config directories and
+/// the `allowed-roots` of the configuration.
+pub(crate) struct AllowedRoots {
+    workspace: PathBuf,
+    /// Roots as written, for checking the rendered paths.
+    roots: Vec<PathBuf>,
+    /// Roots with their symlinks resolved, for checking where the paths really lead.
+    resolved: Vec<PathBuf>,
+    /// Whether paths outside the roots are only reported.
+    allow_outside: bool,
+}
+
+impl AllowedRoots {
+    pub fn from_config(
+        config: &AppConfig,
+        workspace_dir: &Path,
+        allow_outside: bool,
+    ) -> Result<Self> {
+        let mut roots = vec![
+            workspace_dir.to_path_buf(),
+            get_home_dir()?,
+            get_config_dir()?,
+        ];
+        roots.extend(
+            config
+                .allowed_roots
+                .iter()
+                .map(|root| workspace_dir.join(root.trim())),
+        );
+
+        let roots: Vec<PathBuf> = roots.iter().map(|root| normalize_path(root)).collect();
+        let resolved = roots.iter().map(|root| resolve_existing(root)).collect();
+
+        Ok(Self {
+            workspace: normalize_path(workspace_dir),
+            roots,
+            resolved,
+            allow_outside,
+        })
+    }
+
+    /// Why `path` may not be written, if it may not.
+    fn violation(&self, path: &Path) -> Option<String> {
+        let normalized = normalize_path(path);
+
+        // A '..' leaving the workspace is a template gone wrong more often than not, even when
+        // it lands in another root.
+        if path
+            .components()
+            .any(|component| component == Component::ParentDir)
+            && !normalized.starts_with(&self.workspace)
+        {
+            return Some("escapes the workspace through '..'".to_string());
+        }
+
+        if !self.roots.iter().any(|root| normalized.starts_with(root)) {
+            return Some("is outside the allowed roots".to_string());
+        }
+
+        // Symlinked files are written through, so where the file itself leads matters too.
+        let resolved = resolve_existing(&normalized);
+
+        if !self.resolved.iter().any(|root| resolved.starts_with(root)) {
+            return Some(format!(
+                "leads outside the allowed roots through a symlink to {}",
+                resolved.display()
+            ));
+        }
+
+        None
+    }
+
+    /// Why `path` must be left alone, unless paths outside the roots are allowed. Used for
+    /// paths read back from the cache or backups, which a deploy didn't just render.
+    pub fn refusal(&self, path: &Path) -> Option<String> {
+        let reason = self.violation(path)?;
+
+        if self.allow_outside {
+            log::warn!("Allowing {}, which {}", path.display(), reason);
+            return None;
+        }
+
+        Some(reason)
+    }
+
+    /// Fails when any output of `plan` would be written outside the roots. With
+    /// `allow_outside`, such outputs are only reported.
+    pub fn check_plan(&self, plan: &DeployPlan) -> Result<()> {
+        let violations: Vec<(&Path, String)> = plan
+            .units
+            .iter()
+            .flat_map(|unit| &unit.outputs)
+            .filter_map(|output| Some((output.path.as_path(), self.violation(&output.path)?)))
+            .collect();
+
+        if self.allow_outside {
+            for (path, reason) in &violations {
+                log::warn!("Allowing {}, which {}", path.display(), reason);
+            }
+            return Ok(());
+        }
+
+        if !violations.is_empty() {
+            bail!(
+                "{} output(s) would be written outside the allowed roots, pass --allow-outside to write them anyway:\n{}",
+                violations.len(),
+                violations
+                    .iter()
+                    .map(|(path, reason)| format!("      - {} {}", path.display(), reason))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+    use crate::core::plan::{OutputStrategy, PlannedOutput, PlannedUnit};
+    use crate::schema::common::Target;
+    use crate::schema::config::ConfigAgentSettings;
+
+    /// A workspace with a `shared` sibling allowed by the config and an `outside` one that
+    /// isn't, as `(temp dir, workspace)`.
+    fn layout() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+
+        for sub in ["work", "shared", "outside"] {
+            fs::create_dir(root.join(sub)).unwrap();
+        }
+
+        (dir, root.join("work"))
+    }
+
+    fn roots(workspace: &Path, allow_outside: bool) -> AllowedRoots {
+        let mut config = AppConfig::new();
+        config.allowed_roots = vec!["../shared".into()];
+
+        AllowedRoots::from_config(&config, workspace, allow_outside).unwrap()
+    }
+
+    #[test]
+    fn refuses_parent_dirs_leaving_the_workspace() {
+        let (_dir, work) = layout();
+        let roots = roots(&work, false);
+
+        assert_eq!(roots.refusal(&work.join("a/../AGENTS.md")), None);
+        assert_eq!(
+            roots.refusal(&work.join("../shared/AGENTS.md")).as_deref(),
+            Some("escapes the workspace through '..'")
+        );
+        assert_eq!(
+            roots.refusal(&work.join("../outside/AGENTS.md")).as_deref(),
+            Some("escapes the workspace through '..'")
+        );
+
+        // The same roots written out are fine, or not, on their own.
+        assert_eq!(
+            roots.refusal(&work.with_file_name("shared").join("AGENTS.md")),
+            None
+        );
+        assert_eq!(
+            roots
+                .refusal(&work.with_file_name("outside").join("AGENTS.md"))
+                .as_deref(),
+            Some("is outside the allowed roots")
+        );
+    }
+
+    #[test]
+    fn follows_symlinked_parents_out_of_the_roots() {
+        let (_dir, work) = layout();
+        let outside = work.with_file_name("outside");
+        symlink(&outside, work.join("escape")).unwrap();
+        symlink(work.with_file_name("shared"), work.join("linked")).unwrap();
+        let roots = roots(&work, false);
+
+        let reason = roots.refusal(&work.join("escape/AGENTS.md")).unwrap();
+        assert_eq!(
+            reason,
+            format!(
+                "leads outside the allowed roots through a symlink to {}",
+                outside.join("AGENTS.md").display()
+            )
+        );
+
+        // Paths that don't exist yet are resolved through their existing ancestors.
+        let reason = roots
+            .refusal(&work.join("escape/new/deep/rule.md"))
+            .unwrap();
+        assert!(reason.ends_with(&outside.join("new/deep/rule.md").display().to_string()));
+
+        assert_eq!(roots.refusal(&work.join("linked/new/rule.md")), None);
+    }
+
+    #[test]
+    fn only_reports_paths_outside_the_roots_when_allowed() {
+        let (_dir, work) = layout();
+        let outside = work.with_file_name("outside").join("AGENTS.md");
+        let plan = DeployPlan {
+            units: vec![PlannedUnit {
+                target: Target::Cli,
+                provider: "copilot".into(),
+                feature: "instructions",
+                settings: ConfigAgentSettings::default(),
+                outputs: [work.join("AGENTS.md"), outside.clone()]
+                    .into_iter()
+                    .map(|path| PlannedOutput {
+                        item: None,
+                        path,
+                        content: String::new(),
+                        strategy: OutputStrategy::Write,
+                    })
+                    .collect(),
+            }],
+        };
+
+        let error = roots(&work, false).check_plan(&plan).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "1 output(s) would be written outside the allowed roots, pass --allow-outside to write them anyway:\n      - {} is outside the allowed roots",
+                outside.display()
+            )
+        );
+
+        let allowing = roots(&work, true);
+        assert!(allowing.check_plan(&plan).is_ok());
+        assert_eq!(allowing.refusal(&outside), None);
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+
+use anyhow::{Result, bail};
+
+use crate::constants::features::COMMANDS_FEATURE;
+use crate::schema::common::Target;
+use crate::schema::config::AppConfig;
+use crate::utils::did_you_mean;
+
+/// Restricts a deploy to a subset of providers, features and commands.
+#[derive(Default)]
+pub struct DeployScope {
+    pub providers: HashSet<String>,
+    pub skip_providers: HashSet<String>,
+    pub features: HashSet<String>,
+    pub commands: HashSet<String>,
+}
+
+impl DeployScope {
+    pub fn is_full(&self) -> bool {
+        self.providers.is_empty()
+            && self.skip_providers.is_empty()
+            && self.features.is_empty()
+            && self.commands.is_empty()
+    }
+
+    pub fn includes_provider(&self, name: &str) -> bool {
+        (self.providers.is_empty() || self.providers.contains(name))
+            && !self.skip_providers.contains(name)
+    }
+
+    /// Selecting commands implies the commands feature unless features are given explicitly.
+    pub fn includes_feature(&self, feature: &str) -> bool {
+        if self.features.is_empty() {
+            self.commands.is_empty() || feature == COMMANDS_FEATURE
+        } else {
+            self.features.contains(feature)
+        }
+    }
+
+    pub fn includes_command(&self, name: &str) -> bool {
+        self.commands.is_empty() || self.commands.contains(name)
+    }
+
+    /// Whether an output rendered from `item` of `feature` belongs to this deploy.
+    pub fn includes_item(&self, feature: &str, item: Option<&str>) -> bool {
+        if feature != COMMANDS_FEATURE {
+            return true;
+        }
+
+        item.is_none_or(|name| self.includes_command(name))
+    }
+
+    pub fn validate(&self, config: &AppConfig) -> Result<()> {
+        if !self.commands.is_empty() && !self.includes_feature(COMMANDS_FEATURE) {
+            bail!("--command can only be used when deploying the commands feature");
+        }
+
+        let targeted: Vec<&str> = Target::ALL
+            .into_iter()
+            .filter_map(|target| config.targets.get(target))
+            .flatten()
+            .map(String::as_str)
+            .collect();
+
+        for name in self.providers.iter().chain(&self.skip_providers) {
+            if targeted.contains(&name.as_str()) {
+                continue;
+            }
+
+            match did_you_mean(name, targeted.iter().copied()) {
+                Some(suggestion) => bail!(
+                    "Provider '{}' is not listed in targets, did you mean '{}'?",
+                    name,
+                    suggestion
+                ),
+                None => bail!("Provider '{}' is not listed in targets", name),
+            }
+        }
+
+        Ok(())
+    }
+}
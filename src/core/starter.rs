@@ -0,0 +1,462 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use tempfile::TempDir;
+
+use crate::constants::dir::{CACHE_DIR, ROOT_DIR};
+use crate::constants::file::{STARTER_MANIFEST_FILE, STARTER_ORIGIN_FILE};
+use crate::schema::config::TomlConfig;
+use crate::schema::starter::{StarterManifest, StarterOrigin, StarterVariable};
+use crate::utils::fs::write_atomic;
+use crate::utils::git::run_git;
+use crate::utils::hash_content;
+
+/// A starter `.dotagents` tree, read from a directory or cloned from git.
+pub(crate) struct Starter {
+    pub source: String,
+    /// Commit the starter was cloned at, for git sources.
+    pub revision: Option<String>,
+    pub manifest: StarterManifest,
+    /// Directory holding the files to copy.
+    tree: PathBuf,
+    /// The clone of a git starter, removed once the starter is dropped.
+    _checkout: Option<TempDir>,
+}
+
+/// A file of the starter with its placeholders filled in.
+pub(crate) struct StarterFile {
+    /// Path inside the application directory, `/`-separated.
+    pub path: String,
+    pub content: Vec<u8>,
+}
+
+/// What an update did to the files of a workspace.
+#[derive(Default)]
+pub(crate) struct StarterUpdate {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    /// Files edited in the workspace that the starter changed as well.
+    pub kept: usize,
+}
+
+impl Starter {
+    /// Directories are used as they are, anything else is handed to `git clone`, so URLs
+    /// and bare repositories both work.
+    pub fn fetch(source: &str) -> Result<Self> {
+        let local = Path::new(source);
+
+        let (root, checkout, revision) = if local.is_dir() && !is_bare_repository(local) {
+            (local.to_path_buf(), None, None)
+        } else {
+            let checkout = tempfile::Builder::new()
+                .prefix("dotagents-starter-")
+                .tempdir()
+                .context("failed to create a directory to clone into")?;
+            let dir = checkout.path().to_path_buf();
+
+            // Local clones ignore `--depth` with a warning.
+            let depth: &[&str] = if local.exists() {
+                &[]
+            } else {
+                &["--depth", "1"]
+            };
+            run_git(
+                None,
+                &[
+                    &["clone", "--quiet"],
+                    depth,
+                    &["--", source, &dir.to_string_lossy()],
+                ]
+                .concat(),
+            )
+            .context(format!("failed to clone {}", source))?;
+
+            let revision = run_git(Some(&dir), &["rev-parse", "HEAD"]).ok();
+            (dir, Some(checkout), revision)
+        };
+
+        let manifest_path = root.join(STARTER_MANIFEST_FILE);
+        let manifest = match manifest_path.is_file() {
+            true => fs::read_to_string(&manifest_path)
+                .context(format!("failed to read {}", manifest_path.display()))
+                .and_then(|content| StarterManifest::from_toml(&content))
+                .context(format!("failed to parse {}", manifest_path.display()))?,
+            false => StarterManifest::default(),
+        };
+
+        // A repository may keep the tree under `.dotagents` next to other files.
+        let tree = match root.join(ROOT_DIR).is_dir() {
+            true => root.join(ROOT_DIR),
+            false => root,
+        };
+
+        // Recorded for updates, which may run from another directory.
+        let source = match fs::canonicalize(local) {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(_) => source.to_string(),
+        };
+
+        Ok(Self {
+            source,
+            revision,
+            manifest,
+            tree,
+            _checkout: checkout,
+        })
+    }
+
+    /// Declared variables without a value in `values`.
+    pub fn missing_variables<'a>(
+        &'a self,
+        values: &BTreeMap<String, String>,
+    ) -> Vec<(&'a str, &'a StarterVariable)> {
+        self.manifest
+            .variables
+            .iter()
+            .filter(|(name, _)| !values.contains_key(*name))
+            .map(|(name, variable)| (name.as_str(), variable))
+            .collect()
+    }
+
+    /// Every file of the starter with the `{{ name }}` placeholders of `values` replaced in
+    /// its path and content. Other expressions are left for deploys to render.
+    pub fn render(&self, values: &BTreeMap<String, String>) -> Result<Vec<StarterFile>> {
+        let mut paths = Vec::new();
+        collect_files(&self.tree, &self.tree, &mut paths)?;
+        paths.sort();
+
+        let mut files = Vec::new();
+
+        for relative in paths {
+            let path = fill_placeholders(&relative, values);
+
+            if Path::new(&path)
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_)))
+            {
+                bail!("starter file {} leads outside {}", path, ROOT_DIR);
+            }
+
+            let source = self.tree.join(&relative);
+            let content =
+                fs::read(&source).context(format!("failed to read {}", source.display()))?;
+            let content = match String::from_utf8(content) {
+                Ok(text) => fill_placeholders(&text, values).into_bytes(),
+                Err(e) => e.into_bytes(),
+            };
+
+            files.push(StarterFile { path, content });
+        }
+
+        Ok(files)
+    }
+}
+
+/// Writes the files of a starter into `app_dir`, returning their hashes for the origin.
+pub(crate) fn install(files: &[StarterFile], app_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+
+    for file in files {
+        write_file(app_dir, file)?;
+        hashes.insert(file.path.clone(), hash_content(&file.content));
+    }
+
+    Ok(hashes)
+}
+
+/// Brings the changes of a starter into `app_dir`. Files edited since the starter wrote them
+/// are kept as they are and keep their old hash, so the next update still spots the edit.
+pub(crate) fn update(
+    files: &[StarterFile],
+    app_dir: &Path,
+    origin: &StarterOrigin,
+) -> Result<(BTreeMap<String, String>, StarterUpdate)> {
+    let mut hashes = BTreeMap::new();
+    let mut summary = StarterUpdate::default();
+
+    for file in files {
+        let hash = hash_content(&file.content);
+        let target = app_dir.join(&file.path);
+        let current = fs::read(&target).ok().map(|content| hash_content(&content));
+
+        match (origin.files.get(&file.path), current) {
+            (_, Some(current)) if current == hash => {}
+            (Some(recorded), None) => {
+                log::info!("Not restoring {}, it was removed", file.path);
+                hashes.insert(file.path.clone(), recorded.clone());
+                continue;
+            }
+            (Some(recorded), Some(current)) if *recorded != current => {
+                // Edited locally, which only conflicts when the starter changed it as well.
+                if hash != *recorded {
+                    log::warn!(
+                        "Keeping local changes to {}, the starter changed it too",
+                        file.path
+                    );
+                    summary.kept += 1;
+                }
+                hashes.insert(file.path.clone(), recorded.clone());
+                continue;
+            }
+            (None, Some(_)) => {
+                log::warn!("Keeping {}, the starter now has its own version", file.path);
+                summary.kept += 1;
+                continue;
+            }
+            (Some(_), Some(_)) => {
+                write_file(app_dir, file)?;
+                log::info!("Updated {}", file.path);
+                summary.updated += 1;
+            }
+            (None, None) => {
+                write_file(app_dir, file)?;
+                log::info!("Added {}", file.path);
+                summary.added += 1;
+            }
+        }
+
+        hashes.insert(file.path.clone(), hash);
+    }
+
+    for (path, recorded) in &origin.files {
+        if files.iter().any(|file| file.path == *path) {
+            continue;
+        }
+
+        let target = app_dir.join(path);
+        let Ok(content) = fs::read(&target) else {
+            continue;
+        };
+
+        if hash_content(&content) != *recorded {
+            log::warn!("Keeping {}, it was edited but the starter dropped it", path);
+            continue;
+        }
+
+        fs::remove_file(&target).context(format!("failed to remove {}", target.display()))?;
+        log::info!("Removed {}", path);
+        summary.removed += 1;
+    }
+
+    Ok((hashes, summary))
+}
+
+/// Replaces `{{ name }}` for every `name` in `values`, leaving other expressions untouched.
+fn fill_placeholders(text: &str, values: &BTreeMap<String, String>) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        let name = rest[start + 2..start + end].trim();
+        filled.push_str(&rest[..start]);
+
+        match values.get(name) {
+            Some(value) => filled.push_str(value),
+            None => filled.push_str(&rest[start..start + end + 2]),
+        }
+
+        rest = &rest[start + end + 2..];
+    }
+
+    filled.push_str(rest);
+    filled
+}
+
+fn write_file(app_dir: &Path, file: &StarterFile) -> Result<()> {
+    let target = app_dir.join(&file.path);
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("failed to create directory {}", parent.display()))?;
+    }
+
+    write_atomic(&target, &file.content)
+}
+
+/// Collects the files below `dir` as `/`-separated paths relative to `root`, leaving out
+/// git metadata, the manifest and what belongs to a single workspace.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir).context(format!("failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if matches!(
+            relative.as_str(),
+            ".git" | CACHE_DIR | STARTER_MANIFEST_FILE | STARTER_ORIGIN_FILE
+        ) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+
+    Ok(())
+}
+
+fn is_bare_repository(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let identity = [
+            "-c",
+            "user.name=dotagents",
+            "-c",
+            "user.email=dotagents@localhost",
+        ];
+        run_git(Some(dir), &[&identity[..], args].concat()).unwrap();
+    }
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn read(dir: &Path, path: &str) -> String {
+        fs::read_to_string(dir.join(path)).unwrap()
+    }
+
+    /// Commits everything in `work` and pushes it to the bare repository it was cloned from.
+    fn publish(work: &Path, message: &str) {
+        git(work, &["add", "-A"]);
+        git(work, &["commit", "--quiet", "-m", message]);
+        git(work, &["push", "--quiet", "origin", "HEAD:main"]);
+    }
+
+    #[test]
+    fn installs_and_updates_from_a_bare_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let remote = dir.path().join("starter.git");
+        let work = dir.path().join("work");
+        let app_dir = dir.path().join("workspace");
+        let tree = work.join(ROOT_DIR);
+
+        run_git(
+            None,
+            &[
+                "init",
+                "--quiet",
+                "--bare",
+                "-b",
+                "main",
+                &remote.to_string_lossy(),
+            ],
+        )
+        .unwrap();
+        run_git(
+            None,
+            &[
+                "clone",
+                "--quiet",
+                &remote.to_string_lossy(),
+                &work.to_string_lossy(),
+            ],
+        )
+        .unwrap();
+
+        write(
+            &work,
+            "starter.toml",
+            "[variables.team]\ndefault = \"core\"\n",
+        );
+        write(&tree, "instructions/general.md", "Owned by {{ team }}\n");
+        write(&tree, "instructions/review.md", "Review {{ agent_name }}\n");
+        write(&tree, "instructions/legacy.md", "Legacy\n");
+        publish(&work, "Initial starter");
+
+        // init --from
+        let source = remote.to_string_lossy();
+        let values = BTreeMap::from([("team".to_string(), "platform".to_string())]);
+        let (files, revision) = {
+            let starter = Starter::fetch(&source).unwrap();
+            assert!(starter.missing_variables(&values).is_empty());
+            (starter.render(&values).unwrap(), starter.revision.clone())
+        };
+
+        assert!(files.iter().all(|file| !file.path.starts_with(ROOT_DIR)));
+        let hashes = install(&files, &app_dir).unwrap();
+        assert_eq!(
+            read(&app_dir, "instructions/general.md"),
+            "Owned by platform\n"
+        );
+        assert_eq!(
+            read(&app_dir, "instructions/review.md"),
+            "Review {{ agent_name }}\n"
+        );
+
+        let origin = StarterOrigin {
+            source: source.to_string(),
+            revision,
+            variables: values,
+            files: hashes,
+        };
+
+        // Local edits: one to a file the starter leaves alone, one to a file it changes.
+        write(&app_dir, "instructions/general.md", "Owned by us\n");
+        write(&app_dir, "instructions/review.md", "Review carefully\n");
+
+        write(
+            &tree,
+            "instructions/review.md",
+            "Review {{ agent_name }} twice\n",
+        );
+        write(&tree, "instructions/style.md", "Style for {{ team }}\n");
+        fs::remove_file(tree.join("instructions/legacy.md")).unwrap();
+        publish(&work, "Update starter");
+
+        // init --update
+        let starter = Starter::fetch(&origin.source).unwrap();
+        assert_ne!(starter.revision, origin.revision);
+        let files = starter.render(&origin.variables).unwrap();
+        let (hashes, summary) = update(&files, &app_dir, &origin).unwrap();
+
+        assert_eq!(
+            (
+                summary.added,
+                summary.updated,
+                summary.removed,
+                summary.kept
+            ),
+            (1, 0, 1, 1)
+        );
+        assert_eq!(read(&app_dir, "instructions/general.md"), "Owned by us\n");
+        assert_eq!(
+            read(&app_dir, "instructions/review.md"),
+            "Review carefully\n"
+        );
+        assert_eq!(
+            read(&app_dir, "instructions/style.md"),
+            "Style for platform\n"
+        );
+        assert!(!app_dir.join("instructions/legacy.md").exists());
+
+        // Edited files keep the hash the starter wrote, so the edit is still spotted later.
+        assert_eq!(
+            hashes["instructions/general.md"],
+            origin.files["instructions/general.md"]
+        );
+        assert!(!hashes.contains_key("instructions/legacy.md"));
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value};
+
+use super::packages::load_packages;
+use super::plan::{FeatureSource, provider_context};
+use super::scope::DeployScope;
+use crate::constants::features::{COMMANDS_FEATURE, FEATURES, INSTRUCTION_FEATURE, MCP_FEATURE};
+use crate::schema::common::Target;
+use crate::schema::config::{AppConfig, ConfigAgentSettings, GlobalConfig, LocalConfig};
+use crate::schema::variable::{Variable, VariableResolver, merge_variables};
+use crate::templates::helpers::Templater;
+use crate::utils::path::get_workspace_dir;
+use crate::utils::{did_you_mean, merge_json};
+
+/// Where a template context value came from.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ContextSource {
+    GlobalConfig,
+    LocalConfig,
+    Provider(String),
+    Feature(&'static str),
+    Builtin,
+}
+
+impl fmt::Display for ContextSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GlobalConfig => f.write_str("global config"),
+            Self::LocalConfig => f.write_str("local config"),
+            Self::Provider(name) => write!(f, "provider {}", name),
+            Self::Feature(COMMANDS_FEATURE) => f.write_str("command frontmatter"),
+            Self::Feature(MCP_FEATURE) => f.write_str("mcp config"),
+            Self::Feature(INSTRUCTION_FEATURE) => f.write_str("instruction"),
+            Self::Feature(feature) => f.write_str(feature),
+            Self::Builtin => f.write_str("builtin"),
+        }
+    }
+}
+
+/// The data a template is rendered with, kept as separate layers so the origin of every value
+/// can be reported. Later layers take precedence, matching the order used by deploy.
+#[derive(Default)]
+pub(crate) struct TemplateContext {
+    layers: Vec<(ContextSource, Value)>,
+}
+
+/// Narrows a template context to what a single provider feature render receives.
+#[derive(Default)]
+pub(crate) struct ContextSelection {
+    pub provider: Option<String>,
+    pub feature: Option<String>,
+    pub command: Option<String>,
+}
+
+impl ContextSelection {
+    fn feature(&self) -> Option<&str> {
+        match (&self.feature, &self.command) {
+            (Some(feature), _) => Some(feature),
+            (None, Some(_)) => Some(COMMANDS_FEATURE),
+            (None, None) => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.command.is_some() && self.feature() != Some(COMMANDS_FEATURE) {
+            bail!("--command can only be used with the commands feature");
+        }
+
+        if self.provider.is_some() && self.feature().is_none() {
+            bail!("--provider requires --feature as provider settings are defined per feature");
+        }
+
+        Ok(())
+    }
+
+    /// Target and settings of the selected provider for the selected feature, if a provider was
+    /// given.
+    pub fn provider_settings(
+        &self,
+        config: &AppConfig,
+    ) -> Result<Option<(Target, ConfigAgentSettings)>> {
+        let (Some(provider), Some(feature)) = (&self.provider, self.feature()) else {
+            return Ok(None);
+        };
+
+        let providers = config.get_feature_providers(feature);
+
+        if let Some((target, _, settings)) = providers.iter().find(|(_, name, _)| name == provider)
+        {
+            return Ok(Some((*target, settings.clone())));
+        }
+
+        match did_you_mean(provider, providers.iter().map(|(_, name, _)| name.as_str())) {
+            Some(suggestion) => bail!(
+                "Provider '{}' does not deploy {}, did you mean '{}'?",
+                provider,
+                feature,
+                suggestion
+            ),
+            None => bail!("Provider '{}' does not deploy {}", provider, feature),
+        }
+    }
+}
+
+impl TemplateContext {
+    pub fn push(&mut self, source: ContextSource, value: Value) {
+        self.layers.push((source, value));
+    }
+
+    /// Builds the context a deploy would render the selected provider feature with.
+    pub fn build(
+        templater: &Templater,
+        global: &GlobalConfig,
+        local: &LocalConfig,
+        settings: Option<(Target, &ConfigAgentSettings)>,
+        selection: &ContextSelection,
+    ) -> Result<Self> {
+        let workspace_dir = get_workspace_dir()?;
+        let resolver = VariableResolver::new(&workspace_dir);
+        let mut context = Self::default();
+
+        // Resolved merged, as deploy does, so an overridden global variable never runs. The
+        // layers only tag each value with the config defining it.
+        let variables = resolver
+            .resolve(merge_variables(global.variables.as_ref(), local.variables.as_ref()).as_ref())
+            .context("failed to resolve variables")?;
+
+        context.push(
+            ContextSource::GlobalConfig,
+            defined_by(&variables, global.variables.as_ref()),
+        );
+        context.push(
+            ContextSource::LocalConfig,
+            defined_by(&variables, local.variables.as_ref()),
+        );
+
+        if let (Some(provider), Some((target, settings))) = (&selection.provider, settings) {
+            context.push(
+                ContextSource::Provider(provider.clone()),
+                provider_context(target, provider, settings, &resolver)?,
+            );
+        }
+
+        if let Some(feature) = selection.feature() {
+            let Some(feature) = FEATURES.into_iter().find(|known| *known == feature) else {
+                bail!("unknown feature {}", feature);
+            };
+
+            let scope = DeployScope {
+                commands: selection.command.iter().cloned().collect(),
+                ..Default::default()
+            };
+
+            // Commands render once per command, so their data only exists for a chosen one.
+            if feature != COMMANDS_FEATURE || selection.command.is_some() {
+                let provider = selection
+                    .provider
+                    .as_deref()
+                    .zip(settings.map(|(_, settings)| settings));
+                let merged = context.merged();
+                let packages = load_packages(&AppConfig::from_configs(global, local))
+                    .context("failed to load packages")?;
+
+                for item in FeatureSource::load(feature, &scope, &packages)?
+                    .items(provider, templater, &merged)?
+                {
+                    context.push(ContextSource::Feature(feature), item.data);
+                }
+            }
+        }
+
+        context.push(ContextSource::Builtin, templater.globals().clone());
+
+        Ok(context)
+    }
+
+    pub fn merged(&self) -> Value {
+        self.layers
+            .iter()
+            .fold(Value::Object(Map::new()), |merged, (_, value)| {
+                merge_json(&merged, value)
+            })
+    }
+
+    /// Maps the dotted path of every value in the merged context to the layer it came from.
+    pub fn sources(&self) -> BTreeMap<String, String> {
+        let mut sources = BTreeMap::new();
+        self.collect_sources(&self.merged(), &mut Vec::new(), &mut sources);
+        sources
+    }
+
+    fn collect_sources<'a>(
+        &self,
+        value: &'a Value,
+        path: &mut Vec<&'a str>,
+        sources: &mut BTreeMap<String, String>,
+    ) {
+        if let Value::Object(map) = value
+            && !map.is_empty()
+        {
+            for (key, value) in map {
+                path.push(key);
+                self.collect_sources(value, path, sources);
+                path.pop();
+            }
+            return;
+        }
+
+        let source = self
+            .layers
+            .iter()
+            .rev()
+            .find(|(_, layer)| {
+                path.iter()
+                    .try_fold(layer, |value, key| value.get(key))
+                    .is_some()
+            })
+            .map(|(source, _)| source.to_string())
+            .unwrap_or_default();
+
+        sources.insert(path.join("."), source);
+    }
+}
+
+/// The part of the resolved `variables` that `definitions` define, down to nested table keys.
+fn defined_by(variables: &Value, definitions: Option<&HashMap<String, Variable>>) -> Value {
+    fn project(value: &Value, variable: &Variable) -> Value {
+        match (value, variable) {
+            (Value::Object(map), Variable::Table(table)) => Value::Object(
+                table
+                    .iter()
+                    .filter_map(|(key, variable)| {
+                        map.get(key)
+                            .map(|value| (key.clone(), project(value, variable)))
+                    })
+                    .collect(),
+            ),
+            (value, _) => value.clone(),
+        }
+    }
+
+    Value::Object(
+        definitions
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, variable)| {
+                variables
+                    .get(key)
+                    .map(|value| (key.clone(), project(value, variable)))
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,55 @@
+//! Manage the configuration of coding agents from one `.dotagents` directory.
+//!
+//! The `dotagents` binary is a thin wrapper over [`run_cli`]. Tools embedding dotagents load
+//! an [`AppConfig`], read the [`Command`], [`McpConfig`] and [`Instruction`] sources of a
+//! workspace, and use a [`Deployer`] to render them into a [`DeployPlan`] before writing it.
+
+mod cli;
+mod config;
+mod constants;
+mod core;
+mod prelude;
+mod schema;
+mod templates;
+mod utils;
+
+use std::process::ExitCode;
+
+pub use crate::constants::dir::ROOT_DIR;
+pub use crate::core::deployer::Deployer;
+pub use crate::core::plan::{DeployPlan, OutputStrategy, PlannedOutput, PlannedUnit};
+pub use crate::core::renderer::{
+    ProviderRenderer, RenderInput, RendererRegistry, TemplateRenderer,
+};
+pub use crate::core::scope::DeployScope;
+pub use crate::schema::command::{
+    ArgumentStyle, Command, CommandArgument, CommandMetadata, CommandMode,
+};
+pub use crate::schema::common::{DeployMode, FeatureItem, Target};
+pub use crate::schema::config::{
+    AppConfig, CacheConfig, ConfigAgentAbilitySettings, ConfigAgentSettings, DeployedOutput,
+    GlobalConfig, LocalConfig, Providers, Targets, TomlConfig,
+};
+pub use crate::schema::instruction::{
+    Instruction, InstructionFragment, InstructionLayout, InstructionMetadata, InstructionTrigger,
+};
+pub use crate::schema::mcp::{CommonConfig, McpConfig, ServerConfig, ToolFilter};
+pub use crate::schema::package::PackageSource;
+pub use crate::schema::variable::{CommandSource, EnvSource, FileSource, Variable, VariableSource};
+pub use crate::templates::helpers::{RenderType, TemplateSource, Templater};
+
+/// Runs the `dotagents` command line with the arguments of the process. Errors are printed
+/// before returning.
+pub fn run_cli() -> ExitCode {
+    let opts = cli::get_options();
+    utils::set_log_config(opts.quiet, opts.verbosity);
+
+    match cli::run(opts) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            utils::display_error(e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::file::BACKUP_MANIFEST_FILE;
+use crate::schema::config::TomlConfig;
+use crate::utils::fs::write_atomic;
+
+/// Files a deploy replaced without having created them, saved next to this manifest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct BackupManifest {
+    pub created: String,
+
+    #[serde(default)]
+    pub files: Vec<BackedUpFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct BackedUpFile {
+    /// Where the file lived before the deploy replaced it.
+    pub path: String,
+
+    /// Name of the copy inside the backup directory.
+    pub backup: String,
+}
+
+impl BackupManifest {
+    pub fn new(created: &str) -> Self {
+        Self {
+            created: created.to_string(),
+            files: Vec::new(),
+        }
+    }
+
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let path = dir.join(BACKUP_MANIFEST_FILE);
+        let content =
+            fs::read_to_string(&path).context(format!("failed to read {}", path.display()))?;
+        Self::from_toml(&content).context(format!("failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        write_atomic(&dir.join(BACKUP_MANIFEST_FILE), self.to_toml()?.as_bytes())
+            .context("failed to write backup manifest")
+    }
+}
+
+impl TomlConfig for BackupManifest {}
@@ -1,4 +1,4 @@
-use crate::schema::command::{Command, CommandMetadata};
+use crate::schema::command::{Command, CommandArgument, CommandMetadata};
 
 pub(crate) struct CommandBuilder {
     metadata: CommandMetadata,
@@ -11,11 +11,17 @@ impl CommandBuilder {
             metadata: CommandMetadata {
                 name: name.into(),
                 description: description.into(),
+                ..Default::default()
             },
             content: None,
         }
     }
 
+    pub fn add_argument(mut self, argument: CommandArgument) -> Self {
+        self.metadata.arguments.push(argument);
+        self
+    }
+
     pub fn add_content(mut self, content: &str) -> Self {
         self.content = Some(content.into());
         self
@@ -24,7 +30,9 @@ impl CommandBuilder {
     pub fn build(self) -> Command {
         Command {
             metadata: self.metadata,
+            namespace: Vec::new(),
             content: self.content.unwrap_or_default(),
+            source: None,
         }
     }
 }
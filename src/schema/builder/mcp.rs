@@ -42,6 +42,7 @@ impl McpConfigBuilder {
         command: &str,
         args: Vec<String>,
         cwd: Option<&str>,
+        env: Option<HashMap<String, String>>,
         common: Option<CommonConfig>,
     ) -> Self {
         self.servers.insert(
@@ -50,7 +51,7 @@ impl McpConfigBuilder {
                 command: command.into(),
                 args,
                 cwd: cwd.map(|s| s.into()),
-                env: Some(HashMap::new()),
+                env,
                 env_file: None,
                 common,
             },
@@ -62,6 +63,7 @@ impl McpConfigBuilder {
         McpConfig {
             schema: self.schema,
             servers: self.servers,
+            discovered_tools: HashMap::new(),
         }
     }
 }
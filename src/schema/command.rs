@@ -1,22 +1,149 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use gray_matter::Matter;
 use gray_matter::engine::YAML;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json, to_value};
 
+use crate::schema::config::ConfigAgentSettings;
+use crate::utils::fs::collect_markdown_files;
 use crate::utils::path::get_commands_dir;
+use crate::utils::{did_you_mean, merge_json};
 
-#[derive(Serialize, Deserialize)]
-pub(crate) struct CommandMetadata {
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandMode {
+    Agent,
+    Ask,
+    Edit,
+}
+
+/// Joins namespaces and command names unless a provider asks for another separator.
+pub(crate) const NAMESPACE_SEPARATOR: &str = "/";
+
+const ARGUMENT_HINT: &str = "argument-hint";
+
+/// How a provider spells command arguments. Command bodies use the neutral `${args}` for
+/// everything the user typed and `${arg:name}` for a declared argument, which each provider
+/// translates into its native form when rendering.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgumentStyle {
+    /// `$ARGUMENTS` and positional `$1`, `$2`, ...
+    Claude,
+    /// `${input:name:description}`, with no way to refer to the whole input.
+    Copilot,
+    /// `{{args}}`, with no way to refer to a single argument.
+    Gemini,
+    /// `<name>` and `<arguments>`, left for the agent to fill in from the invocation text.
+    Plain,
+    /// The provider has no argument support.
+    #[default]
+    None,
+}
+
+/// An argument placeholder found in a command body.
+enum Placeholder<'a> {
+    All,
+    Named(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandMetadata {
+    /// Defaults to the file name when omitted from the frontmatter.
+    #[serde(default)]
     pub name: String,
     pub description: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<CommandArgument>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<CommandMode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Frontmatter overrides applied only when rendering for the named provider.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub agents: BTreeMap<String, Value>,
+    /// Keys dotagents doesn't know about, kept so they reach templates and survive rewrites.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub(crate) struct Command {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Command {
+    #[serde(flatten)]
     pub metadata: CommandMetadata,
+    /// Folders between the commands directory and the command file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub namespace: Vec<String>,
     pub content: String,
+    /// File the command was loaded from.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl CommandArgument {
+    fn hint(&self) -> String {
+        if self.required {
+            format!("<{}>", self.name)
+        } else {
+            format!("[{}]", self.name)
+        }
+    }
+}
+
+impl ArgumentStyle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Claude => "claude",
+            Self::Copilot => "copilot",
+            Self::Gemini => "gemini",
+            Self::Plain => "plain",
+            Self::None => "none",
+        }
+    }
+
+    fn translate(
+        &self,
+        placeholder: &Placeholder,
+        arguments: &[CommandArgument],
+    ) -> Option<String> {
+        match (self, placeholder) {
+            (Self::Claude, Placeholder::All) => Some("$ARGUMENTS".into()),
+            (Self::Claude, Placeholder::Named(name)) => arguments
+                .iter()
+                .position(|argument| argument.name == *name)
+                .map(|index| format!("${}", index + 1)),
+            (Self::Copilot, Placeholder::Named(name)) => {
+                let argument = arguments.iter().find(|argument| argument.name == *name)?;
+                match &argument.description {
+                    Some(description) => Some(format!("${{input:{}:{}}}", name, description)),
+                    None => Some(format!("${{input:{}}}", name)),
+                }
+            }
+            (Self::Gemini, Placeholder::All) => Some("{{args}}".into()),
+            (Self::Plain, Placeholder::All) => Some("<arguments>".into()),
+            (Self::Plain, Placeholder::Named(name)) => Some(format!("<{}>", name)),
+            _ => None,
+        }
+    }
 }
 
 impl Command {
@@ -35,25 +162,347 @@ impl Command {
 
         Ok(Command {
             metadata,
+            namespace: Vec::new(),
             content: parsed.content,
+            source: None,
         })
     }
 
+    /// Loads the command at `path`, namespaced by its folders relative to `root`.
+    fn from_file(root: &Path, path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).context("failed to read file")?;
+        let mut command = Self::from_markdown(&content)?;
+
+        if command.metadata.name.is_empty() {
+            command.metadata.name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .context("file name is not valid UTF-8")?
+                .to_string();
+        } else {
+            // The name ends up in the output path of every provider.
+            let name = &command.metadata.name;
+            if name.contains(['/', '\\']) || name == "." || name == ".." {
+                bail!(
+                    "name '{}' may not contain path separators or be '.' or '..', use folders for namespaces",
+                    name
+                );
+            }
+        }
+
+        if let Some(parent) = path.strip_prefix(root).ok().and_then(Path::parent) {
+            command.namespace = parent
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect();
+        }
+
+        command.source = Some(path.to_path_buf());
+
+        Ok(command)
+    }
+
+    /// Name of the command with its namespace joined by `separator`.
+    pub fn name_with(&self, separator: &str) -> String {
+        self.namespace
+            .iter()
+            .map(String::as_str)
+            .chain([self.metadata.name.as_str()])
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    /// Name used to refer to the command on the command line and in the deploy cache.
+    pub fn qualified_name(&self) -> String {
+        self.name_with(NAMESPACE_SEPARATOR)
+    }
+
+    /// Rewrites the argument placeholders of the body into `style`. Fails when the body refers
+    /// to an undeclared argument or to one `style` cannot express.
+    pub fn translate_arguments(&self, style: ArgumentStyle) -> Result<String> {
+        let arguments = &self.metadata.arguments;
+        let mut translated = String::with_capacity(self.content.len());
+        let mut rest = self.content.as_str();
+
+        while let Some(start) = rest.find("${") {
+            translated.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+
+            let placeholder = match &rest[2..end] {
+                "args" => Placeholder::All,
+                inner => match inner.strip_prefix("arg:") {
+                    Some(name) => Placeholder::Named(name.trim()),
+                    None => {
+                        // Not ours, e.g. a shell variable in a code sample.
+                        translated.push_str(&rest[..end + 1]);
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
+                },
+            };
+
+            if let Placeholder::Named(name) = placeholder
+                && !arguments.iter().any(|argument| argument.name == name)
+            {
+                let names = arguments.iter().map(|argument| argument.name.as_str());
+                match did_you_mean(name, names) {
+                    Some(suggestion) => bail!(
+                        "argument '{}' is not declared in the frontmatter, did you mean '{}'?",
+                        name,
+                        suggestion
+                    ),
+                    None => bail!("argument '{}' is not declared in the frontmatter", name),
+                }
+            }
+
+            // Copilot ends the placeholder at the first `}`, whatever the description says.
+            if let (ArgumentStyle::Copilot, Placeholder::Named(name)) = (style, &placeholder)
+                && let Some(argument) = arguments.iter().find(|argument| argument.name == *name)
+                && argument
+                    .description
+                    .as_deref()
+                    .is_some_and(|d| d.contains('}'))
+            {
+                bail!(
+                    "the description of argument '{}' contains '}}', which the 'copilot' argument style cannot express",
+                    name
+                );
+            }
+
+            let Some(native) = style.translate(&placeholder, arguments) else {
+                match placeholder {
+                    Placeholder::All => bail!(
+                        "${{args}} has no equivalent in the '{}' argument style",
+                        style.as_str()
+                    ),
+                    Placeholder::Named(name) => bail!(
+                        "argument '{}' has no equivalent in the '{}' argument style",
+                        name,
+                        style.as_str()
+                    ),
+                }
+            };
+
+            translated.push_str(&native);
+            rest = &rest[end + 1..];
+        }
+
+        translated.push_str(rest);
+        Ok(translated)
+    }
+
+    /// The command as seen by the templates of `agent`, with its `agents.<agent>` overrides
+    /// merged over the shared frontmatter and its name and arguments spelled the way the
+    /// agent's settings ask for.
+    pub fn to_template_value(&self, agent: Option<(&str, &ConfigAgentSettings)>) -> Result<Value> {
+        let mut value = to_value(self).context("failed to serialize command")?;
+
+        if let Some((_, settings)) = agent {
+            let content = self
+                .translate_arguments(settings.arguments.unwrap_or_default())
+                .context(format!(
+                    "failed to translate arguments of command {}",
+                    self.qualified_name()
+                ))?;
+            value["content"] = Value::String(content);
+
+            let separator = settings
+                .namespace_separator
+                .as_deref()
+                .unwrap_or(NAMESPACE_SEPARATOR);
+            value["name"] = Value::String(self.name_with(separator));
+        }
+
+        // A hint written in the frontmatter is kept over the one computed from the arguments.
+        if !self.metadata.arguments.is_empty() && !self.metadata.extra.contains_key(ARGUMENT_HINT) {
+            let hint: Vec<String> = self.metadata.arguments.iter().map(|a| a.hint()).collect();
+            value = merge_json(&value, &json!({ ARGUMENT_HINT: hint.join(" ") }));
+        }
+
+        // Overrides of other agents are none of this agent's business.
+        if let Value::Object(map) = &mut value {
+            map.remove("agents");
+        }
+
+        if let Some(overrides) = agent.and_then(|(agent, _)| self.metadata.agents.get(agent)) {
+            value = merge_json(&value, overrides);
+        }
+
+        Ok(value)
+    }
+
+    /// Loads every markdown file below the commands directory.
     pub fn from_application() -> Result<Vec<Self>> {
-        let dir = get_commands_dir()?;
+        Self::from_dir(&get_commands_dir()?)
+    }
+
+    /// Loads every markdown file below `dir`. Files that fail to load and names defined more
+    /// than once are reported together.
+    pub fn from_dir(dir: &Path) -> Result<Vec<Self>> {
+        let mut files = Vec::new();
+        collect_markdown_files(dir, &mut files)?;
+        files.sort();
+
         let mut commands = Vec::<Self>::new();
+        let mut defined_in = HashMap::<String, &Path>::new();
+        let mut errors = Vec::new();
+
+        for path in &files {
+            let relative = path.strip_prefix(dir).unwrap_or(path);
 
-        for entry in fs::read_dir(&dir)? {
-            let entry = entry?;
-            let path = entry.path();
+            match Self::from_file(dir, path) {
+                Ok(command) => {
+                    let name = command.qualified_name();
 
-            if path.is_file() {
-                let content = fs::read_to_string(&path).context("failed to read file")?;
-                let command = Self::from_markdown(&content)?;
-                commands.push(command);
+                    if let Some(first) = defined_in.get(&name) {
+                        errors.push(format!(
+                            "{}: command '{}' is already defined in {}",
+                            relative.display(),
+                            name,
+                            first.display()
+                        ));
+                        continue;
+                    }
+
+                    defined_in.insert(name, relative);
+                    commands.push(command);
+                }
+                Err(e) => errors.push(format!("{}: {:#}", relative.display(), e)),
             }
         }
 
+        if !errors.is_empty() {
+            bail!(
+                "found {} invalid command file(s) in {}:\n{}",
+                errors.len(),
+                dir.display(),
+                errors
+                    .iter()
+                    .map(|error| format!("      - {}", error))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
         Ok(commands)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_body(content: &str, arguments: &[(&str, Option<&str>)]) -> Command {
+        Command {
+            metadata: CommandMetadata {
+                name: "test".into(),
+                arguments: arguments
+                    .iter()
+                    .map(|(name, description)| CommandArgument {
+                        name: name.to_string(),
+                        description: description.map(str::to_string),
+                        required: false,
+                        default: None,
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            namespace: Vec::new(),
+            content: content.into(),
+            source: None,
+        }
+    }
+
+    fn translate(command: &Command, style: ArgumentStyle) -> String {
+        command.translate_arguments(style).unwrap()
+    }
+
+    fn error(command: &Command, style: ArgumentStyle) -> String {
+        command.translate_arguments(style).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn translates_claude_placeholders() {
+        let command = with_body("${args} ${arg:b} ${arg:a}", &[("a", None), ("b", None)]);
+        assert_eq!(
+            translate(&command, ArgumentStyle::Claude),
+            "$ARGUMENTS $2 $1"
+        );
+    }
+
+    #[test]
+    fn translates_copilot_placeholders() {
+        let command = with_body(
+            "${arg:file} ${arg:scope}",
+            &[("file", Some("File to fix")), ("scope", None)],
+        );
+        assert_eq!(
+            translate(&command, ArgumentStyle::Copilot),
+            "${input:file:File to fix} ${input:scope}"
+        );
+    }
+
+    #[test]
+    fn translates_gemini_and_plain_placeholders() {
+        let all = with_body("Run ${args}", &[]);
+        assert_eq!(translate(&all, ArgumentStyle::Gemini), "Run {{args}}");
+        assert_eq!(translate(&all, ArgumentStyle::Plain), "Run <arguments>");
+
+        let named = with_body("Fix ${arg: file }", &[("file", None)]);
+        assert_eq!(translate(&named, ArgumentStyle::Plain), "Fix <file>");
+    }
+
+    #[test]
+    fn keeps_foreign_and_unterminated_placeholders() {
+        let command = with_body("echo ${HOME} ${args} and ${arg:file", &[]);
+        assert_eq!(
+            translate(&command, ArgumentStyle::Claude),
+            "echo ${HOME} $ARGUMENTS and ${arg:file"
+        );
+    }
+
+    #[test]
+    fn rejects_undeclared_arguments() {
+        let command = with_body("${arg:fiel}", &[("file", None)]);
+        assert_eq!(
+            error(&command, ArgumentStyle::Claude),
+            "argument 'fiel' is not declared in the frontmatter, did you mean 'file'?"
+        );
+
+        let command = with_body("${arg:other}", &[]);
+        assert_eq!(
+            error(&command, ArgumentStyle::Claude),
+            "argument 'other' is not declared in the frontmatter"
+        );
+    }
+
+    #[test]
+    fn rejects_placeholders_a_style_cannot_express() {
+        assert_eq!(
+            error(&with_body("${args}", &[]), ArgumentStyle::Copilot),
+            "${args} has no equivalent in the 'copilot' argument style"
+        );
+        assert_eq!(
+            error(
+                &with_body("${arg:file}", &[("file", None)]),
+                ArgumentStyle::Gemini
+            ),
+            "argument 'file' has no equivalent in the 'gemini' argument style"
+        );
+        assert_eq!(
+            error(&with_body("${args}", &[]), ArgumentStyle::None),
+            "${args} has no equivalent in the 'none' argument style"
+        );
+    }
+
+    #[test]
+    fn rejects_closing_braces_in_copilot_descriptions() {
+        let command = with_body("${arg:file}", &[("file", Some("a {b} c"))]);
+        assert!(error(&command, ArgumentStyle::Copilot).contains("contains '}'"));
+        assert_eq!(translate(&command, ArgumentStyle::Plain), "<file>");
+    }
+}
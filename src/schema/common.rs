@@ -1,11 +1,61 @@
-pub(crate) enum Target {
-    IDE,
-    CLI,
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Target {
+    Ide,
+    Cli,
     Custom,
 }
 
-pub(crate) enum AgentAbility {
-    MCP,
-    Instructions,
-    Commands,
-}
\ No newline at end of file
+impl Target {
+    pub const ALL: [Target; 3] = [Target::Ide, Target::Cli, Target::Custom];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Target::Ide => "ide",
+            Target::Cli => "cli",
+            Target::Custom => "custom",
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How deploy turns a source into an output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployMode {
+    /// Render the provider template with the source data.
+    #[default]
+    Template,
+    /// Copy the source file as is.
+    Copy,
+    /// Link to the source file, so edits show up without a deploy.
+    Symlink,
+}
+
+impl DeployMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Template => "template",
+            Self::Copy => "copy",
+            Self::Symlink => "symlink",
+        }
+    }
+}
+
+/// Template data of one output, along with the file it was read from if there is exactly one.
+pub struct FeatureItem {
+    /// Name of the source item, e.g. a command, used to scope deploys and prune outputs.
+    pub id: Option<String>,
+    pub data: Value,
+    pub source: Option<PathBuf>,
+}
@@ -5,9 +5,9 @@ pub(crate) mod global;
 pub(crate) mod local;
 pub(crate) mod traits;
 
-pub(crate) use app::AppConfig;
-pub(crate) use cache::CacheConfig;
-pub(crate) use common::{ConfigAgentAbilitySettings, ConfigAgentSettings, Providers, Targets};
-pub(crate) use global::GlobalConfig;
-pub(crate) use local::LocalConfig;
-pub(crate) use traits::TomlConfig;
+pub use app::AppConfig;
+pub use cache::{CacheConfig, DeployedOutput};
+pub use common::{ConfigAgentAbilitySettings, ConfigAgentSettings, Providers, Targets};
+pub use global::GlobalConfig;
+pub use local::LocalConfig;
+pub use traits::TomlConfig;
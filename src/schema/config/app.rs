@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use anyhow::{Context, Result};
 
@@ -6,11 +6,15 @@ use super::cache::CacheConfig;
 use super::common::{Providers, Targets};
 use super::global::GlobalConfig;
 use super::local::LocalConfig;
-use crate::constants::features::{COMMANDS_FEATURE, INSTRUCTION_FEATURE, MCP_FEATURE};
+use crate::config::catalog::{builtin_provider_names, builtin_providers};
 use crate::constants::file::{GLOBAL_CONFIG_FILE, LOCAL_CONFIG_FILE};
 use crate::constants::schema::CONFIG_SCHEMA;
+use crate::schema::common::Target;
 use crate::schema::config::{ConfigAgentSettings, TomlConfig};
-use crate::templates::helpers::{RenderType, Templater, get_templater};
+use crate::schema::package::PackageSource;
+use crate::schema::variable::{Variable, merge_variables};
+use crate::templates::helpers::{RenderType, Templater};
+use crate::utils::did_you_mean;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -21,7 +25,11 @@ pub struct AppConfig {
     pub targets: Targets,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub providers: Option<Providers>,
-    pub variables: Option<HashMap<String, String>>,
+    pub variables: Option<HashMap<String, Variable>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_roots: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub packages: BTreeMap<String, PackageSource>,
 }
 
 impl AppConfig {
@@ -32,6 +40,8 @@ impl AppConfig {
             targets: Targets::new(),
             providers: None,
             variables: None,
+            allowed_roots: Vec::new(),
+            packages: BTreeMap::new(),
         }
     }
 
@@ -39,32 +49,83 @@ impl AppConfig {
         self.features.contains(feature)
     }
 
-    pub fn get_feature_providers(&self, feature: &str) -> HashMap<String, ConfigAgentSettings> {
-        let Some(providers) = &self.providers else {
-            return HashMap::new();
-        };
-
+    /// Providers that should deploy `feature`, restricted to the agents listed in `targets`.
+    pub fn get_feature_providers(
+        &self,
+        feature: &str,
+    ) -> Vec<(Target, String, ConfigAgentSettings)> {
+        let providers = self.resolved_providers();
         let has_feature = self.has_feature(feature);
 
-        [
-            providers.cli.clone(),
-            providers.ide.clone(),
-            providers.custom.clone(),
-        ]
-        .into_iter()
-        .flatten()
-        .flat_map(|map| map.into_iter())
-        .filter_map(|(name, settings)| {
-            let config = settings.get_config(feature)?;
-            let is_enabled = config.disabled.unwrap_or(false);
-
-            if has_feature || is_enabled {
-                Some((name.clone(), config.clone()))
-            } else {
-                None
+        Target::ALL
+            .into_iter()
+            .filter_map(|target| self.targets.get(target).map(|names| (target, names)))
+            .flat_map(|(target, names)| {
+                let mut names: Vec<_> = names.iter().collect();
+                names.sort();
+                names.into_iter().map(move |name| (target, name))
+            })
+            .filter_map(|(target, name)| {
+                let settings = providers.get_provider(target, name)?.get_config(feature)?;
+                let is_enabled = settings.disabled.map_or(has_feature, |disabled| !disabled);
+
+                if is_enabled {
+                    Some((target, name.clone(), settings))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// User providers layered on top of the built-in catalog.
+    pub fn resolved_providers(&self) -> Providers {
+        match &self.providers {
+            Some(providers) => builtin_providers().merge(providers),
+            None => builtin_providers().clone(),
+        }
+    }
+
+    /// Describes every name in `targets` that has no matching provider.
+    pub fn unknown_targets(&self) -> Vec<String> {
+        let providers = self.resolved_providers();
+        let catalog = builtin_provider_names();
+        let mut messages = Vec::new();
+
+        for target in Target::ALL {
+            let Some(names) = self.targets.get(target) else {
+                continue;
+            };
+
+            let mut names: Vec<_> = names.iter().collect();
+            names.sort();
+
+            for name in names {
+                if providers.get_provider(target, name).is_some() {
+                    continue;
+                }
+
+                let mut message = format!("Unknown {} target '{}'", target, name);
+
+                if let Some((kind, _)) = catalog.iter().find(|(_, known)| known == name) {
+                    message.push_str(&format!(", did you mean to list it under {}?", kind));
+                } else {
+                    let candidates = providers
+                        .get(target)
+                        .into_iter()
+                        .flat_map(|map| map.keys().map(String::as_str))
+                        .chain(catalog.iter().map(|(_, known)| *known));
+
+                    if let Some(suggestion) = did_you_mean(name, candidates) {
+                        message.push_str(&format!(", did you mean '{}'?", suggestion));
+                    }
+                }
+
+                messages.push(message);
             }
-        })
-        .collect::<HashMap<_, _>>()
+        }
+
+        messages
     }
 
     pub fn from_configs(global: &GlobalConfig, local: &LocalConfig) -> Self {
@@ -93,16 +154,22 @@ impl AppConfig {
             (Some(g), Some(l)) => Some(g.merge(l)),
         };
 
-        let variables = match (&global.variables, &local.variables) {
-            (None, None) => None,
-            (Some(g), None) => Some(g.clone()),
-            (None, Some(l)) => Some(l.clone()),
-            (Some(g), Some(l)) => {
-                let mut var = g.clone();
-                var.extend(l.clone());
-                Some(var)
-            }
-        };
+        let variables = merge_variables(global.variables.as_ref(), local.variables.as_ref());
+
+        let allowed_roots = [&global.allowed_roots, &local.allowed_roots]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .cloned()
+            .collect();
+
+        // A package defined in both files is taken from the local one as a whole.
+        let packages = [&global.packages, &local.packages]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|(name, source)| (name.clone(), source.clone()))
+            .collect();
 
         Self {
             schema,
@@ -110,6 +177,8 @@ impl AppConfig {
             targets,
             providers,
             variables,
+            allowed_roots,
+            packages,
         }
     }
 
@@ -120,6 +189,8 @@ impl AppConfig {
             targets: Targets::new(),
             providers: cache.providers.clone(),
             variables: None,
+            allowed_roots: Vec::new(),
+            packages: BTreeMap::new(),
         }
     }
 
@@ -130,7 +201,8 @@ impl AppConfig {
         }
     }
 
-    pub fn from_application(templater: &Templater) -> Result<Self> {
+    /// Reads the global and local configuration files without merging them.
+    pub fn load_layers(templater: &Templater) -> Result<(GlobalConfig, LocalConfig)> {
         let global_config_content =
             templater.render_template(RenderType::Name(GLOBAL_CONFIG_FILE.to_string()), None)?;
         let local_config_content =
@@ -139,10 +211,20 @@ impl AppConfig {
         let local_config = LocalConfig::from_toml(&local_config_content)?;
         local_config.validate().context("invalid local config")?;
         let global_config = GlobalConfig::from_toml(&global_config_content)?;
-        global_config.validate().context("invalid local config")?;
+        global_config.validate().context("invalid global config")?;
+
+        Ok((global_config, local_config))
+    }
+
+    pub fn from_application(templater: &Templater) -> Result<Self> {
+        let (global_config, local_config) = Self::load_layers(templater)?;
 
         let app_config = AppConfig::from_configs(&global_config, &local_config);
 
+        for message in app_config.unknown_targets() {
+            log::warn!("{}", message);
+        }
+
         Ok(app_config)
     }
 }
@@ -155,3 +237,86 @@ impl Default for AppConfig {
 
 #[cfg(debug_assertions)]
 impl TomlConfig for AppConfig {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(global: &str, local: &str) -> AppConfig {
+        AppConfig::from_configs(
+            &GlobalConfig::from_toml(global).unwrap(),
+            &LocalConfig::from_toml(local).unwrap(),
+        )
+    }
+
+    fn provider_names(config: &AppConfig, feature: &str) -> Vec<String> {
+        config
+            .get_feature_providers(feature)
+            .into_iter()
+            .map(|(_, name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn deploys_only_the_listed_targets() {
+        let config = config(
+            "features = [\"commands\", \"instructions\"]\n\n[targets]\ncli = [\"copilot\"]\n",
+            "",
+        );
+
+        assert_eq!(provider_names(&config, "commands"), ["copilot"]);
+        assert_eq!(provider_names(&config, "instructions"), ["copilot"]);
+        assert!(provider_names(&config, "mcp").is_empty());
+        assert!(config.unknown_targets().is_empty());
+    }
+
+    #[test]
+    fn skips_features_disabled_per_provider() {
+        let config = config(
+            r#"features = ["commands", "instructions"]
+
+[targets]
+ide = ["windsurf"]
+cli = ["copilot"]
+
+[providers.ide.windsurf.commands]
+disabled = true
+"#,
+            "",
+        );
+
+        assert_eq!(provider_names(&config, "commands"), ["copilot"]);
+        assert_eq!(
+            provider_names(&config, "instructions"),
+            ["windsurf", "copilot"]
+        );
+    }
+
+    #[test]
+    fn local_targets_replace_global_ones() {
+        let config = config(
+            "features = [\"commands\"]\n\n[targets]\ncli = [\"copilot\"]\n",
+            "[targets]\nide = [\"windsurf\"]\ncli = []\n",
+        );
+
+        assert_eq!(provider_names(&config, "commands"), ["windsurf"]);
+    }
+
+    #[test]
+    fn reports_unknown_targets_with_suggestions() {
+        let config = config(
+            "features = [\"commands\"]\n\n[targets]\nide = [\"windsurff\", \"copilot\"]\ncli = [\"gemini\"]\n",
+            "",
+        );
+
+        assert_eq!(
+            config.unknown_targets(),
+            [
+                "Unknown ide target 'copilot', did you mean to list it under cli?",
+                "Unknown ide target 'windsurff', did you mean 'windsurf'?",
+                "Unknown cli target 'gemini'",
+            ]
+        );
+        assert!(provider_names(&config, "commands").is_empty());
+    }
+}
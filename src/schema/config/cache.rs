@@ -1,8 +1,17 @@
-use super::common::Providers;
+use std::fs;
+
+use anyhow::{Context, Result};
+
+use super::common::{ConfigAgentSettings, Providers};
 use super::traits::TomlConfig;
+use crate::constants::features::FEATURES;
 use crate::constants::schema::CONFIG_SCHEMA;
+use crate::schema::common::{DeployMode, Target};
+use crate::utils::fs::write_atomic;
+use crate::utils::path::{get_cache_dir, get_cache_file};
 use serde::{Deserialize, Serialize};
 
+/// `cache/cache.toml`, recording the settings and outputs of the last deploy of each provider.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct CacheConfig {
@@ -12,6 +21,23 @@ pub struct CacheConfig {
     pub providers: Option<Providers>,
 }
 
+/// A file written by a deploy, recorded so later deploys can detect edits and prune it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct DeployedOutput {
+    pub path: String,
+    pub hash: String,
+
+    /// The source item (e.g. command name) the output was rendered from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<String>,
+
+    /// How the output was deployed. The hash of a symlink covers its target path rather than
+    /// the content it points to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<DeployMode>,
+}
+
 impl CacheConfig {
     pub fn new() -> Self {
         Self {
@@ -27,32 +53,117 @@ impl CacheConfig {
         }
     }
 
-    pub fn has_valid_hash(&self, target_type: &str, target_name: &str, feature: &str) -> bool {
-        if let Some(providers) = &self.providers {
-            let provider_map = match target_type {
-                "ide" => providers.ide.as_ref(),
-                "cli" => providers.cli.as_ref(),
-                "custom" => providers.custom.as_ref(),
-                _ => return false,
+    pub fn from_application() -> Result<Self> {
+        let path = get_cache_file()?;
+
+        if !path.try_exists().context("failed to check cache file")? {
+            return Ok(Self::new());
+        }
+
+        let content = fs::read_to_string(&path).context("failed to read cache file")?;
+        Self::from_toml(&content)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(get_cache_dir()?).context("failed to create cache directory")?;
+        write_atomic(&get_cache_file()?, self.to_toml()?.as_bytes())
+            .context("failed to write cache file")
+    }
+
+    pub fn has_valid_hash(&self, target: Target, target_name: &str, feature: &str) -> bool {
+        self.get_settings(target, target_name, feature)
+            .and_then(|settings| settings.hash)
+            .is_some_and(|hash| !hash.is_empty())
+    }
+
+    pub fn get_settings(
+        &self,
+        target: Target,
+        target_name: &str,
+        feature: &str,
+    ) -> Option<ConfigAgentSettings> {
+        self.providers
+            .as_ref()
+            .and_then(|providers| providers.get_provider(target, target_name))
+            .and_then(|settings| settings.get_config(feature))
+    }
+
+    /// Replaces the cached settings of a provider feature, dropping empty entries.
+    pub fn set_settings(
+        &mut self,
+        target: Target,
+        target_name: &str,
+        feature: &str,
+        settings: Option<ConfigAgentSettings>,
+    ) {
+        let providers = self.providers.get_or_insert_with(Providers::new);
+        let map = providers.get_mut(target);
+        let provider = map.entry(target_name.to_string()).or_default();
+
+        provider.set_config(feature, settings);
+
+        if provider.is_empty() {
+            map.remove(target_name);
+        }
+    }
+
+    /// Every provider feature recorded in the cache.
+    pub fn entries(&self) -> Vec<(Target, String, &'static str)> {
+        let Some(providers) = &self.providers else {
+            return Vec::new();
+        };
+
+        let mut entries: Vec<_> = Target::ALL
+            .into_iter()
+            .filter_map(|target| providers.get(target).map(|map| (target, map)))
+            .flat_map(|(target, map)| {
+                map.iter().flat_map(move |(name, settings)| {
+                    FEATURES
+                        .into_iter()
+                        .filter(|feature| settings.get_config(feature).is_some())
+                        .map(move |feature| (target, name.clone(), feature))
+                })
+            })
+            .collect();
+
+        entries.sort();
+        entries
+    }
+
+    /// Whether a deploy recorded writing `path`.
+    pub fn owns(&self, path: &str) -> bool {
+        self.entries()
+            .into_iter()
+            .filter_map(|(target, name, feature)| self.get_settings(target, &name, feature))
+            .flat_map(|settings| settings.outputs.unwrap_or_default())
+            .any(|output| output.path == path)
+    }
+
+    /// Whether `path` is a symlink dotagents created in symlink mode.
+    pub fn owns_link(&self, path: &str) -> bool {
+        self.entries()
+            .into_iter()
+            .filter_map(|(target, name, feature)| self.get_settings(target, &name, feature))
+            .flat_map(|settings| settings.outputs.unwrap_or_default())
+            .any(|output| output.path == path && output.mode == Some(DeployMode::Symlink))
+    }
+
+    /// Drops `path` from every provider feature, so dotagents no longer treats it as its own.
+    pub fn forget(&mut self, path: &str) {
+        for (target, name, feature) in self.entries() {
+            let Some(mut settings) = self.get_settings(target, &name, feature) else {
+                continue;
+            };
+
+            let Some(outputs) = settings.outputs.as_mut() else {
+                continue;
             };
 
-            if let Some(map) = provider_map {
-                if let Some(settings) = map.get(target_name) {
-                    let feature_settings = match feature {
-                        "mcp" => settings.mcp.as_ref(),
-                        "instructions" => settings.instructions.as_ref(),
-                        "commands" => settings.commands.as_ref(),
-                        _ => return false,
-                    };
-
-                    return feature_settings
-                        .and_then(|s| s.hash.as_ref())
-                        .map(|h| !h.is_empty())
-                        .unwrap_or(false);
-                }
+            if outputs.iter().any(|output| output.path == path) {
+                outputs.retain(|output| output.path != path);
+                self.set_settings(target, &name, feature, Some(settings));
             }
         }
-        false
     }
 
     pub fn is_empty(&self) -> bool {
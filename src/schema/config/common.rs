@@ -1,7 +1,13 @@
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
+use super::cache::DeployedOutput;
 use crate::constants::features::{COMMANDS_FEATURE, INSTRUCTION_FEATURE, MCP_FEATURE};
+use crate::schema::command::ArgumentStyle;
+use crate::schema::common::{DeployMode, Target};
+use crate::schema::instruction::InstructionLayout;
+use crate::schema::mcp::ToolFilter;
+use crate::schema::variable::{Variable, merge_variables};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[serde(rename_all = "kebab-case")]
@@ -55,10 +61,42 @@ pub struct ConfigAgentSettings {
     pub disabled: Option<bool>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub variables: Option<HashMap<String, String>>,
+    pub variables: Option<HashMap<String, Variable>>,
+
+    /// How command argument placeholders are spelled for this provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<ArgumentStyle>,
+
+    /// Joins command namespaces and names, `/` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace_separator: Option<String>,
+
+    /// Whether outputs are rendered from the template, copied or linked from their source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<DeployMode>,
+
+    /// Whether instructions are deployed as one file or one file per fragment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<InstructionLayout>,
+
+    /// How MCP tool filters are passed to this provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_filter: Option<ToolFilter>,
+
+    /// Program rendering this provider instead of its template, as `exec:<path>`. Relative
+    /// paths start at the workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renderer: Option<String>,
+
+    /// Seconds the renderer program may run before it is stopped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renderer_timeout: Option<u64>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hash: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<Vec<DeployedOutput>>,
 }
 
 impl Targets {
@@ -73,6 +111,14 @@ impl Targets {
             custom: other.custom.clone().or_else(|| self.custom.clone()),
         }
     }
+
+    pub fn get(&self, target: Target) -> Option<&HashSet<String>> {
+        match target {
+            Target::Ide => self.ide.as_ref(),
+            Target::Cli => self.cli.as_ref(),
+            Target::Custom => self.custom.as_ref(),
+        }
+    }
 }
 
 impl Providers {
@@ -88,6 +134,26 @@ impl Providers {
         }
     }
 
+    pub fn get(&self, target: Target) -> Option<&HashMap<String, ConfigAgentAbilitySettings>> {
+        match target {
+            Target::Ide => self.ide.as_ref(),
+            Target::Cli => self.cli.as_ref(),
+            Target::Custom => self.custom.as_ref(),
+        }
+    }
+
+    pub fn get_provider(&self, target: Target, name: &str) -> Option<&ConfigAgentAbilitySettings> {
+        self.get(target).and_then(|map| map.get(name))
+    }
+
+    pub fn get_mut(&mut self, target: Target) -> &mut HashMap<String, ConfigAgentAbilitySettings> {
+        match target {
+            Target::Ide => self.ide.get_or_insert_with(HashMap::new),
+            Target::Cli => self.cli.get_or_insert_with(HashMap::new),
+            Target::Custom => self.custom.get_or_insert_with(HashMap::new),
+        }
+    }
+
     fn merge_provider_maps(
         base: Option<&HashMap<String, ConfigAgentAbilitySettings>>,
         override_map: Option<&HashMap<String, ConfigAgentAbilitySettings>>,
@@ -135,6 +201,19 @@ impl ConfigAgentAbilitySettings {
         }
     }
 
+    pub fn set_config(&mut self, feature: &str, settings: Option<ConfigAgentSettings>) {
+        match feature {
+            MCP_FEATURE => self.mcp = settings,
+            INSTRUCTION_FEATURE => self.instructions = settings,
+            COMMANDS_FEATURE => self.commands = settings,
+            _ => {}
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mcp.is_none() && self.instructions.is_none() && self.commands.is_none()
+    }
+
     fn merge_settings(
         base: Option<&ConfigAgentSettings>,
         override_settings: Option<&ConfigAgentSettings>,
@@ -158,24 +237,19 @@ impl ConfigAgentSettings {
             template: other.template.clone().or_else(|| self.template.clone()),
             target: other.target.clone().or_else(|| self.target.clone()),
             disabled: other.disabled.or(self.disabled),
-            variables: Self::merge_variables(self.variables.as_ref(), other.variables.as_ref()),
+            variables: merge_variables(self.variables.as_ref(), other.variables.as_ref()),
+            arguments: other.arguments.or(self.arguments),
+            namespace_separator: other
+                .namespace_separator
+                .clone()
+                .or_else(|| self.namespace_separator.clone()),
+            mode: other.mode.or(self.mode),
+            layout: other.layout.or(self.layout),
+            tool_filter: other.tool_filter.or(self.tool_filter),
+            renderer: other.renderer.clone().or_else(|| self.renderer.clone()),
+            renderer_timeout: other.renderer_timeout.or(self.renderer_timeout),
             hash: other.hash.clone().or_else(|| self.hash.clone()),
-        }
-    }
-
-    fn merge_variables(
-        base: Option<&HashMap<String, String>>,
-        override_vars: Option<&HashMap<String, String>>,
-    ) -> Option<HashMap<String, String>> {
-        match (base, override_vars) {
-            (None, None) => None,
-            (Some(b), None) => Some(b.clone()),
-            (None, Some(o)) => Some(o.clone()),
-            (Some(b), Some(o)) => {
-                let mut merged = b.clone();
-                merged.extend(o.clone());
-                Some(merged)
-            }
+            outputs: other.outputs.clone().or_else(|| self.outputs.clone()),
         }
     }
 }
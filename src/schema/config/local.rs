@@ -1,8 +1,11 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use super::common::{Providers, Targets};
 use super::traits::TomlConfig;
 use crate::constants::schema::CONFIG_SCHEMA;
+use crate::schema::common::Target;
+use crate::schema::package::PackageSource;
+use crate::schema::variable::Variable;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -17,7 +20,14 @@ pub struct LocalConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub providers: Option<Providers>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub variables: Option<HashMap<String, String>>,
+    pub variables: Option<HashMap<String, Variable>>,
+    /// Directories besides the workspace, home and config directories that deploys may
+    /// write to. Relative paths start at the workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_roots: Option<Vec<String>>,
+    /// Shared commands, MCP servers and instructions merged into the workspace's own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<BTreeMap<String, PackageSource>>,
 }
 
 impl LocalConfig {
@@ -28,6 +38,8 @@ impl LocalConfig {
             targets: None,
             providers: None,
             variables: None,
+            allowed_roots: None,
+            packages: None,
         }
     }
 
@@ -38,6 +50,8 @@ impl LocalConfig {
             targets: None,
             providers: None,
             variables: None,
+            allowed_roots: None,
+            packages: None,
         }
     }
 
@@ -48,6 +62,8 @@ impl LocalConfig {
             targets: None,
             providers: Some(providers),
             variables: None,
+            allowed_roots: None,
+            packages: None,
         }
     }
 
@@ -64,21 +80,24 @@ impl LocalConfig {
             }
         }
 
-        if let (Some(targets), Some(providers)) = (&self.targets, &self.providers) {
-            if let Some(custom_targets) = &targets.custom {
-                if let Some(custom_providers) = &providers.custom {
-                    for target in custom_targets {
-                        if !custom_providers.contains_key(target) {
-                            anyhow::bail!(
-                                "Custom target '{}' is defined in targets but has no provider configuration",
-                                target
-                            );
-                        }
-                    }
+        if let (Some(targets), Some(providers)) = (&self.targets, &self.providers)
+            && let Some(custom_targets) = targets.get(Target::Custom)
+            && let Some(custom_providers) = providers.get(Target::Custom)
+        {
+            for target in custom_targets {
+                if !custom_providers.contains_key(target) {
+                    anyhow::bail!(
+                        "Custom target '{}' is defined in targets but has no provider configuration",
+                        target
+                    );
                 }
             }
         }
 
+        for (name, package) in self.packages.iter().flatten() {
+            package.validate(name)?;
+        }
+
         Ok(())
     }
 
@@ -87,6 +106,7 @@ impl LocalConfig {
             && self.features.is_none()
             && self.targets.is_none()
             && self.providers.is_none()
+            && self.packages.is_none()
     }
 }
 
@@ -1,20 +1,579 @@
+use std::collections::BTreeMap;
 use std::fs;
+use std::path::{Component, Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use gray_matter::Matter;
+use gray_matter::engine::YAML;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json, to_value};
 
-use crate::{constants::file::INSTRUCTIONS_FILE, utils::path::get_application_dir};
+use crate::constants::file::INSTRUCTIONS_FILE;
+use crate::schema::common::FeatureItem;
+use crate::schema::config::ConfigAgentSettings;
+use crate::utils::fs::collect_markdown_files;
+use crate::utils::path::{get_application_dir, get_instructions_dir};
 
-#[derive(Serialize, Deserialize)]
-pub(crate) struct Instruction {
-    content: String,
+/// Fragments whose file name starts with this prefix are only pulled in through includes.
+const PARTIAL_PREFIX: char = '_';
+
+/// How a provider receives the instruction fragments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstructionLayout {
+    /// All fragments concatenated into one file, e.g. `AGENTS.md`.
+    #[default]
+    Single,
+    /// One file per fragment, for agents reading a rules directory.
+    Fragments,
+}
+
+/// When an agent should apply a fragment.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstructionTrigger {
+    /// Always part of the context.
+    Always,
+    /// While working on files matching the fragment's `globs`.
+    Globs,
+    /// Whenever the agent decides the fragment is relevant.
+    Model,
+    /// Only when the user refers to the fragment.
+    Manual,
+}
+
+impl InstructionTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::Globs => "globs",
+            Self::Model => "model",
+            Self::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstructionMetadata {
+    /// Position among the other fragments, lower first. Ties are broken by file path.
+    #[serde(default)]
+    pub order: i32,
+    /// Providers receiving the fragment, every provider when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub agents: Vec<String>,
+    /// Derived from `always_apply`, `globs` and `description` when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<InstructionTrigger>,
+    /// Files the fragment applies to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub globs: Vec<String>,
+    /// What the fragment is about, used by agents deciding whether to apply it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Cursor's spelling of `when: always`. When `false`, the fragment applies to its `globs`
+    /// or, without globs, whenever its `description` matches the task.
+    #[serde(
+        default,
+        alias = "alwaysApply",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub always_apply: Option<bool>,
+    /// Keys dotagents doesn't know about, passed on to templates.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstructionFragment {
+    /// Path of the fragment relative to the instructions directory, without extension.
+    pub name: String,
+    #[serde(flatten)]
+    pub metadata: InstructionMetadata,
+    /// Body of the fragment with its includes expanded.
+    pub content: String,
+    #[serde(skip)]
+    pub source: PathBuf,
+}
+
+#[derive(Clone)]
+pub struct Instruction {
+    pub fragments: Vec<InstructionFragment>,
+}
+
+impl InstructionFragment {
+    pub fn trigger(&self) -> InstructionTrigger {
+        let metadata = &self.metadata;
+
+        match (metadata.when, metadata.always_apply) {
+            (Some(trigger), _) => trigger,
+            (None, Some(true)) => InstructionTrigger::Always,
+            (None, _) if !metadata.globs.is_empty() => InstructionTrigger::Globs,
+            (None, Some(false)) if metadata.description.is_some() => InstructionTrigger::Model,
+            (None, Some(false)) => InstructionTrigger::Manual,
+            (None, None) => InstructionTrigger::Always,
+        }
+    }
+
+    /// The fragment as part of a single instructions file. Fragments that don't always apply
+    /// get a heading saying when they do, as such files have no notion of scope.
+    fn inline_content(&self) -> String {
+        let content = self.content.trim();
+
+        let scope = match self.trigger() {
+            InstructionTrigger::Always => return content.to_string(),
+            InstructionTrigger::Globs => format!(
+                "Applies to files matching {}.",
+                self.metadata
+                    .globs
+                    .iter()
+                    .map(|glob| format!("`{}`", glob))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            InstructionTrigger::Model => "Applies when relevant to the task.".to_string(),
+            InstructionTrigger::Manual => "Applies only when explicitly requested.".to_string(),
+        };
+
+        let title = self.metadata.description.as_deref().unwrap_or(&self.name);
+        format!("## {}\n\n_{}_\n\n{}", title, scope, content)
+    }
+
+    pub fn applies_to(&self, agent: &str) -> bool {
+        self.metadata.agents.is_empty() || self.metadata.agents.iter().any(|name| name == agent)
+    }
+
+    fn from_file(root: &Path, path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).context("failed to read file")?;
+        let (metadata, content) = parse_markdown(&text)?;
+        let content = expand_includes(root, &content, &mut vec![path.to_path_buf()])?;
+
+        let name = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let fragment = Self {
+            name,
+            metadata,
+            content,
+            source: path.to_path_buf(),
+        };
+
+        if let (Some(when), Some(true)) = (fragment.metadata.when, fragment.metadata.always_apply)
+            && when != InstructionTrigger::Always
+        {
+            bail!("`always_apply: true` contradicts `when: {}`", when.as_str());
+        }
+
+        match fragment.trigger() {
+            InstructionTrigger::Globs if fragment.metadata.globs.is_empty() => {
+                bail!("`when: globs` needs at least one entry in `globs`")
+            }
+            InstructionTrigger::Always | InstructionTrigger::Model | InstructionTrigger::Manual
+                if !fragment.metadata.globs.is_empty() =>
+            {
+                bail!("`globs` are only used together with `when: globs`")
+            }
+            _ => Ok(fragment),
+        }
+    }
+
+    fn to_template_value(&self) -> Result<Value> {
+        let mut value = to_value(self).context("failed to serialize instruction fragment")?;
+        value["when"] = to_value(self.trigger())?;
+        Ok(value)
+    }
 }
 
 impl Instruction {
+    /// Loads the fragments of the instructions directory in order, falling back to a single
+    /// `INSTRUCTIONS.md` for workspaces set up before fragments existed.
     pub fn from_application() -> Result<Self> {
-        let dir = get_application_dir()?;
-        let path = dir.join(INSTRUCTIONS_FILE);
-        let content = fs::read_to_string(path)?;
-        Ok(Self { content })
+        let dir = get_instructions_dir()?;
+
+        if !dir.is_dir() {
+            let path = get_application_dir()?.join(INSTRUCTIONS_FILE);
+            let content =
+                fs::read_to_string(&path).context(format!("failed to read {}", path.display()))?;
+
+            return Ok(Self {
+                fragments: vec![InstructionFragment {
+                    name: "instructions".into(),
+                    metadata: InstructionMetadata::default(),
+                    content,
+                    source: path,
+                }],
+            });
+        }
+
+        Self::from_dir(&dir)
+    }
+
+    /// Loads the fragments below `dir`, ordered by their `order` and then by path.
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let mut files = Vec::new();
+        collect_markdown_files(dir, &mut files)?;
+        files.sort();
+
+        let mut fragments = Vec::new();
+        let mut errors = Vec::new();
+
+        for path in &files {
+            let is_partial = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(PARTIAL_PREFIX));
+
+            if is_partial {
+                continue;
+            }
+
+            match InstructionFragment::from_file(dir, path) {
+                Ok(fragment) => fragments.push(fragment),
+                Err(e) => errors.push(format!(
+                    "{}: {:#}",
+                    path.strip_prefix(dir).unwrap_or(path).display(),
+                    e
+                )),
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!(
+                "found {} invalid instruction file(s) in {}:\n{}",
+                errors.len(),
+                dir.display(),
+                errors
+                    .iter()
+                    .map(|error| format!("      - {}", error))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
+        // Stable, so fragments sharing an order keep their path order.
+        fragments.sort_by_key(|fragment| fragment.metadata.order);
+
+        Ok(Self { fragments })
+    }
+
+    /// Renders the content of every fragment, which may use the template context of the
+    /// provider it is deployed to.
+    pub fn render(&self, render: impl Fn(&str) -> Result<String>) -> Result<Self> {
+        let fragments = self
+            .fragments
+            .iter()
+            .map(|fragment| {
+                let content = render(&fragment.content)
+                    .context(format!("failed to render instruction {}", fragment.name))?;
+                Ok(InstructionFragment {
+                    content,
+                    ..fragment.clone()
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { fragments })
+    }
+
+    /// Template data for `agent`, one item for every output its layout produces. Fragments
+    /// meant for other agents are left out.
+    pub(crate) fn items(
+        &self,
+        agent: Option<(&str, &ConfigAgentSettings)>,
+    ) -> Result<Vec<FeatureItem>> {
+        let fragments: Vec<&InstructionFragment> = self
+            .fragments
+            .iter()
+            .filter(|fragment| agent.is_none_or(|(name, _)| fragment.applies_to(name)))
+            .collect();
+
+        let layout = agent
+            .and_then(|(_, settings)| settings.layout)
+            .unwrap_or_default();
+
+        if layout == InstructionLayout::Fragments {
+            return fragments
+                .into_iter()
+                .map(|fragment| {
+                    Ok(FeatureItem {
+                        id: Some(fragment.name.clone()),
+                        data: json!({ "instruction": fragment.to_template_value()? }),
+                        source: Some(fragment.source.clone()),
+                    })
+                })
+                .collect();
+        }
+
+        if fragments.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let content = fragments
+            .iter()
+            .map(|fragment| fragment.inline_content())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let values = fragments
+            .iter()
+            .map(|fragment| fragment.to_template_value())
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(vec![FeatureItem {
+            id: None,
+            data: json!({ "instruction": { "content": content + "\n", "fragments": values } }),
+            // Only a lone fragment can stand in for the whole file.
+            source: match fragments.as_slice() {
+                [fragment] => Some(fragment.source.clone()),
+                _ => None,
+            },
+        }])
+    }
+}
+
+fn parse_markdown(text: &str) -> Result<(InstructionMetadata, String)> {
+    let matter = Matter::<YAML>::new();
+    let parsed = matter
+        .parse::<InstructionMetadata>(text)
+        .context("failed to parse markdown")?;
+
+    Ok((parsed.data.unwrap_or_default(), parsed.content))
+}
+
+/// Replaces every `{{> name}}` with the body of `name.md` from the instructions directory,
+/// expanding its own includes in turn. `stack` holds the files being expanded.
+fn expand_includes(root: &Path, text: &str, stack: &mut Vec<PathBuf>) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{>") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        let name = rest[start + 3..start + end].trim();
+
+        // Includes name fragments of the instructions directory, never other files.
+        if name.is_empty()
+            || Path::new(name)
+                .components()
+                .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            bail!(
+                "cannot include '{}', includes name a fragment relative to the instructions directory without '..'",
+                name
+            );
+        }
+
+        let mut path = root.join(name);
+        if path.extension().is_none() {
+            path.set_extension("md");
+        }
+
+        if stack.contains(&path) {
+            bail!("including '{}' forms a cycle", name);
+        }
+
+        let included = fs::read_to_string(&path).context(format!(
+            "failed to include '{}' from {}",
+            name,
+            path.display()
+        ))?;
+        let (_, body) =
+            parse_markdown(&included).context(format!("failed to include '{}'", name))?;
+
+        stack.push(path);
+        let body = expand_includes(root, &body, stack)?;
+        stack.pop();
+
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(body.trim_end());
+        rest = &rest[start + end + 2..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instructions(files: &[(&str, &str)]) -> (tempfile::TempDir, Result<Instruction>) {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let instruction = Instruction::from_dir(dir.path());
+        (dir, instruction)
+    }
+
+    #[test]
+    fn expands_nested_includes() {
+        let (_dir, instruction) = instructions(&[
+            ("general.md", "# General\n\n{{> _style}}\n\nDone.\n"),
+            (
+                "_style.md",
+                "---\norder: 1\n---\nUse tabs.\n{{> shared/_naming }}\n",
+            ),
+            ("shared/_naming.md", "Name things well.\n"),
+        ]);
+        let instruction = instruction.unwrap();
+
+        assert_eq!(instruction.fragments.len(), 1);
+        assert_eq!(
+            instruction.fragments[0].content,
+            "# General\n\nUse tabs.\nName things well.\n\nDone."
+        );
+    }
+
+    #[test]
+    fn rejects_include_cycles() {
+        let (_dir, instruction) = instructions(&[
+            ("general.md", "{{> _a}}\n"),
+            ("_a.md", "{{> _b}}\n"),
+            ("_b.md", "{{> _a}}\n"),
+        ]);
+        let error = format!("{:#}", instruction.err().unwrap());
+
+        assert!(error.contains("including '_a' forms a cycle"), "{}", error);
+
+        let (_dir, instruction) = instructions(&[("general.md", "{{> general}}\n")]);
+        let error = format!("{:#}", instruction.err().unwrap());
+
+        assert!(
+            error.contains("including 'general' forms a cycle"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn keeps_includes_inside_the_instructions_directory() {
+        for name in ["../secret", "/etc/passwd", "shared/../../secret", ""] {
+            let (_dir, instruction) =
+                instructions(&[("general.md", &format!("{{{{> {}}}}}\n", name))]);
+            let error = format!("{:#}", instruction.err().unwrap());
+
+            assert!(
+                error.contains(&format!("cannot include '{}'", name)),
+                "{}",
+                error
+            );
+        }
+
+        let (_dir, instruction) = instructions(&[("general.md", "{{> _missing}}\n")]);
+        let error = format!("{:#}", instruction.err().unwrap());
+
+        assert!(error.contains("failed to include '_missing'"), "{}", error);
+    }
+
+    const FRAGMENTS: &[(&str, &str)] = &[
+        ("general.md", "Be brief.\n"),
+        (
+            "frontend/styles.md",
+            "---\nglobs: [\"*.css\"]\n---\nUse variables.\n",
+        ),
+        (
+            "review.md",
+            "---\ndescription: Reviewing code\nalways_apply: false\n---\nCheck tests.\n",
+        ),
+        ("release.md", "---\nwhen: manual\n---\nBump the version.\n"),
+        (
+            "setup.md",
+            "---\norder: -1\nagents: [cursor]\n---\nRun setup.\n",
+        ),
+    ];
+
+    fn items(agent: &str, layout: InstructionLayout) -> Vec<FeatureItem> {
+        let (_dir, instruction) = instructions(FRAGMENTS);
+        let settings = ConfigAgentSettings {
+            layout: Some(layout),
+            ..Default::default()
+        };
+
+        instruction
+            .unwrap()
+            .items(Some((agent, &settings)))
+            .unwrap()
+    }
+
+    #[test]
+    fn renders_one_item_per_fragment_in_the_fragments_layout() {
+        let items = items("windsurf", InstructionLayout::Fragments);
+        let ids: Vec<_> = items.iter().filter_map(|item| item.id.as_deref()).collect();
+
+        assert_eq!(ids, ["frontend/styles", "general", "release", "review"]);
+        assert_eq!(
+            items[0].data["instruction"],
+            json!({
+                "name": "frontend/styles",
+                "order": 0,
+                "globs": ["*.css"],
+                "when": "globs",
+                "content": "Use variables.",
+            })
+        );
+        assert_eq!(items[3].data["instruction"]["when"], "model");
+        assert!(
+            items[0]
+                .source
+                .as_ref()
+                .is_some_and(|source| source.ends_with("frontend/styles.md"))
+        );
+    }
+
+    #[test]
+    fn inlines_every_fragment_in_the_single_layout() {
+        let items = items("windsurf", InstructionLayout::Single);
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].id, None);
+        assert_eq!(items[0].source, None);
+        assert_eq!(
+            items[0].data["instruction"]["content"],
+            "## frontend/styles\n\n_Applies to files matching `*.css`._\n\nUse variables.\n\n\
+             Be brief.\n\n\
+             ## release\n\n_Applies only when explicitly requested._\n\nBump the version.\n\n\
+             ## Reviewing code\n\n_Applies when relevant to the task._\n\nCheck tests.\n"
+        );
+        assert_eq!(
+            items[0].data["instruction"]["fragments"]
+                .as_array()
+                .map(Vec::len),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn keeps_fragments_for_other_agents_out_of_both_layouts() {
+        let fragments = items("cursor", InstructionLayout::Fragments);
+        let single = items("cursor", InstructionLayout::Single);
+
+        assert_eq!(fragments.len(), 5);
+        assert_eq!(fragments[0].id.as_deref(), Some("setup"));
+        assert!(
+            single[0].data["instruction"]["content"]
+                .as_str()
+                .unwrap()
+                .starts_with("Run setup.\n\n## frontend/styles")
+        );
+
+        let (_dir, instruction) = instructions(&[FRAGMENTS[4]]);
+        let settings = ConfigAgentSettings::default();
+        let items = instruction
+            .unwrap()
+            .items(Some(("windsurf", &settings)))
+            .unwrap();
+
+        assert!(items.is_empty());
     }
 }
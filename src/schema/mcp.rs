@@ -1,22 +1,55 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use serde::{Deserialize, Serialize};
-use serde_json::{Value, to_value};
+use serde_json::{Map, Value, json, to_value};
+use std::path::Path;
 use std::{collections::HashMap, fs};
 
-use crate::{constants::file::MCP_FILE, utils::path::get_application_dir};
+use crate::schema::config::ConfigAgentSettings;
+use crate::utils::glob::{is_glob, matches_glob};
+use crate::utils::{did_you_mean, merge_json};
+use crate::utils::{jsonc::strip_comments, path::get_mcp_file};
 
 #[derive(Serialize, Deserialize)]
-pub(crate) struct McpConfig {
+pub struct McpConfig {
     #[serde(rename = "$schema")]
     pub schema: String,
     pub servers: HashMap<String, ServerConfig>,
+
+    /// Tools reported by each server during a handshake, when deploy was asked to look.
+    #[serde(skip)]
+    pub discovered_tools: HashMap<String, Vec<String>>,
+}
+
+/// How a provider narrows down the tools of a server. `enabledTools` and `disabledTools`
+/// patterns are translated into whichever list the provider understands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolFilter {
+    /// The provider takes the list of tools to allow.
+    Allow,
+    /// The provider takes the list of tools to hide.
+    Deny,
+    /// The provider exposes every tool of a server.
+    #[default]
+    None,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CommonConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub disabled: Option<bool>,
-    #[serde(rename = "disabledTools")]
+    #[serde(
+        rename = "enabledTools",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub enabled_tools: Option<Vec<String>>,
+    #[serde(
+        rename = "disabledTools",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub disabled_tools: Option<Vec<String>>,
 }
 
@@ -29,6 +62,7 @@ pub enum ServerConfig {
         #[serde(flatten)]
         common: Option<CommonConfig>,
         url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         headers: Option<HashMap<String, String>>,
     },
 
@@ -37,16 +71,151 @@ pub enum ServerConfig {
         #[serde(flatten)]
         common: Option<CommonConfig>,
         command: String,
+        #[serde(default)]
         args: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         cwd: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         env: Option<HashMap<String, String>>,
+        #[serde(rename = "envFile", default, skip_serializing_if = "Option::is_none")]
         env_file: Option<String>,
     },
 }
 
+impl ServerConfig {
+    pub fn common(&self) -> Option<&CommonConfig> {
+        match self {
+            Self::Http { common, .. } | Self::Stdio { common, .. } => common.as_ref(),
+        }
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.common()
+            .and_then(|common| common.disabled)
+            .unwrap_or(false)
+    }
+
+    fn enabled_tools(&self) -> &[String] {
+        self.common()
+            .and_then(|common| common.enabled_tools.as_deref())
+            .unwrap_or_default()
+    }
+
+    fn disabled_tools(&self) -> &[String] {
+        self.common()
+            .and_then(|common| common.disabled_tools.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn has_tool_filters(&self) -> bool {
+        !self.enabled_tools().is_empty() || !self.disabled_tools().is_empty()
+    }
+
+    /// Names of `tools` left after applying the enabled and disabled patterns.
+    fn filter_tools<'a>(&self, tools: &'a [String]) -> Vec<&'a String> {
+        let matches_any = |patterns: &[String], tool: &str| {
+            patterns.iter().any(|pattern| matches_glob(pattern, tool))
+        };
+
+        tools
+            .iter()
+            .filter(|tool| {
+                self.enabled_tools().is_empty() || matches_any(self.enabled_tools(), tool)
+            })
+            .filter(|tool| !matches_any(self.disabled_tools(), tool))
+            .collect()
+    }
+
+    /// The `(allowed, denied)` tool lists of the server for a provider using `filter`. Without
+    /// `discovered` tools only literal names can be passed on, anything else is warned about.
+    fn tool_lists(
+        &self,
+        name: &str,
+        provider: &str,
+        filter: ToolFilter,
+        discovered: Option<&[String]>,
+    ) -> (Option<Vec<String>>, Option<Vec<String>>) {
+        if !self.has_tool_filters() {
+            return (None, None);
+        }
+
+        if let Some(tools) = discovered {
+            let kept = self.filter_tools(tools);
+
+            return match filter {
+                ToolFilter::Allow => (Some(kept.into_iter().cloned().collect()), None),
+                ToolFilter::Deny => {
+                    let denied = tools.iter().filter(|tool| !kept.contains(tool));
+                    (None, Some(denied.cloned().collect()))
+                }
+                ToolFilter::None => {
+                    log::warn!("{} cannot filter the tools of server {}", provider, name);
+                    (None, None)
+                }
+            };
+        }
+
+        let (supported, unsupported, field) = match filter {
+            ToolFilter::Allow => (self.enabled_tools(), self.disabled_tools(), "disabledTools"),
+            ToolFilter::Deny => (self.disabled_tools(), self.enabled_tools(), "enabledTools"),
+            ToolFilter::None => {
+                log::warn!("{} cannot filter the tools of server {}", provider, name);
+                return (None, None);
+            }
+        };
+
+        if !unsupported.is_empty() {
+            log::warn!(
+                "{} cannot express {} of server {} without its tool list, deploy with --discover-tools",
+                provider,
+                field,
+                name
+            );
+        }
+
+        if supported.is_empty() {
+            return (None, None);
+        }
+
+        if supported.iter().any(|pattern| is_glob(pattern)) {
+            log::warn!(
+                "{} cannot expand the tool patterns of server {} without its tool list, deploy with --discover-tools",
+                provider,
+                name
+            );
+            return (None, None);
+        }
+
+        match filter {
+            ToolFilter::Allow => (Some(supported.to_vec()), None),
+            _ => (None, Some(supported.to_vec())),
+        }
+    }
+
+    pub fn transport(&self) -> &'static str {
+        match self {
+            Self::Http { .. } => "http",
+            Self::Stdio { .. } => "stdio",
+        }
+    }
+
+    /// The URL or command line the server is reached through.
+    pub fn endpoint(&self) -> String {
+        match self {
+            Self::Http { url, .. } => url.clone(),
+            Self::Stdio { command, args, .. } => std::iter::once(command)
+                .chain(args)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
 impl McpConfig {
+    /// Parses the config, allowing the comments and trailing commas of JSONC.
     pub fn from_json(json: &str) -> Result<Self> {
-        let result = serde_json::from_str::<McpConfig>(json)
+        let result = serde_json::from_str::<McpConfig>(&strip_comments(json))
             .context("failed to parse MCP config from JSON")?;
 
         Ok(result)
@@ -59,17 +228,249 @@ impl McpConfig {
         Ok(result)
     }
 
+    /// Template data of the config. `enabled_servers` holds the servers that are not disabled,
+    /// for agents that have no way to keep a server configured but switched off.
     pub fn to_json_value(&self) -> Result<Value> {
-        let result = to_value(&self).context("failed to convert to json value")?;
+        let mut result = to_value(self).context("failed to convert to json value")?;
+
+        let enabled: Map<String, Value> = result["servers"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(_, server)| server["disabled"] != Value::Bool(true))
+            .map(|(name, server)| (name.clone(), server.clone()))
+            .collect();
+        result["enabled_servers"] = Value::Object(enabled);
+
         Ok(result)
     }
 
+    /// Template data of the config for `provider`, with the tool patterns of every server
+    /// translated into the `allowed_tools` or `denied_tools` list the provider understands.
+    pub fn to_template_value(
+        &self,
+        provider: Option<(&str, &ConfigAgentSettings)>,
+    ) -> Result<Value> {
+        let mut value = self.to_json_value()?;
+
+        let Some((provider, settings)) = provider else {
+            return Ok(value);
+        };
+
+        let filter = settings.tool_filter.unwrap_or_default();
+
+        for (name, server) in &self.servers {
+            let discovered = self.discovered_tools.get(name).map(Vec::as_slice);
+            let (allowed, denied) = server.tool_lists(name, provider, filter, discovered);
+            let lists = json!({ "allowed_tools": allowed, "denied_tools": denied });
+
+            for key in ["servers", "enabled_servers"] {
+                if let Some(server) = value[key].get_mut(name) {
+                    *server = merge_json(server, &lists);
+                }
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Fails when an `enabledTools` or `disabledTools` pattern matches none of the tools a
+    /// server reported, which usually means a typo.
+    pub fn check_tool_patterns(&self) -> Result<()> {
+        let mut names: Vec<&String> = self.discovered_tools.keys().collect();
+        names.sort();
+
+        for name in names {
+            let (Some(server), Some(tools)) =
+                (self.servers.get(name), self.discovered_tools.get(name))
+            else {
+                continue;
+            };
+
+            let patterns = server
+                .enabled_tools()
+                .iter()
+                .map(|pattern| ("enabledTools", pattern))
+                .chain(server.disabled_tools().iter().map(|p| ("disabledTools", p)));
+
+            for (field, pattern) in patterns {
+                if tools.iter().any(|tool| matches_glob(pattern, tool)) {
+                    continue;
+                }
+
+                match did_you_mean(pattern, tools.iter().map(String::as_str)) {
+                    Some(suggestion) => bail!(
+                        "{} pattern '{}' of server {} matches no tool, did you mean '{}'?",
+                        field,
+                        pattern,
+                        name,
+                        suggestion
+                    ),
+                    None => bail!(
+                        "{} pattern '{}' of server {} matches no tool",
+                        field,
+                        pattern,
+                        name
+                    ),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn from_application() -> Result<Self> {
-        let dir = get_application_dir()?;
+        Self::from_file(&get_mcp_file()?)
+    }
 
-        let config_path = dir.join(MCP_FILE);
-        let config = fs::read_to_string(config_path).context("failed to read MCP config file")?;
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let config = fs::read_to_string(path).context("failed to read MCP config file")?;
 
         Self::from_json(&config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stdio_server_round_trips_camel_case_fields() {
+        let config = json!({
+            "type": "stdio",
+            "command": "server",
+            "args": ["--stdio"],
+            "cwd": "tools",
+            "env": { "TOKEN": "${TOKEN}" },
+            "envFile": ".env",
+            "enabledTools": ["read_*"],
+            "disabledTools": ["read_secret"],
+        });
+
+        let server: ServerConfig = serde_json::from_value(config.clone()).unwrap();
+        let ServerConfig::Stdio { env_file, .. } = &server else {
+            panic!("expected a stdio server");
+        };
+
+        assert_eq!(env_file.as_deref(), Some(".env"));
+        assert_eq!(server.enabled_tools(), ["read_*"]);
+        assert_eq!(server.disabled_tools(), ["read_secret"]);
+        assert_eq!(to_value(&server).unwrap(), config);
+    }
+
+    #[test]
+    fn http_server_round_trips() {
+        let config = json!({
+            "type": "http",
+            "url": "https://example.com/mcp",
+            "headers": { "Authorization": "Bearer ${TOKEN}" },
+            "disabled": true,
+        });
+
+        let server: ServerConfig = serde_json::from_value(config.clone()).unwrap();
+
+        assert!(server.is_disabled());
+        assert_eq!(to_value(&server).unwrap(), config);
+    }
+
+    fn server(enabled: &[&str], disabled: &[&str]) -> ServerConfig {
+        serde_json::from_value(json!({
+            "type": "stdio",
+            "command": "server",
+            "enabledTools": enabled,
+            "disabledTools": disabled,
+        }))
+        .unwrap()
+    }
+
+    fn tools(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn resolves_patterns_against_discovered_tools() {
+        let server = server(&["read_*", "list"], &["read_secret"]);
+        let discovered = tools(&["read_file", "read_secret", "list", "write_file"]);
+        let lists = |filter| server.tool_lists("files", "agent", filter, Some(&discovered));
+
+        assert_eq!(
+            lists(ToolFilter::Allow),
+            (Some(tools(&["read_file", "list"])), None)
+        );
+        assert_eq!(
+            lists(ToolFilter::Deny),
+            (None, Some(tools(&["read_secret", "write_file"])))
+        );
+        assert_eq!(lists(ToolFilter::None), (None, None));
+    }
+
+    #[test]
+    fn passes_on_literal_names_without_discovered_tools() {
+        let allow_only = server(&["read_file", "list"], &[]);
+        let deny_only = server(&[], &["delete"]);
+
+        assert_eq!(
+            allow_only.tool_lists("files", "agent", ToolFilter::Allow, None),
+            (Some(tools(&["read_file", "list"])), None)
+        );
+        assert_eq!(
+            deny_only.tool_lists("files", "agent", ToolFilter::Deny, None),
+            (None, Some(tools(&["delete"])))
+        );
+
+        // An allow-list provider cannot express denied names, and the other way around.
+        assert_eq!(
+            deny_only.tool_lists("files", "agent", ToolFilter::Allow, None),
+            (None, None)
+        );
+        assert_eq!(
+            allow_only.tool_lists("files", "agent", ToolFilter::Deny, None),
+            (None, None)
+        );
+
+        // Patterns need the tool list to be expanded.
+        assert_eq!(
+            server(&["read_*"], &[]).tool_lists("files", "agent", ToolFilter::Allow, None),
+            (None, None)
+        );
+        assert_eq!(
+            server(&[], &[]).tool_lists("files", "agent", ToolFilter::Allow, None),
+            (None, None)
+        );
+    }
+
+    fn config(server: ServerConfig, discovered: &[&str]) -> McpConfig {
+        McpConfig {
+            schema: String::new(),
+            servers: HashMap::from([("files".to_string(), server)]),
+            discovered_tools: HashMap::from([("files".to_string(), tools(discovered))]),
+        }
+    }
+
+    #[test]
+    fn rejects_patterns_matching_no_discovered_tool() {
+        let discovered = ["read_file", "write_file"];
+
+        assert!(
+            config(server(&["read_*"], &["write_file"]), &discovered)
+                .check_tool_patterns()
+                .is_ok()
+        );
+
+        let error = config(server(&["read_fle"], &[]), &discovered)
+            .check_tool_patterns()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "enabledTools pattern 'read_fle' of server files matches no tool, did you mean 'read_file'?"
+        );
+
+        let error = config(server(&[], &["zzz_*"]), &discovered)
+            .check_tool_patterns()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "disabledTools pattern 'zzz_*' of server files matches no tool"
+        );
+    }
+}
@@ -1,6 +1,10 @@
+pub(crate) mod backup;
 pub(crate) mod builder;
 pub(crate) mod command;
 pub(crate) mod common;
 pub(crate) mod config;
 pub(crate) mod instruction;
 pub(crate) mod mcp;
+pub(crate) mod package;
+pub(crate) mod starter;
+pub(crate) mod variable;
@@ -0,0 +1,131 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::config::TomlConfig;
+use crate::utils::fs::write_atomic;
+
+/// Where a package comes from, as listed under `[packages]` in `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct PackageSource {
+    /// Directory of the package, relative to the workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    /// URL of a git repository holding the package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+
+    /// Tag, branch or commit of `git` to use, the default branch when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+}
+
+impl PackageSource {
+    pub fn validate(&self, name: &str) -> Result<()> {
+        // Git packages are checked out into a directory of that name, which gets removed when
+        // the checkout is stale.
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            bail!(
+                "'{}' is not a valid package name, it can't be empty, '.', '..' or contain path separators",
+                name
+            );
+        }
+
+        match (&self.path, &self.git) {
+            (Some(_), Some(_)) => bail!("package '{}' sets both `path` and `git`", name),
+            (None, None) => bail!("package '{}' needs either `path` or `git`", name),
+            (Some(_), None) if self.rev.is_some() => {
+                bail!("package '{}' sets `rev`, which only applies to `git`", name)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `packages.lock`, pinning every git package to the commit deploys use.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct PackageLock {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockedPackage {
+    pub name: String,
+    pub git: String,
+
+    /// The `rev` of the package when it was locked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+
+    /// Commit `rev` resolved to.
+    pub revision: String,
+}
+
+impl PackageLock {
+    /// The lockfile at `path`, empty when there is none yet.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content =
+            fs::read_to_string(path).context(format!("failed to read {}", path.display()))?;
+        Self::from_toml(&content).context(format!("failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, self.to_toml()?.as_bytes())
+            .context(format!("failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+}
+
+impl TomlConfig for PackageLock {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git_package() -> PackageSource {
+        PackageSource {
+            path: None,
+            git: Some("https://example.com/shared.git".into()),
+            rev: None,
+        }
+    }
+
+    #[test]
+    fn accepts_plain_names() {
+        assert!(git_package().validate("shared").is_ok());
+        assert!(git_package().validate("team.shared-v2").is_ok());
+    }
+
+    #[test]
+    fn rejects_names_leaving_the_checkouts_directory() {
+        for name in [
+            "",
+            ".",
+            "..",
+            "../shared",
+            "team/shared",
+            "team\\shared",
+            "/tmp",
+        ] {
+            assert!(
+                git_package().validate(name).is_err(),
+                "'{}' was accepted",
+                name
+            );
+        }
+    }
+}
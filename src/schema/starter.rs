@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::schema::config::TomlConfig;
+use crate::utils::fs::write_atomic;
+
+/// `starter.toml` at the root of a starter, declaring the placeholders it uses.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct StarterManifest {
+    #[serde(default)]
+    pub variables: BTreeMap<String, StarterVariable>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct StarterVariable {
+    /// Shown when asking for a value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Used when no value is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+/// Where a workspace was started from, so later changes to the starter can be pulled in.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct StarterOrigin {
+    /// Directory or git URL of the starter.
+    pub source: String,
+
+    /// Commit the files were taken from, for git sources.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+
+    /// Values the placeholders were filled with.
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+
+    /// Hash of every file as the starter wrote it, keyed by its path inside the application
+    /// directory. Files whose hash no longer matches were edited and are left alone.
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+}
+
+impl StarterOrigin {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).context(format!("failed to read {}", path.display()))?;
+        Self::from_toml(&content).context(format!("failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, self.to_toml()?.as_bytes())
+            .context(format!("failed to write {}", path.display()))
+    }
+}
+
+impl TomlConfig for StarterManifest {}
+impl TomlConfig for StarterOrigin {}
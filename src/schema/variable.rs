@@ -0,0 +1,455 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Deserializer, Serialize, de};
+use serde_json::{Map, Value};
+
+/// A template variable as written in `config.toml`. Besides plain values, a variable can be
+/// read from the environment, the output of a command or a file when the deploy starts, which
+/// a table asks for with the reserved `from` key, e.g. `{ from = "env", env = "API_URL" }`.
+/// Tables without `from` are plain values, whatever their keys.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum Variable {
+    Source(VariableSource),
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Variable>),
+    Table(BTreeMap<String, Variable>),
+}
+
+/// Where a variable is read from, named by its `from` key.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "from", rename_all = "lowercase")]
+pub enum VariableSource {
+    Env(EnvSource),
+    Command(CommandSource),
+    File(FileSource),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EnvSource {
+    pub env: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CommandSource {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FileSource {
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+const SOURCE_KEY: &str = "from";
+
+// Not derived: an untagged enum would take any table with an `env`, `command` or `file` key
+// for a source, and report a mistyped source as a mismatch of every variant.
+impl<'de> Deserialize<'de> for Variable {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Variable::try_from(Value::deserialize(deserializer)?)
+            .map_err(|e| de::Error::custom(format!("{:#}", e)))
+    }
+}
+
+impl TryFrom<Value> for Variable {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        let variable = match value {
+            Value::Null => bail!("variables can't be null"),
+            Value::Bool(value) => Self::Boolean(value),
+            Value::Number(number) => match number.as_i64() {
+                Some(value) => Self::Integer(value),
+                None => Self::Float(number.as_f64().unwrap_or_default()),
+            },
+            Value::String(value) => Self::String(value),
+            Value::Array(values) => Self::Array(
+                values
+                    .into_iter()
+                    .map(Variable::try_from)
+                    .collect::<Result<_>>()?,
+            ),
+            Value::Object(table) if table.contains_key(SOURCE_KEY) => Self::Source(
+                serde_json::from_value(Value::Object(table)).context("invalid variable source")?,
+            ),
+            Value::Object(table) => Self::Table(
+                table
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let variable = Variable::try_from(value).context(format!("in {}", key))?;
+                        Ok((key, variable))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+        };
+
+        Ok(variable)
+    }
+}
+
+impl Variable {
+    /// Resolves the variable into a template value. Relative files and commands are resolved
+    /// from `base_dir`.
+    pub fn resolve(&self, base_dir: &Path) -> Result<Value> {
+        let value = match self {
+            Self::Source(VariableSource::Env(source)) => Value::String(source.resolve()?),
+            Self::Source(VariableSource::Command(source)) => {
+                Value::String(source.resolve(base_dir)?)
+            }
+            Self::Source(VariableSource::File(source)) => Value::String(source.resolve(base_dir)?),
+            Self::Boolean(value) => Value::Bool(*value),
+            Self::Integer(value) => Value::from(*value),
+            Self::Float(value) => Value::from(*value),
+            Self::String(value) => Value::String(value.clone()),
+            Self::Array(values) => Value::Array(
+                values
+                    .iter()
+                    .map(|value| value.resolve(base_dir))
+                    .collect::<Result<_>>()?,
+            ),
+            Self::Table(table) => Value::Object(
+                table
+                    .iter()
+                    .map(|(key, value)| {
+                        let resolved = value
+                            .resolve(base_dir)
+                            .context(format!("failed to resolve {}", key))?;
+                        Ok((key.clone(), resolved))
+                    })
+                    .collect::<Result<Map<_, _>>>()?,
+            ),
+        };
+
+        Ok(value)
+    }
+
+    /// Tables are merged key by key, any other value is replaced by `other`.
+    pub fn merge(&self, other: &Variable) -> Variable {
+        match (self, other) {
+            (Self::Table(base), Self::Table(overrides)) => {
+                let mut merged = base.clone();
+                for (key, value) in overrides {
+                    merged
+                        .entry(key.clone())
+                        .and_modify(|existing| *existing = existing.merge(value))
+                        .or_insert_with(|| value.clone());
+                }
+                Self::Table(merged)
+            }
+            (_, other) => other.clone(),
+        }
+    }
+}
+
+impl EnvSource {
+    fn resolve(&self) -> Result<String> {
+        match std::env::var(&self.env) {
+            Ok(value) => Ok(value),
+            Err(_) => self
+                .default
+                .clone()
+                .ok_or_else(|| anyhow!("environment variable {} is not set", self.env)),
+        }
+    }
+}
+
+impl CommandSource {
+    fn resolve(&self, base_dir: &Path) -> Result<String> {
+        let output = shell_command(&self.command)
+            .current_dir(base_dir)
+            .stdin(process::Stdio::null())
+            .output()
+            .context(format!("failed to run `{}`", self.command));
+
+        let output = match (output, &self.default) {
+            (Ok(output), _) if output.status.success() => output,
+            (_, Some(default)) => return Ok(default.clone()),
+            (Ok(output), None) => bail!(
+                "`{}` exited with {}: {}",
+                self.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            (Err(e), None) => return Err(e),
+        };
+
+        let stdout = String::from_utf8(output.stdout)
+            .context(format!("`{}` printed invalid UTF-8", self.command))?;
+
+        Ok(stdout.trim().to_string())
+    }
+}
+
+impl FileSource {
+    fn resolve(&self, base_dir: &Path) -> Result<String> {
+        let path = base_dir.join(&self.file);
+
+        match (std::fs::read_to_string(&path), &self.default) {
+            (Ok(content), _) => Ok(content.trim().to_string()),
+            (Err(_), Some(default)) => Ok(default.clone()),
+            (Err(e), None) => Err(e).context(format!("failed to read {}", path.display())),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell_command(command: &str) -> process::Command {
+    let mut cmd = process::Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> process::Command {
+    let mut cmd = process::Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+pub fn merge_variables(
+    base: Option<&HashMap<String, Variable>>,
+    override_vars: Option<&HashMap<String, Variable>>,
+) -> Option<HashMap<String, Variable>> {
+    match (base, override_vars) {
+        (None, None) => None,
+        (Some(b), None) => Some(b.clone()),
+        (None, Some(o)) => Some(o.clone()),
+        (Some(b), Some(o)) => {
+            let mut merged = b.clone();
+            for (key, value) in o {
+                merged
+                    .entry(key.clone())
+                    .and_modify(|existing| *existing = existing.merge(value))
+                    .or_insert_with(|| value.clone());
+            }
+            Some(merged)
+        }
+    }
+}
+
+/// Resolves a set of variables into a template context object.
+pub fn resolve_variables(
+    variables: Option<&HashMap<String, Variable>>,
+    base_dir: &Path,
+) -> Result<Value> {
+    VariableResolver::new(base_dir).resolve(variables)
+}
+
+/// Resolves variables for a whole deploy, running every distinct definition once however many
+/// providers share it.
+pub struct VariableResolver {
+    base_dir: PathBuf,
+    resolved: RefCell<HashMap<String, Value>>,
+}
+
+impl VariableResolver {
+    pub fn new(base_dir: &Path) -> Self {
+        Self {
+            base_dir: base_dir.to_path_buf(),
+            resolved: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Resolves a set of variables into a template context object.
+    pub fn resolve(&self, variables: Option<&HashMap<String, Variable>>) -> Result<Value> {
+        let Some(variables) = variables else {
+            return Ok(Value::Object(Map::new()));
+        };
+
+        let resolved = variables
+            .iter()
+            .map(|(key, value)| {
+                let resolved = self
+                    .resolve_one(value)
+                    .context(format!("failed to resolve variable {}", key))?;
+                Ok((key.clone(), resolved))
+            })
+            .collect::<Result<Map<_, _>>>()?;
+
+        Ok(Value::Object(resolved))
+    }
+
+    fn resolve_one(&self, variable: &Variable) -> Result<Value> {
+        let definition = serde_json::to_string(variable).context("failed to serialize variable")?;
+
+        if let Some(value) = self.resolved.borrow().get(&definition) {
+            return Ok(value.clone());
+        }
+
+        let value = variable.resolve(&self.base_dir)?;
+        self.resolved.borrow_mut().insert(definition, value.clone());
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(toml: &str) -> HashMap<String, Variable> {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn reads_sources_only_from_tables_with_from() {
+        let parsed = variables(
+            r#"
+stage = { env = "prod" }
+notes = { file = "x", owner = "docs" }
+api = { from = "env", env = "API_URL", default = "http://localhost" }
+branch = { from = "command", command = "git branch --show-current" }
+"#,
+        );
+
+        assert!(matches!(parsed["stage"], Variable::Table(_)));
+        assert!(matches!(parsed["notes"], Variable::Table(_)));
+        assert_eq!(
+            parsed["api"],
+            Variable::Source(VariableSource::Env(EnvSource {
+                env: "API_URL".into(),
+                default: Some("http://localhost".into()),
+            }))
+        );
+        assert!(matches!(
+            parsed["branch"],
+            Variable::Source(VariableSource::Command(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_sources() {
+        for toml in [
+            r#"api = { from = "vault", env = "API_URL" }"#,
+            r#"api = { from = "env" }"#,
+            r#"api = { from = "env", env = "API_URL", fallback = "x" }"#,
+        ] {
+            let error = toml::from_str::<HashMap<String, Variable>>(toml).unwrap_err();
+            assert!(
+                error.to_string().contains("invalid variable source"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn sources_round_trip() {
+        let parsed = variables(
+            r#"notes = { from = "file", file = "NOTES.md" }
+plain = { env = "prod", replicas = 3 }"#,
+        );
+        let serialized: HashMap<String, Variable> =
+            serde_json::from_value(serde_json::to_value(&parsed).unwrap()).unwrap();
+
+        assert_eq!(serialized, parsed);
+    }
+
+    #[test]
+    fn resolves_values_and_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("VERSION"), "1.2.3\n").unwrap();
+
+        let parsed = variables(
+            r#"
+name = "demo"
+retries = 3
+ratio = 0.5
+tags = ["a", { from = "file", file = "VERSION" }]
+missing = { from = "env", env = "DOTAGENTS_TEST_UNSET_VARIABLE", default = "fallback" }
+release = { version = { from = "file", file = "VERSION" }, stage = { env = "prod" } }
+"#,
+        );
+
+        let resolved = resolve_variables(Some(&parsed), dir.path()).unwrap();
+
+        assert_eq!(
+            resolved,
+            serde_json::json!({
+                "name": "demo",
+                "retries": 3,
+                "ratio": 0.5,
+                "tags": ["a", "1.2.3"],
+                "missing": "fallback",
+                "release": { "version": "1.2.3", "stage": { "env": "prod" } },
+            })
+        );
+    }
+
+    #[test]
+    fn reports_the_variable_that_failed() {
+        let parsed = variables(r#"notes = { from = "file", file = "missing.md" }"#);
+        let dir = tempfile::tempdir().unwrap();
+
+        let error = resolve_variables(Some(&parsed), dir.path()).unwrap_err();
+
+        assert_eq!(error.to_string(), "failed to resolve variable notes");
+    }
+
+    #[test]
+    fn local_variables_take_precedence() {
+        let global = variables(
+            r#"
+name = "global"
+kept = "global"
+release = { stage = "dev", region = "eu" }
+"#,
+        );
+        let local = variables(
+            r#"
+name = "local"
+release = { stage = "prod" }
+"#,
+        );
+
+        let merged = merge_variables(Some(&global), Some(&local)).unwrap();
+        let resolved = resolve_variables(Some(&merged), Path::new(".")).unwrap();
+
+        assert_eq!(
+            resolved,
+            serde_json::json!({
+                "name": "local",
+                "kept": "global",
+                "release": { "stage": "prod", "region": "eu" },
+            })
+        );
+        assert_eq!(merge_variables(None, Some(&local)), Some(local.clone()));
+        assert_eq!(merge_variables(None, None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolver_runs_each_definition_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let counter = r#"{ from = "command", command = "echo run >> runs && wc -l < runs" }"#;
+        let first = variables(&format!("count = {}", counter));
+        let second = variables(&format!("calls = {}\nother = \"x\"", counter));
+
+        let resolver = VariableResolver::new(dir.path());
+        let first = resolver.resolve(Some(&first)).unwrap();
+        let second = resolver.resolve(Some(&second)).unwrap();
+
+        assert_eq!(first["count"], "1");
+        assert_eq!(second["calls"], "1");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("runs")).unwrap(),
+            "run\n"
+        );
+    }
+}
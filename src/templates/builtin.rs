@@ -0,0 +1,79 @@
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderErrorReason,
+    Renderable,
+};
+use serde_json::Value;
+
+/// Block helper rendering its body when both parameters are equal, e.g. `{{#ifEq a "b"}}`.
+struct IfEqHelper;
+
+impl HelperDef for IfEqHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let left = h.param(0).map(|p| p.value()).unwrap_or(&Value::Null);
+        let right = h.param(1).map(|p| p.value()).unwrap_or(&Value::Null);
+
+        let tmpl = if left == right {
+            h.template()
+        } else {
+            h.inverse()
+        };
+
+        match tmpl {
+            Some(t) => t.render(r, ctx, rc, out),
+            None => Ok(()),
+        }
+    }
+}
+
+fn json_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = h.param(0).map(|p| p.value()).unwrap_or(&Value::Null);
+    let json = serde_json::to_string(value).map_err(|e| RenderErrorReason::Other(e.to_string()))?;
+
+    out.write(&json)?;
+    Ok(())
+}
+
+/// Joins the items of an array with a separator, `", "` unless given, e.g. `{{join globs ","}}`.
+fn join_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let items = h.param(0).map(|p| p.value()).unwrap_or(&Value::Null);
+    let separator = h.param(1).and_then(|p| p.value().as_str()).unwrap_or(", ");
+
+    let joined = items
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|item| match item {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(separator);
+
+    out.write(&joined)?;
+    Ok(())
+}
+
+pub(crate) fn register_builtin_helpers(handlebar: &mut Handlebars) {
+    handlebar.register_helper("ifEq", Box::new(IfEqHelper));
+    handlebar.register_helper("json", Box::new(json_helper));
+    handlebar.register_helper("join", Box::new(join_helper));
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use crate::utils::path::{get_config_dir, get_home_dir};
+
+/// Parsed `git config` file, keyed by `section.subsection.key` in lowercase sections.
+struct GitConfig(HashMap<String, String>);
+
+impl GitConfig {
+    fn read(path: &Path) -> Self {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let mut values = HashMap::new();
+        let mut section = String::new();
+
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = match header.split_once(' ') {
+                    Some((name, sub)) => {
+                        format!("{}.{}", name.to_lowercase(), sub.trim().trim_matches('"'))
+                    }
+                    None => header.to_lowercase(),
+                };
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_matches('"').to_string();
+                values.insert(format!("{}.{}", section, key.trim().to_lowercase()), value);
+            }
+        }
+
+        Self(values)
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+}
+
+/// Locates the git directory of `workspace`, following `.git` files used by worktrees.
+fn find_git_dir(workspace: &Path) -> Option<PathBuf> {
+    let dot_git = workspace.join(".git");
+
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+
+    let content = fs::read_to_string(&dot_git).ok()?;
+    let path = content.trim().strip_prefix("gitdir:")?.trim();
+
+    Some(workspace.join(path))
+}
+
+/// Git keeps shared refs and config in the common dir when `git_dir` belongs to a worktree.
+fn find_common_dir(git_dir: &Path) -> PathBuf {
+    fs::read_to_string(git_dir.join("commondir"))
+        .map(|path| git_dir.join(path.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf())
+}
+
+/// How many symbolic refs are followed before giving up, as git does.
+const MAX_REF_DEPTH: usize = 5;
+
+fn read_ref(common_dir: &Path, git_dir: &Path, name: &str) -> Option<String> {
+    resolve_ref(common_dir, git_dir, name, MAX_REF_DEPTH)
+}
+
+fn resolve_ref(common_dir: &Path, git_dir: &Path, name: &str, depth: usize) -> Option<String> {
+    for dir in [git_dir, common_dir] {
+        if let Ok(content) = fs::read_to_string(dir.join(name)) {
+            let content = content.trim();
+
+            return match content.strip_prefix("ref:") {
+                // A cycle of symbolic refs points nowhere.
+                Some(_) if depth == 0 => None,
+                Some(target) => resolve_ref(common_dir, git_dir, target.trim(), depth - 1),
+                None => Some(content.to_string()),
+            };
+        }
+    }
+
+    let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+
+    packed
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .find(|(_, ref_name)| *ref_name == name)
+        .map(|(sha, _)| sha.to_string())
+}
+
+fn default_branch(common_dir: &Path, git_dir: &Path) -> Option<String> {
+    let remote_head = fs::read_to_string(common_dir.join("refs/remotes/origin/HEAD")).ok();
+
+    if let Some(branch) = remote_head
+        .as_deref()
+        .and_then(|head| head.trim().strip_prefix("ref: refs/remotes/origin/"))
+    {
+        return Some(branch.to_string());
+    }
+
+    ["main", "master"]
+        .into_iter()
+        .find(|branch| read_ref(common_dir, git_dir, &format!("refs/heads/{}", branch)).is_some())
+        .map(str::to_string)
+}
+
+fn repo_name(remote_url: Option<&str>, workspace: &Path) -> Option<String> {
+    let from_remote = remote_url
+        .map(|url| url.trim_end_matches('/').trim_end_matches(".git"))
+        .and_then(|url| url.rsplit(['/', ':']).next())
+        .filter(|name| !name.is_empty());
+
+    from_remote
+        .or_else(|| workspace.file_name().and_then(|name| name.to_str()))
+        .map(str::to_string)
+}
+
+/// Global git config files, lowest priority first.
+fn user_config_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    if let Ok(config_dir) = get_config_dir() {
+        files.push(config_dir.join("git/config"));
+    }
+
+    if let Ok(home_dir) = get_home_dir() {
+        files.push(home_dir.join(".gitconfig"));
+    }
+
+    files
+}
+
+/// Builds the `git` template namespace from the repository metadata on disk. Nothing here
+/// runs `git` or touches the network, so it is cheap enough to compute on every render.
+pub(crate) fn git_context(workspace: &Path) -> Value {
+    let Some(git_dir) = find_git_dir(workspace) else {
+        return json!({ "is_repo": false });
+    };
+
+    let common_dir = find_common_dir(&git_dir);
+    let mut configs: Vec<GitConfig> = user_config_files()
+        .iter()
+        .map(|path| GitConfig::read(path))
+        .collect();
+    configs.push(GitConfig::read(&common_dir.join("config")));
+
+    let lookup = |key: &str| {
+        configs
+            .iter()
+            .rev()
+            .find_map(|config| config.get(key))
+            .map(str::to_string)
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+    let branch = head
+        .trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(str::to_string);
+    let commit = read_ref(&common_dir, &git_dir, "HEAD");
+    let remote_url = lookup("remote.origin.url");
+
+    json!({
+        "is_repo": true,
+        "branch": branch,
+        "commit": commit,
+        "short_commit": commit.as_deref().map(|sha| &sha[..sha.len().min(7)]),
+        "default_branch": default_branch(&common_dir, &git_dir),
+        "remote_url": remote_url,
+        "repo_name": repo_name(remote_url.as_deref(), workspace),
+        "user": {
+            "name": lookup("user.name"),
+            "email": lookup("user.email"),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &str = "1111111111111111111111111111111111111111";
+    const FEATURE: &str = "2222222222222222222222222222222222222222";
+
+    fn write(dir: &Path, path: &str, content: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// A repository on `feature`, with `main` only in packed-refs.
+    fn repository(dir: &Path) {
+        write(dir, ".git/HEAD", "ref: refs/heads/feature\n");
+        write(dir, ".git/refs/heads/feature", &format!("{}\n", FEATURE));
+        write(
+            dir,
+            ".git/packed-refs",
+            &format!(
+                "# pack-refs with: peeled fully-peeled sorted\n{} refs/heads/main\n^{}\n",
+                MAIN, FEATURE
+            ),
+        );
+        write(
+            dir,
+            ".git/refs/remotes/origin/HEAD",
+            "ref: refs/remotes/origin/main\n",
+        );
+        write(
+            dir,
+            ".git/config",
+            r#"[core]
+	bare = false
+[remote "origin"]
+	url = git@github.com:acme/widgets.git
+	fetch = +refs/heads/*:refs/remotes/origin/*
+[user]
+	name = "Ada Lovelace"
+	email = ada@example.com
+"#,
+        );
+    }
+
+    #[test]
+    fn reads_head_refs_and_config() {
+        let dir = tempfile::tempdir().unwrap();
+        repository(dir.path());
+
+        let context = git_context(dir.path());
+
+        assert_eq!(context["is_repo"], true);
+        assert_eq!(context["branch"], "feature");
+        assert_eq!(context["commit"], FEATURE);
+        assert_eq!(context["short_commit"], "2222222");
+        assert_eq!(context["default_branch"], "main");
+        assert_eq!(context["remote_url"], "git@github.com:acme/widgets.git");
+        assert_eq!(context["repo_name"], "widgets");
+        assert_eq!(context["user"]["name"], "Ada Lovelace");
+        assert_eq!(context["user"]["email"], "ada@example.com");
+    }
+
+    #[test]
+    fn reads_packed_refs_and_detached_heads() {
+        let dir = tempfile::tempdir().unwrap();
+        repository(dir.path());
+        let git_dir = dir.path().join(".git");
+
+        assert_eq!(
+            read_ref(&git_dir, &git_dir, "refs/heads/main").as_deref(),
+            Some(MAIN)
+        );
+        assert_eq!(read_ref(&git_dir, &git_dir, "refs/heads/gone"), None);
+
+        write(dir.path(), ".git/HEAD", &format!("{}\n", MAIN));
+        let context = git_context(dir.path());
+
+        assert_eq!(context["branch"], Value::Null);
+        assert_eq!(context["commit"], MAIN);
+    }
+
+    #[test]
+    fn follows_worktree_git_files() {
+        let dir = tempfile::tempdir().unwrap();
+        repository(dir.path());
+        let worktree = dir.path().join("worktree");
+        write(&worktree, ".git", "gitdir: ../.git/worktrees/wt\n");
+        write(
+            dir.path(),
+            ".git/worktrees/wt/HEAD",
+            "ref: refs/heads/main\n",
+        );
+        write(dir.path(), ".git/worktrees/wt/commondir", "../..\n");
+
+        let context = git_context(&worktree);
+
+        assert_eq!(context["branch"], "main");
+        assert_eq!(context["commit"], MAIN);
+        assert_eq!(context["repo_name"], "widgets");
+    }
+
+    #[test]
+    fn gives_up_on_symbolic_ref_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        repository(dir.path());
+        write(dir.path(), ".git/HEAD", "ref: refs/heads/a\n");
+        write(dir.path(), ".git/refs/heads/a", "ref: refs/heads/b\n");
+        write(dir.path(), ".git/refs/heads/b", "ref: refs/heads/a\n");
+
+        let context = git_context(dir.path());
+
+        assert_eq!(context["branch"], "a");
+        assert_eq!(context["commit"], Value::Null);
+    }
+
+    #[test]
+    fn derives_repo_names_from_remotes_or_the_directory() {
+        let workspace = Path::new("/work/fallback");
+
+        for (url, name) in [
+            ("https://github.com/acme/widgets.git", "widgets"),
+            ("https://github.com/acme/widgets/", "widgets"),
+            ("git@github.com:widgets.git", "widgets"),
+        ] {
+            assert_eq!(repo_name(Some(url), workspace).as_deref(), Some(name));
+        }
+
+        assert_eq!(repo_name(None, workspace).as_deref(), Some("fallback"));
+    }
+
+    #[test]
+    fn reports_directories_outside_repositories() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(git_context(dir.path()), json!({ "is_repo": false }));
+    }
+}
@@ -1,13 +1,19 @@
 use anyhow::{Context, Result};
-use handlebars::Handlebars;
+use handlebars::{Handlebars, no_escape};
 use serde_json::{Value, json};
-use std::sync::OnceLock;
+use std::{fs, path::Path, sync::OnceLock};
 
-use crate::utils::path::{get_application_dir, get_config_dir, get_workspace_dir};
+use super::builtin::register_builtin_helpers;
+use super::git::git_context;
+use super::project::project_context;
+use super::remote::{fetch_template, is_remote};
+use crate::utils::path::{
+    get_application_dir, get_config_dir, get_home_dir, get_workspace_dir, in_workspace,
+};
 use crate::{
     constants::{
         file::{GLOBAL_CONFIG_FILE, LOCAL_CONFIG_FILE},
-        variables::{APPLICATION_DIR, CONFIG_DIR, WORKSPACE_DIR},
+        variables::{APPLICATION_DIR, CONFIG_DIR, GIT, HOME_DIR, PROJECT, WORKSPACE_DIR},
     },
     utils::merge_json,
 };
@@ -35,14 +41,19 @@ pub struct Templater {
 
 impl Templater {
     fn load_default_variables() -> Result<Value> {
+        let workspace = get_workspace_dir()?;
+        let home_dir = get_home_dir()?.to_string_lossy().to_string();
         let config_dir = get_config_dir()?.to_string_lossy().to_string();
-        let workspace_dir = get_workspace_dir()?.to_string_lossy().to_string();
+        let workspace_dir = workspace.to_string_lossy().to_string();
         let application_dir = get_application_dir()?.to_string_lossy().to_string();
 
         Ok(json!({
+            HOME_DIR: &home_dir,
             CONFIG_DIR: &config_dir,
             WORKSPACE_DIR: &workspace_dir,
             APPLICATION_DIR: &application_dir,
+            GIT: git_context(&workspace),
+            PROJECT: project_context(&workspace),
         }))
     }
 
@@ -65,14 +76,25 @@ impl Templater {
 
     pub fn new() -> Result<Self> {
         let globals = Self::load_default_variables().expect("failed to load global variables");
-        let mut templater = Self {
-            handlebar: Handlebars::new(),
-            globals,
-        };
+        let mut handlebar = Handlebars::new();
+        handlebar.register_escape_fn(no_escape);
+        register_builtin_helpers(&mut handlebar);
+
+        let mut templater = Self { handlebar, globals };
         templater.register_default_templates()?;
         Ok(templater)
     }
 
+    /// A templater for the workspace at `dir` rather than the one containing the current
+    /// directory.
+    pub fn for_workspace(dir: &Path) -> Result<Self> {
+        in_workspace(dir, Self::new)
+    }
+
+    pub fn globals(&self) -> &Value {
+        &self.globals
+    }
+
     pub fn register_template(&mut self, name: &str, source: TemplateSource) -> Result<()> {
         match source {
             TemplateSource::File(path) => self.handlebar.register_template_file(name, path),
@@ -94,3 +116,13 @@ impl Templater {
         .context("failed to render template")
     }
 }
+
+/// Reads a provider template, either bundled/remote or relative to the application directory.
+pub fn load_template(template: &str) -> Result<String> {
+    if is_remote(template) {
+        return fetch_template(template);
+    }
+
+    let path = get_application_dir()?.join(template);
+    fs::read_to_string(&path).context(format!("failed to read template {}", path.display()))
+}
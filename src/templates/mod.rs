@@ -1,2 +1,5 @@
+mod builtin;
+mod git;
 pub(crate) mod helpers;
+mod project;
 mod remote;
@@ -0,0 +1,325 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Value, json};
+
+/// A toolchain recognised from a manifest file in the workspace root.
+struct Toolchain {
+    language: &'static str,
+    package_manager: &'static str,
+    name: Option<String>,
+    test_command: Option<String>,
+    build_command: Option<String>,
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    toml::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn toml_str(value: &toml::Value, path: &[&str]) -> Option<String> {
+    path.iter()
+        .try_fold(value, |value, key| value.get(key))
+        .and_then(|value| value.as_str())
+        .map(str::to_string)
+}
+
+fn detect_rust(workspace: &Path) -> Option<Toolchain> {
+    let manifest = read_toml(&workspace.join("Cargo.toml"))?;
+
+    Some(Toolchain {
+        language: "rust",
+        package_manager: "cargo",
+        name: toml_str(&manifest, &["package", "name"]),
+        test_command: Some("cargo test".into()),
+        build_command: Some("cargo build".into()),
+    })
+}
+
+fn detect_node(workspace: &Path) -> Option<Toolchain> {
+    let content = fs::read_to_string(workspace.join("package.json")).ok()?;
+    let manifest: Value = serde_json::from_str(&content).ok()?;
+
+    let package_manager = [
+        ("pnpm-lock.yaml", "pnpm"),
+        ("yarn.lock", "yarn"),
+        ("bun.lockb", "bun"),
+        ("bun.lock", "bun"),
+    ]
+    .into_iter()
+    .find(|(lockfile, _)| workspace.join(lockfile).exists())
+    .map_or("npm", |(_, manager)| manager);
+
+    let has_dependency = |name: &str| {
+        ["dependencies", "devDependencies"]
+            .iter()
+            .any(|key| manifest[key].get(name).is_some())
+    };
+    let language = if workspace.join("tsconfig.json").exists() || has_dependency("typescript") {
+        "typescript"
+    } else {
+        "javascript"
+    };
+
+    let script = |name: &str| {
+        manifest["scripts"]
+            .get(name)
+            .map(|_| format!("{} run {}", package_manager, name))
+    };
+
+    Some(Toolchain {
+        language,
+        package_manager,
+        name: manifest["name"].as_str().map(str::to_string),
+        test_command: script("test"),
+        build_command: script("build"),
+    })
+}
+
+fn detect_python(workspace: &Path) -> Option<Toolchain> {
+    let pyproject = read_toml(&workspace.join("pyproject.toml"));
+
+    if pyproject.is_none() && !workspace.join("requirements.txt").exists() {
+        return None;
+    }
+
+    let package_manager = [
+        ("uv.lock", "uv"),
+        ("poetry.lock", "poetry"),
+        ("pdm.lock", "pdm"),
+    ]
+    .into_iter()
+    .find(|(lockfile, _)| workspace.join(lockfile).exists())
+    .map_or("pip", |(_, manager)| manager);
+
+    let test_command = match package_manager {
+        "pip" => "pytest".to_string(),
+        manager => format!("{} run pytest", manager),
+    };
+
+    let name = pyproject.as_ref().and_then(|manifest| {
+        toml_str(manifest, &["project", "name"])
+            .or_else(|| toml_str(manifest, &["tool", "poetry", "name"]))
+    });
+
+    Some(Toolchain {
+        language: "python",
+        package_manager,
+        name,
+        test_command: Some(test_command),
+        build_command: None,
+    })
+}
+
+fn detect_go(workspace: &Path) -> Option<Toolchain> {
+    let manifest = fs::read_to_string(workspace.join("go.mod")).ok()?;
+    let name = manifest
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("module "))
+        .map(|module| module.trim().to_string());
+
+    Some(Toolchain {
+        language: "go",
+        package_manager: "go",
+        name,
+        test_command: Some("go test ./...".into()),
+        build_command: Some("go build ./...".into()),
+    })
+}
+
+fn detect_jvm(workspace: &Path) -> Option<Toolchain> {
+    if workspace.join("pom.xml").exists() {
+        return Some(Toolchain {
+            language: "java",
+            package_manager: "maven",
+            name: None,
+            test_command: Some("mvn test".into()),
+            build_command: Some("mvn package".into()),
+        });
+    }
+
+    let kotlin = workspace.join("build.gradle.kts").exists();
+
+    if !kotlin && !workspace.join("build.gradle").exists() {
+        return None;
+    }
+
+    let gradle = if workspace.join("gradlew").exists() {
+        "./gradlew"
+    } else {
+        "gradle"
+    };
+
+    Some(Toolchain {
+        language: if kotlin { "kotlin" } else { "java" },
+        package_manager: "gradle",
+        name: None,
+        test_command: Some(format!("{} test", gradle)),
+        build_command: Some(format!("{} build", gradle)),
+    })
+}
+
+fn detect_ruby(workspace: &Path) -> Option<Toolchain> {
+    if !workspace.join("Gemfile").exists() {
+        return None;
+    }
+
+    Some(Toolchain {
+        language: "ruby",
+        package_manager: "bundler",
+        name: None,
+        test_command: Some("bundle exec rake test".into()),
+        build_command: None,
+    })
+}
+
+/// Builds the `project` template namespace from the manifests found in `workspace`. The first
+/// detected toolchain is considered the primary one.
+pub(crate) fn project_context(workspace: &Path) -> Value {
+    let toolchains: Vec<Toolchain> = [
+        detect_rust,
+        detect_node,
+        detect_python,
+        detect_go,
+        detect_jvm,
+        detect_ruby,
+    ]
+    .into_iter()
+    .filter_map(|detect| detect(workspace))
+    .collect();
+
+    let primary = toolchains.first();
+    let name = primary
+        .and_then(|toolchain| toolchain.name.clone())
+        .or_else(|| {
+            workspace
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(str::to_string)
+        });
+
+    let mut languages: Vec<&str> = Vec::new();
+    let mut package_managers: Vec<&str> = Vec::new();
+
+    for toolchain in &toolchains {
+        if !languages.contains(&toolchain.language) {
+            languages.push(toolchain.language);
+        }
+
+        if !package_managers.contains(&toolchain.package_manager) {
+            package_managers.push(toolchain.package_manager);
+        }
+    }
+
+    json!({
+        "name": name,
+        "language": primary.map(|toolchain| toolchain.language),
+        "languages": languages,
+        "package_manager": primary.map(|toolchain| toolchain.package_manager),
+        "package_managers": package_managers,
+        "test_command": primary.and_then(|toolchain| toolchain.test_command.clone()),
+        "build_command": primary.and_then(|toolchain| toolchain.build_command.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (path, content) in files {
+            fs::write(dir.path().join(path), content).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn reads_cargo_manifests() {
+        let dir = workspace(&[("Cargo.toml", "[package]\nname = \"widgets\"\n")]);
+
+        assert_eq!(
+            project_context(dir.path()),
+            json!({
+                "name": "widgets",
+                "language": "rust",
+                "languages": ["rust"],
+                "package_manager": "cargo",
+                "package_managers": ["cargo"],
+                "test_command": "cargo test",
+                "build_command": "cargo build",
+            })
+        );
+    }
+
+    #[test]
+    fn reads_package_json_with_its_lockfile() {
+        let dir = workspace(&[
+            (
+                "package.json",
+                r#"{ "name": "web", "scripts": { "test": "vitest" }, "devDependencies": { "typescript": "5" } }"#,
+            ),
+            ("pnpm-lock.yaml", ""),
+        ]);
+
+        let context = project_context(dir.path());
+
+        assert_eq!(context["name"], "web");
+        assert_eq!(context["language"], "typescript");
+        assert_eq!(context["package_manager"], "pnpm");
+        assert_eq!(context["test_command"], "pnpm run test");
+        assert_eq!(context["build_command"], Value::Null);
+    }
+
+    #[test]
+    fn reads_poetry_and_go_manifests() {
+        let dir = workspace(&[
+            ("pyproject.toml", "[tool.poetry]\nname = \"tools\"\n"),
+            ("poetry.lock", ""),
+        ]);
+        let context = project_context(dir.path());
+
+        assert_eq!(context["name"], "tools");
+        assert_eq!(context["test_command"], "poetry run pytest");
+
+        let dir = workspace(&[("go.mod", "module example.com/api\n\ngo 1.22\n")]);
+        let context = project_context(dir.path());
+
+        assert_eq!(context["name"], "example.com/api");
+        assert_eq!(context["package_manager"], "go");
+    }
+
+    #[test]
+    fn lists_every_toolchain_with_the_first_as_primary() {
+        let dir = workspace(&[
+            ("Cargo.toml", "[package]\nname = \"core\"\n"),
+            ("package.json", r#"{ "name": "bindings" }"#),
+            ("build.gradle.kts", ""),
+        ]);
+
+        let context = project_context(dir.path());
+
+        assert_eq!(context["name"], "core");
+        assert_eq!(
+            context["languages"],
+            json!(["rust", "javascript", "kotlin"])
+        );
+        assert_eq!(
+            context["package_managers"],
+            json!(["cargo", "npm", "gradle"])
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_directory_name() {
+        let dir = workspace(&[("package.json", "not json")]);
+        let context = project_context(dir.path());
+
+        assert_eq!(
+            context["name"].as_str(),
+            dir.path().file_name().and_then(|name| name.to_str())
+        );
+        assert_eq!(context["language"], Value::Null);
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Result, bail};
+
+use crate::constants::schema::TEMPLATES_URL;
+
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "copilot/command.hbs",
+        include_str!("../../public/templates/copilot/command.hbs"),
+    ),
+    (
+        "copilot/instructions.hbs",
+        include_str!("../../public/templates/copilot/instructions.hbs"),
+    ),
+    (
+        "copilot/mcp.hbs",
+        include_str!("../../public/templates/copilot/mcp.hbs"),
+    ),
+    (
+        "cursor/instructions.hbs",
+        include_str!("../../public/templates/cursor/instructions.hbs"),
+    ),
+    (
+        "windsurf/command.hbs",
+        include_str!("../../public/templates/windsurf/command.hbs"),
+    ),
+    (
+        "windsurf/instructions.hbs",
+        include_str!("../../public/templates/windsurf/instructions.hbs"),
+    ),
+    (
+        "windsurf/mcp.hbs",
+        include_str!("../../public/templates/windsurf/mcp.hbs"),
+    ),
+];
+
+pub(crate) fn is_remote(template: &str) -> bool {
+    template.starts_with("https://") || template.starts_with("http://")
+}
+
+/// Resolves a remote template. Templates published alongside dotagents are bundled into the
+/// binary, so deploys never need network access for the built-in providers.
+pub(crate) fn fetch_template(url: &str) -> Result<String> {
+    let builtin = url.strip_prefix(TEMPLATES_URL).and_then(|name| {
+        BUILTIN_TEMPLATES
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, content)| content.to_string())
+    });
+
+    match builtin {
+        Some(content) => Ok(content),
+        None => bail!("remote template {} is not bundled with dotagents", url),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+
+    use handlebars::{Handlebars, no_escape};
+
+    use super::*;
+    use crate::schema::config::ConfigAgentSettings;
+    use crate::schema::instruction::{Instruction, InstructionLayout};
+    use crate::templates::builtin::register_builtin_helpers;
+
+    /// Renders the bundled instructions template of `agent` for every fragment, by name.
+    fn render_fragments(agent: &str, files: &[(&str, &str)]) -> HashMap<String, String> {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, content) in files {
+            fs::write(dir.path().join(path), content).unwrap();
+        }
+
+        let settings = ConfigAgentSettings {
+            layout: Some(InstructionLayout::Fragments),
+            ..Default::default()
+        };
+        let items = Instruction::from_dir(dir.path())
+            .unwrap()
+            .items(Some((agent, &settings)))
+            .unwrap();
+
+        let mut handlebar = Handlebars::new();
+        handlebar.register_escape_fn(no_escape);
+        register_builtin_helpers(&mut handlebar);
+        let template =
+            fetch_template(&format!("{}{}/instructions.hbs", TEMPLATES_URL, agent)).unwrap();
+
+        items
+            .into_iter()
+            .map(|item| {
+                let output = handlebar.render_template(&template, &item.data).unwrap();
+                (item.id.unwrap(), output)
+            })
+            .collect()
+    }
+
+    const FRAGMENTS: &[(&str, &str)] = &[
+        ("always.md", "Be brief.\n"),
+        (
+            "styles.md",
+            "---\nglobs: [\"*.css\", \"src/**/*.scss\"]\n---\nUse variables.\n",
+        ),
+        (
+            "review.md",
+            "---\ndescription: Reviewing a pull request\nalways_apply: false\n---\nCheck tests.\n",
+        ),
+        ("release.md", "---\nwhen: manual\n---\nBump the version.\n"),
+    ];
+
+    #[test]
+    fn renders_windsurf_triggers() {
+        let outputs = render_fragments("windsurf", FRAGMENTS);
+
+        assert_eq!(
+            outputs["always"],
+            "---\ntrigger: always_on\n---\n\nBe brief.\n"
+        );
+        assert_eq!(
+            outputs["styles"],
+            "---\ntrigger: glob\nglobs: *.css, src/**/*.scss\n---\n\nUse variables.\n"
+        );
+        assert_eq!(
+            outputs["review"],
+            "---\ntrigger: model_decision\ndescription: \"Reviewing a pull request\"\n---\n\nCheck tests.\n"
+        );
+        assert_eq!(
+            outputs["release"],
+            "---\ntrigger: manual\n---\n\nBump the version.\n"
+        );
+    }
+
+    #[test]
+    fn renders_cursor_triggers() {
+        let outputs = render_fragments("cursor", FRAGMENTS);
+
+        assert_eq!(
+            outputs["always"],
+            "---\ndescription: \nglobs: \nalwaysApply: true\n---\n\nBe brief.\n"
+        );
+        assert_eq!(
+            outputs["styles"],
+            "---\ndescription: \nglobs: *.css,src/**/*.scss\nalwaysApply: false\n---\n\nUse variables.\n"
+        );
+        assert_eq!(
+            outputs["review"],
+            "---\ndescription: \"Reviewing a pull request\"\nglobs: \nalwaysApply: false\n---\n\nCheck tests.\n"
+        );
+        // Manual rules are the ones cursor can neither match by file nor by description.
+        assert_eq!(
+            outputs["release"],
+            "---\ndescription: \nglobs: \nalwaysApply: false\n---\n\nBump the version.\n"
+        );
+    }
+}
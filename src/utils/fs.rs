@@ -1,10 +1,158 @@
-use std::{fs::read_to_string, path::PathBuf};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
-pub fn read_file(file_path: PathBuf) -> Result<String> {
-    match read_to_string(file_path) {
-        Ok(f) => Ok(f),
-        Err(e) => Err(e.into()),
+/// Collects every markdown file below `dir`, descending into subdirectories. Symlinked
+/// directories are followed once, so a link pointing back up the tree can't loop forever.
+pub fn collect_markdown_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    collect_markdown_files_in(dir, files, &mut HashSet::new())
+}
+
+fn collect_markdown_files_in(
+    dir: &Path,
+    files: &mut Vec<PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<()> {
+    let canonical = fs::canonicalize(dir).context(format!("failed to read {}", dir.display()))?;
+
+    if !visited.insert(canonical) {
+        log::debug!("Skipping {} as it was already visited", dir.display());
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir).context(format!("failed to read {}", dir.display()))? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_markdown_files_in(&path, files, visited)?;
+        } else if path.extension().is_some_and(|extension| extension == "md") {
+            files.push(path);
+        }
     }
+
+    Ok(())
+}
+
+/// Writes `content` to a temporary file next to `path` and renames it into place, so readers
+/// never see a half-written file. An existing file keeps its permissions, and a symlink, e.g.
+/// one managed by a dotfile manager, is written through so the link stays in place.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let resolved;
+    let path = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            resolved = fs::canonicalize(path).context(format!(
+                "failed to resolve symlink {}, remove it to write a file in its place",
+                path.display()
+            ))?;
+            resolved.as_path()
+        }
+        _ => path,
+    };
+
+    let name = path
+        .file_name()
+        .context(format!("{} is not a file path", path.display()))?;
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), process::id()));
+
+    let result = (|| {
+        let mut file =
+            fs::File::create(&temp).context(format!("failed to create {}", temp.display()))?;
+        file.write_all(content)
+            .and_then(|_| file.sync_all())
+            .context(format!("failed to write {}", temp.display()))?;
+
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp, metadata.permissions())
+                .context(format!("failed to copy permissions of {}", path.display()))?;
+        }
+
+        fs::rename(&temp, path).context(format!("failed to replace {}", path.display()))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+
+    result
+}
+
+/// Path leading from the directory `from` to `to`, both absolute.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    // Nothing in common, e.g. different drives, so only an absolute path works.
+    if common == 0 {
+        return to.iter().collect();
+    }
+
+    let mut path: PathBuf = from[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .collect();
+    path.extend(&to[common..]);
+    path
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Canonicalizes the longest existing ancestor of `path` and appends the rest, so symlinks
+/// are resolved even for files that don't exist yet.
+pub fn resolve_existing(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(resolved) = fs::canonicalize(ancestor) {
+            let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return if rest.as_os_str().is_empty() {
+                resolved
+            } else {
+                resolved.join(rest)
+            };
+        }
+    }
+
+    path.to_path_buf()
+}
+
+/// Points `path` at `target` through a symlink created next to it and renamed into place,
+/// replacing whatever was there.
+pub fn symlink_atomic(target: &Path, path: &Path) -> Result<()> {
+    let name = path
+        .file_name()
+        .context(format!("{} is not a file path", path.display()))?;
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), process::id()));
+
+    let _ = fs::remove_file(&temp);
+
+    #[cfg(unix)]
+    let created = std::os::unix::fs::symlink(target, &temp);
+    #[cfg(windows)]
+    let created = std::os::windows::fs::symlink_file(target, &temp);
+
+    created.context(format!("failed to create symlink {}", temp.display()))?;
+
+    fs::rename(&temp, path).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        anyhow::Error::new(e).context(format!("failed to replace {}", path.display()))
+    })
 }
@@ -0,0 +1,22 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+/// Runs git inside `dir`, returning its trimmed output. Failures carry git's own message.
+pub(crate) fn run_git(dir: Option<&Path>, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("git");
+    command.args(args);
+
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+
+    let output = command.output().context("failed to run git")?;
+
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,80 @@
+/// Whether `pattern` uses any wildcard and so only makes sense against a list of names.
+pub(crate) fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Matches `name` against a pattern where `*` stands for any run of characters and `?` for
+/// a single one.
+pub(crate) fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards_and_literals() {
+        for (pattern, name, expected) in [
+            ("read_file", "read_file", true),
+            ("read_file", "read_files", false),
+            ("read_*", "read_file", true),
+            ("read_*", "read_", true),
+            ("read_*", "write_file", false),
+            ("*_file", "read_file", true),
+            ("*file*", "list_files_now", true),
+            ("a*b*c", "axxbyyc", true),
+            ("a*b*c", "axxbyy", false),
+            ("get_?", "get_a", true),
+            ("get_?", "get_", false),
+            ("get_?", "get_ab", false),
+            ("*", "anything", true),
+            ("*", "", true),
+            ("", "", true),
+            ("", "tool", false),
+        ] {
+            assert_eq!(
+                matches_glob(pattern, name),
+                expected,
+                "{} against {}",
+                pattern,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn detects_wildcards() {
+        assert!(is_glob("read_*"));
+        assert!(is_glob("get_?"));
+        assert!(!is_glob("read_file"));
+        assert!(!is_glob(""));
+    }
+}
@@ -0,0 +1,11 @@
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Stable FNV-1a digest used to detect changes to deployed files between runs.
+pub(crate) fn hash_content(content: &[u8]) -> String {
+    let hash = content.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    });
+
+    format!("{:016x}", hash)
+}
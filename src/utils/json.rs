@@ -1,4 +1,4 @@
-use serde_json::{Value, json};
+use serde_json::Value;
 
 pub fn merge_json(a: &Value, b: &Value) -> Value {
     match (a, b) {
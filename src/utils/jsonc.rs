@@ -0,0 +1,571 @@
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Serialize;
+
+/// Removes comments and trailing commas so JSONC can be handed to `serde_json`. Comments are
+/// replaced by spaces, keeping byte offsets and line numbers of parse errors meaningful.
+pub(crate) fn strip_comments(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut output = bytes.to_vec();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'"' => pos = string_end(bytes, pos),
+            b'/' if matches!(bytes.get(pos + 1), Some(b'/') | Some(b'*')) => {
+                let end = comment_end(bytes, pos);
+                for byte in &mut output[pos..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                pos = end;
+            }
+            b',' => {
+                if matches!(
+                    bytes.get(skip_trivia(bytes, pos + 1)),
+                    Some(b'}') | Some(b']')
+                ) {
+                    output[pos] = b' ';
+                }
+                pos += 1;
+            }
+            _ => pos += 1,
+        }
+    }
+
+    // Only ASCII bytes outside of strings were replaced, so the output is still valid UTF-8.
+    String::from_utf8(output).unwrap_or_default()
+}
+
+/// Position just past the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut pos = start + 1;
+
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'"' => return pos + 1,
+            _ => pos += 1,
+        }
+    }
+
+    bytes.len()
+}
+
+/// Position just past the comment starting at `start`. Line comments keep their newline.
+fn comment_end(bytes: &[u8], start: usize) -> usize {
+    if bytes.get(start + 1) == Some(&b'/') {
+        return bytes[start..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(bytes.len(), |offset| start + offset);
+    }
+
+    bytes[start + 2..]
+        .windows(2)
+        .position(|window| window == b"*/")
+        .map_or(bytes.len(), |offset| start + 2 + offset + 2)
+}
+
+/// Skips whitespace and comments, returning the position of the next token.
+fn skip_trivia(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b' ' | b'\t' | b'\r' | b'\n' => pos += 1,
+            b'/' if matches!(bytes.get(pos + 1), Some(b'/') | Some(b'*')) => {
+                pos = comment_end(bytes, pos)
+            }
+            _ => break,
+        }
+    }
+
+    pos
+}
+
+/// Location of a value inside the document text.
+struct Node {
+    start: usize,
+    end: usize,
+    members: Vec<Member>,
+    is_object: bool,
+}
+
+struct Member {
+    key: String,
+    key_start: usize,
+    value: Node,
+    /// Position of the comma following the value, if any.
+    comma: Option<usize>,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_trivia(&mut self) {
+        self.pos = skip_trivia(self.text.as_bytes(), self.pos);
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        let line = self.text[..self.pos].matches('\n').count() + 1;
+        anyhow!("{} at line {}", message, line)
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        self.skip_trivia();
+
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn comma(&mut self) -> Option<usize> {
+        self.skip_trivia();
+
+        if self.peek() == Some(b',') {
+            self.pos += 1;
+            return Some(self.pos - 1);
+        }
+
+        None
+    }
+
+    fn value(&mut self) -> Result<Node> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => {
+                self.pos += 1;
+                self.skip_trivia();
+
+                while self.peek() != Some(b']') {
+                    self.value()?;
+                    if self.comma().is_none() {
+                        break;
+                    }
+                    self.skip_trivia();
+                }
+
+                self.expect(b']')?;
+                Ok(Node::scalar(start, self.pos))
+            }
+            Some(b'"') => {
+                self.pos = string_end(self.text.as_bytes(), start);
+                Ok(Node::scalar(start, self.pos))
+            }
+            Some(_) => {
+                let length = self.text[start..]
+                    .find(|c: char| c.is_whitespace() || ",}]/".contains(c))
+                    .unwrap_or(self.text.len() - start);
+
+                if length == 0 {
+                    return Err(self.error("expected a value"));
+                }
+
+                self.pos += length;
+                Ok(Node::scalar(start, self.pos))
+            }
+            None => Err(self.error("unexpected end of document")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Node> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut members = Vec::new();
+
+        loop {
+            self.skip_trivia();
+
+            if self.peek() != Some(b'"') {
+                break;
+            }
+
+            let key_start = self.pos;
+            self.pos = string_end(self.text.as_bytes(), key_start);
+            let key: String = serde_json::from_str(&self.text[key_start..self.pos])
+                .map_err(|_| self.error("invalid object key"))?;
+
+            self.expect(b':')?;
+            let value = self.value()?;
+            let comma = self.comma();
+
+            members.push(Member {
+                key,
+                key_start,
+                value,
+                comma,
+            });
+
+            if comma.is_none() {
+                break;
+            }
+        }
+
+        self.expect(b'}')?;
+
+        Ok(Node {
+            start,
+            end: self.pos,
+            members,
+            is_object: true,
+        })
+    }
+}
+
+impl Node {
+    fn scalar(start: usize, end: usize) -> Self {
+        Self {
+            start,
+            end,
+            members: Vec::new(),
+            is_object: false,
+        }
+    }
+
+    fn member(&self, key: &str) -> Option<&Member> {
+        self.members.iter().find(|member| member.key == key)
+    }
+}
+
+/// A JSONC document edited in place, so comments, formatting and key order outside of the
+/// edited values survive.
+pub(crate) struct JsoncDocument {
+    text: String,
+}
+
+impl JsoncDocument {
+    pub fn parse(text: String) -> Result<Self> {
+        let document = Self { text };
+        document.root()?;
+        Ok(document)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn root(&self) -> Result<Node> {
+        let mut parser = Parser {
+            text: &self.text,
+            pos: 0,
+        };
+        let root = parser.value()?;
+
+        parser.skip_trivia();
+        if parser.pos != self.text.len() {
+            return Err(parser.error("unexpected content after the document"));
+        }
+
+        Ok(root)
+    }
+
+    fn find<'a>(root: &'a Node, path: &[&str]) -> Result<&'a Node> {
+        path.iter().try_fold(root, |node, key| {
+            node.member(key)
+                .map(|member| &member.value)
+                .ok_or_else(|| anyhow!("missing key '{}'", key))
+        })
+    }
+
+    /// Keys of the object at `path`, in document order.
+    pub fn keys(&self, path: &[&str]) -> Result<Vec<String>> {
+        let root = self.root()?;
+        let node = Self::find(&root, path)?;
+
+        Ok(node
+            .members
+            .iter()
+            .map(|member| member.key.clone())
+            .collect())
+    }
+
+    /// Sets `key` of the object at `path` to `value`, replacing an existing value where it
+    /// stands or appending a new member to the object.
+    pub fn set<T: Serialize>(&mut self, path: &[&str], key: &str, value: &T) -> Result<()> {
+        let root = self.root()?;
+        let object = Self::find(&root, path)?;
+
+        if !object.is_object {
+            bail!("'{}' is not an object", path.join("."));
+        }
+
+        let value = serde_json::to_string_pretty(value).context("failed to serialize value")?;
+
+        if let Some(member) = object.member(key) {
+            let indent = self.line_indent(member.key_start).to_string();
+            let value = reindent(&value, &indent);
+            self.text
+                .replace_range(member.value.start..member.value.end, &value);
+            return Ok(());
+        }
+
+        let indent = match object.members.first() {
+            Some(member) => self.line_indent(member.key_start).to_string(),
+            None => format!("{}{}", self.line_indent(object.start), self.indent_unit()),
+        };
+        let key = serde_json::to_string(key).context("failed to serialize key")?;
+        let member = format!("{}: {}", key, reindent(&value, &indent));
+        let close = object.end - 1;
+
+        let Some(last) = object.members.last() else {
+            let inner = &self.text[object.start + 1..close];
+
+            if inner.trim().is_empty() {
+                let outer = self.line_indent(object.start).to_string();
+                self.text.replace_range(
+                    object.start + 1..close,
+                    &format!("\n{}{}\n{}", indent, member, outer),
+                );
+            } else {
+                let at = self.text[..close].trim_end().len();
+                self.text.insert_str(at, &format!("\n{}{}", indent, member));
+            }
+
+            return Ok(());
+        };
+
+        // Insert before the whitespace preceding the closing brace so comments trailing the
+        // last member stay where they are.
+        let at = self.text[..close].trim_end().len();
+        self.text.insert_str(at, &format!("\n{}{}", indent, member));
+
+        if last.comma.is_none() {
+            self.text.insert(last.value.end, ',');
+        }
+
+        Ok(())
+    }
+
+    /// Removes `key` from the object at `path` along with the comments directly above it.
+    /// Returns whether the key existed.
+    pub fn remove(&mut self, path: &[&str], key: &str) -> Result<bool> {
+        let root = self.root()?;
+        let object = Self::find(&root, path)?;
+
+        let Some(index) = object.members.iter().position(|member| member.key == key) else {
+            return Ok(false);
+        };
+
+        let member = &object.members[index];
+        let start = self.removal_start(member.key_start);
+        let end = self.removal_end(member.comma.map_or(member.value.end, |comma| comma + 1));
+
+        self.text.replace_range(start..end, "");
+
+        // The previous member now ends the object and must lose its comma.
+        if index + 1 == object.members.len()
+            && let Some(comma) = index
+                .checked_sub(1)
+                .and_then(|previous| object.members[previous].comma)
+        {
+            self.text.remove(comma);
+        }
+
+        Ok(true)
+    }
+
+    fn line_start(&self, pos: usize) -> usize {
+        self.text[..pos]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1)
+    }
+
+    fn line_indent(&self, pos: usize) -> &str {
+        let start = self.line_start(pos);
+        let line = &self.text[start..pos];
+        &line[..line.len() - line.trim_start().len()]
+    }
+
+    fn indent_unit(&self) -> String {
+        self.text
+            .lines()
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .find(|indent| !indent.is_empty())
+            .unwrap_or("  ")
+            .to_string()
+    }
+
+    /// Widens a removal starting at `pos` to whole lines, including comment lines above it.
+    fn removal_start(&self, pos: usize) -> usize {
+        let mut start = self.line_start(pos);
+
+        if !self.text[start..pos].trim().is_empty() {
+            return pos;
+        }
+
+        while start > 0 {
+            let previous = self.line_start(start - 1);
+            let line = self.text[previous..start].trim();
+
+            if !(line.starts_with("//") || line.starts_with("/*") || line.starts_with('*')) {
+                break;
+            }
+            start = previous;
+        }
+
+        start
+    }
+
+    /// Widens a removal ending at `pos` to the end of its line when only whitespace or a line
+    /// comment follows.
+    fn removal_end(&self, pos: usize) -> usize {
+        let rest = &self.text[pos..];
+        let line_end = rest.find('\n').map_or(rest.len(), |newline| newline + 1);
+        let remainder = rest[..line_end].trim();
+
+        if remainder.is_empty() || remainder.starts_with("//") {
+            pos + line_end
+        } else {
+            pos
+        }
+    }
+}
+
+/// Indents every line but the first of a pretty-printed value by `indent`.
+fn reindent(value: &str, indent: &str) -> String {
+    value.replace('\n', &format!("\n{}", indent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn edit(text: &str, change: impl FnOnce(&mut JsoncDocument)) -> String {
+        let mut document = JsoncDocument::parse(text.to_string()).unwrap();
+        change(&mut document);
+
+        // Every edit must leave a document that still parses.
+        serde_json::from_str::<Value>(&strip_comments(document.text())).unwrap();
+        document.text().to_string()
+    }
+
+    #[test]
+    fn strip_comments_keeps_comment_markers_inside_strings() {
+        let text = r#"{"url": "http://a/*b*/", // note
+"quote": "\"//\"" /* block */}"#;
+        let value: Value = serde_json::from_str(&strip_comments(text)).unwrap();
+
+        assert_eq!(value, json!({ "url": "http://a/*b*/", "quote": "\"//\"" }));
+    }
+
+    #[test]
+    fn strip_comments_removes_trailing_commas() {
+        let text = "{\"a\": [1, 2, /* last */ ], \"b\": {\"c\": 1,\n// end\n},\n}";
+        let value: Value = serde_json::from_str(&strip_comments(text)).unwrap();
+
+        assert_eq!(value, json!({ "a": [1, 2], "b": { "c": 1 } }));
+    }
+
+    #[test]
+    fn strip_comments_keeps_line_numbers() {
+        let text = "{\n/* one\ntwo */\n\"a\": 1\n}";
+        assert_eq!(strip_comments(text).lines().count(), text.lines().count());
+    }
+
+    #[test]
+    fn set_replaces_an_existing_value_in_place() {
+        let text = "{\n  // servers\n  \"a\": 1, // keep\n  \"b\": 2\n}";
+        let edited = edit(text, |document| {
+            document.set(&[], "a", &json!({ "x": true })).unwrap()
+        });
+
+        assert_eq!(
+            edited,
+            "{\n  // servers\n  \"a\": {\n    \"x\": true\n  }, // keep\n  \"b\": 2\n}"
+        );
+    }
+
+    #[test]
+    fn set_appends_to_an_empty_object() {
+        let text = "{\n    \"servers\": {}\n}";
+        let edited = edit(text, |document| {
+            document.set(&["servers"], "a", &json!(1)).unwrap()
+        });
+
+        assert_eq!(edited, "{\n    \"servers\": {\n        \"a\": 1\n    }\n}");
+    }
+
+    #[test]
+    fn set_appends_after_the_last_member() {
+        let text = "{\n\t\"a\": 1 // last\n}";
+        let edited = edit(text, |document| {
+            document.set(&[], "b", &json!([1, 2])).unwrap()
+        });
+
+        assert_eq!(
+            edited,
+            "{\n\t\"a\": 1, // last\n\t\"b\": [\n\t  1,\n\t  2\n\t]\n}"
+        );
+    }
+
+    #[test]
+    fn set_keeps_trailing_commas() {
+        let text = "{\n  \"a\": 1,\n}";
+        let edited = edit(text, |document| document.set(&[], "b", &json!(2)).unwrap());
+
+        assert_eq!(edited, "{\n  \"a\": 1,\n  \"b\": 2\n}");
+    }
+
+    #[test]
+    fn set_rejects_paths_that_are_not_objects() {
+        let mut document = JsoncDocument::parse("{\"a\": 1}".into()).unwrap();
+
+        assert!(document.set(&["a"], "b", &json!(1)).is_err());
+        assert!(document.set(&["missing"], "b", &json!(1)).is_err());
+    }
+
+    const SERVERS: &str = "{\n  \"a\": 1,\n  // about b\n  \"b\": 2,\n  \"c\": 3\n}";
+
+    #[test]
+    fn remove_first_member() {
+        let edited = edit(SERVERS, |document| {
+            assert!(document.remove(&[], "a").unwrap())
+        });
+        assert_eq!(edited, "{\n  // about b\n  \"b\": 2,\n  \"c\": 3\n}");
+    }
+
+    #[test]
+    fn remove_middle_member_with_the_comment_above_it() {
+        let edited = edit(SERVERS, |document| {
+            assert!(document.remove(&[], "b").unwrap())
+        });
+        assert_eq!(edited, "{\n  \"a\": 1,\n  \"c\": 3\n}");
+    }
+
+    #[test]
+    fn remove_last_member_drops_the_previous_comma() {
+        let edited = edit(SERVERS, |document| {
+            assert!(document.remove(&[], "c").unwrap())
+        });
+        assert_eq!(edited, "{\n  \"a\": 1,\n  // about b\n  \"b\": 2\n}");
+    }
+
+    #[test]
+    fn remove_only_member() {
+        let edited = edit("{\"a\": {\"b\": 1}}", |document| {
+            assert!(document.remove(&["a"], "b").unwrap())
+        });
+        assert_eq!(edited, "{\"a\": {}}");
+    }
+
+    #[test]
+    fn remove_missing_member() {
+        let edited = edit(SERVERS, |document| {
+            assert!(!document.remove(&[], "d").unwrap())
+        });
+        assert_eq!(edited, SERVERS);
+    }
+}
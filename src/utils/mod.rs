@@ -1,9 +1,20 @@
 mod error;
 pub(crate) mod fs;
+pub(crate) mod git;
+pub(crate) mod glob;
+mod hash;
 mod json;
+pub(crate) mod jsonc;
 mod logs;
 pub(crate) mod path;
+mod suggest;
+#[cfg(test)]
+pub(crate) mod testing;
+mod time;
 
 pub(crate) use error::display_error;
+pub(crate) use hash::hash_content;
 pub(crate) use json::merge_json;
 pub(crate) use logs::set_log_config;
+pub(crate) use suggest::did_you_mean;
+pub(crate) use time::utc_now;
@@ -1,9 +1,15 @@
 use anyhow::{Context, Result, anyhow};
+use std::cell::RefCell;
 use std::env;
 use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::constants::dir::ROOT_DIR;
+use crate::constants::dir::{
+    BACKUPS_DIR, CACHE_DIR, COMMANDS_DIR, INSTRUCTIONS_DIR, PACKAGES_DIR, ROOT_DIR,
+};
+use crate::constants::file::{
+    CACHE_CONFIG_FILE, MCP_FILE, PACKAGES_LOCK_FILE, STARTER_ORIGIN_FILE,
+};
 
 fn get_dir_or_die(path: PathBuf) -> Result<PathBuf> {
     if path.is_dir() {
@@ -16,7 +22,33 @@ fn get_dir_or_die(path: PathBuf) -> Result<PathBuf> {
     }
 }
 
+thread_local! {
+    /// Workspace set by [`in_workspace`], used instead of searching from the current directory.
+    static WORKSPACE_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `dir` as the workspace, so library callers can work on any workspace
+/// without changing the current directory of the process.
+pub fn in_workspace<T>(dir: &Path, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<PathBuf>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            WORKSPACE_DIR.with(|workspace| *workspace.borrow_mut() = self.0.take());
+        }
+    }
+
+    let dir = std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let _restore = Restore(WORKSPACE_DIR.with(|workspace| workspace.replace(Some(dir))));
+
+    f()
+}
+
 pub fn get_workspace_dir() -> Result<PathBuf> {
+    if let Some(dir) = WORKSPACE_DIR.with(|workspace| workspace.borrow().clone()) {
+        return Ok(dir);
+    }
+
     let mut current = env::current_dir().context("failed to get current directory")?;
 
     loop {
@@ -53,6 +85,38 @@ pub fn get_application_dir() -> Result<PathBuf> {
 }
 
 pub fn get_commands_dir() -> Result<PathBuf> {
-    let commands_dir = get_application_dir()?.join("commands");
+    let commands_dir = get_application_dir()?.join(COMMANDS_DIR);
     get_dir_or_die(commands_dir)
 }
+
+pub fn get_instructions_dir() -> Result<PathBuf> {
+    Ok(get_application_dir()?.join(INSTRUCTIONS_DIR))
+}
+
+pub fn get_cache_dir() -> Result<PathBuf> {
+    Ok(get_application_dir()?.join(CACHE_DIR))
+}
+
+pub fn get_backups_dir() -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join(BACKUPS_DIR))
+}
+
+pub fn get_cache_file() -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join(CACHE_CONFIG_FILE))
+}
+
+pub fn get_mcp_file() -> Result<PathBuf> {
+    Ok(get_application_dir()?.join(MCP_FILE))
+}
+
+pub fn get_starter_origin_file() -> Result<PathBuf> {
+    Ok(get_application_dir()?.join(STARTER_ORIGIN_FILE))
+}
+
+pub fn get_packages_dir() -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join(PACKAGES_DIR))
+}
+
+pub fn get_packages_lock_file() -> Result<PathBuf> {
+    Ok(get_application_dir()?.join(PACKAGES_LOCK_FILE))
+}
@@ -0,0 +1,14 @@
+const SIMILARITY_THRESHOLD: f64 = 0.7;
+
+/// Returns the candidate closest to `value`, if any is similar enough to be a likely typo.
+pub(crate) fn did_you_mean<'a, I>(value: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::jaro(value, candidate), candidate))
+        .filter(|(score, _)| *score > SIMILARITY_THRESHOLD)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate)
+}
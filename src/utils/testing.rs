@@ -0,0 +1,46 @@
+use std::fs;
+use std::path::PathBuf;
+
+use tempfile::TempDir;
+
+use crate::constants::dir::ROOT_DIR;
+use crate::utils::path::in_workspace;
+
+/// A workspace in a temporary directory, with an empty configuration to start from.
+pub(crate) struct TestWorkspace {
+    dir: TempDir,
+}
+
+impl TestWorkspace {
+    pub fn new() -> Self {
+        let workspace = Self {
+            dir: tempfile::tempdir().unwrap(),
+        };
+
+        workspace.write(&format!("{}/config.toml", ROOT_DIR), "");
+        workspace.write(&format!("{}/local.config.toml", ROOT_DIR), "");
+        workspace
+    }
+
+    /// The workspace directory with its symlinks resolved, as paths are compared resolved.
+    pub fn path(&self) -> PathBuf {
+        fs::canonicalize(self.dir.path()).unwrap()
+    }
+
+    /// Writes `content` to `path`, relative to the workspace, creating its parents.
+    pub fn write(&self, path: &str, content: &str) -> PathBuf {
+        let path = self.dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.dir.path().join(path)).unwrap()
+    }
+
+    /// Runs `f` with this workspace as the current one.
+    pub fn run<T>(&self, f: impl FnOnce() -> T) -> T {
+        in_workspace(&self.path(), f)
+    }
+}
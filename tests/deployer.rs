@@ -0,0 +1,264 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Result;
+use dotagents::{
+    AppConfig, DeployScope, Deployer, OutputStrategy, PlannedOutput, ProviderRenderer, ROOT_DIR,
+    RenderInput, Templater,
+};
+
+fn write(dir: &Path, path: &str, content: &str) {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args([
+            "-c",
+            "user.name=dotagents",
+            "-c",
+            "user.email=dotagents@localhost",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Every file below `dir` with its content.
+fn snapshot(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        match path.is_dir() {
+            true => files.extend(snapshot(&path)),
+            false => files.push((path.clone(), fs::read(&path).unwrap())),
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// A workspace deploying one instruction and one command to copilot, along with the
+/// commands of a git package kept in `library`.
+fn workspace() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let app_dir = dir.path().join(ROOT_DIR);
+    let library = dir.path().join("library");
+
+    write(
+        &library.join(ROOT_DIR),
+        "commands/shared.md",
+        "---\ndescription: Shared by the team\n---\n\nShare.\n",
+    );
+    git(&library, &["init", "--quiet"]);
+    git(&library, &["add", "-A"]);
+    git(&library, &["commit", "--quiet", "-m", "init"]);
+
+    write(
+        &app_dir,
+        "config.toml",
+        &format!(
+            "features = [\"instructions\", \"commands\"]\n\n[targets]\ncli = [\"copilot\"]\n\n\
+             [packages.shared]\ngit = {:?}\n",
+            library.display().to_string()
+        ),
+    );
+    write(&app_dir, "local.config.toml", "");
+    write(
+        &app_dir,
+        "instructions/general.md",
+        "# Instructions for {{ agent_name }}\n",
+    );
+    write(
+        &app_dir,
+        "commands/hello.md",
+        "---\nname: hello\ndescription: Greet the user\n---\n\nSay hello.\n",
+    );
+
+    dir
+}
+
+#[test]
+fn plans_and_applies_a_workspace_outside_the_current_directory() {
+    let dir = workspace();
+    // Temporary directories may sit behind a symlink, e.g. /tmp on macOS.
+    let root = fs::canonicalize(dir.path()).unwrap();
+
+    let templater = Templater::for_workspace(&root).unwrap();
+    let config = AppConfig::from_application(&templater).unwrap();
+    let deployer = Deployer::new(&templater, config).workspace_dir(&root);
+    deployer.sync_packages().unwrap();
+    let files = snapshot(&root);
+
+    let plan = deployer.plan().unwrap();
+    let outputs: Vec<_> = plan.units.iter().flat_map(|unit| &unit.outputs).collect();
+
+    assert!(plan.units.iter().all(|unit| unit.provider == "copilot"));
+    assert!(!outputs.is_empty());
+    assert!(outputs.iter().all(|output| output.path.starts_with(&root)));
+    assert!(
+        outputs
+            .iter()
+            .any(|output| output.content.contains("Instructions for"))
+    );
+    assert!(
+        outputs
+            .iter()
+            .any(|output| output.item.as_deref() == Some("hello"))
+    );
+    assert!(
+        outputs
+            .iter()
+            .any(|output| output.content.contains("Shared by the team"))
+    );
+
+    // Planning writes nothing, packages included.
+    assert!(outputs.iter().all(|output| !output.path.exists()));
+    assert_eq!(snapshot(&root), files);
+
+    deployer.apply(&plan).unwrap();
+
+    for output in &outputs {
+        assert_eq!(fs::read_to_string(&output.path).unwrap(), output.content);
+    }
+    assert!(root.join(ROOT_DIR).join("cache/cache.toml").is_file());
+}
+
+#[test]
+fn asks_for_a_sync_before_planning_unsynced_packages() {
+    let dir = workspace();
+    let root = fs::canonicalize(dir.path()).unwrap();
+    let files = snapshot(&root);
+
+    let templater = Templater::for_workspace(&root).unwrap();
+    let config = AppConfig::from_application(&templater).unwrap();
+    let deployer = Deployer::new(&templater, config).workspace_dir(&root);
+
+    let error = deployer.plan().unwrap_err();
+
+    assert!(format!("{:#}", error).contains("run `dotagents packages sync`"));
+    assert_eq!(snapshot(&root), files);
+}
+
+/// Writes the name of every item to `<provider>/<item>.txt`.
+struct ListRenderer;
+
+impl ProviderRenderer for ListRenderer {
+    fn render(&self, input: &RenderInput) -> Result<Vec<PlannedOutput>> {
+        Ok(input
+            .items
+            .iter()
+            .filter_map(|item| item.id.clone())
+            .map(|id| PlannedOutput {
+                path: input
+                    .workspace_dir
+                    .join(input.provider)
+                    .join(format!("{}.txt", id)),
+                content: format!("{} for {}\n", id, input.feature),
+                item: Some(id),
+                strategy: OutputStrategy::Write,
+            })
+            .collect())
+    }
+}
+
+#[test]
+fn plans_the_outputs_of_registered_renderers() {
+    let dir = workspace();
+    let root = fs::canonicalize(dir.path()).unwrap();
+
+    let templater = Templater::for_workspace(&root).unwrap();
+    let config = AppConfig::from_application(&templater).unwrap();
+    let deployer = Deployer::new(&templater, config)
+        .workspace_dir(&root)
+        .renderer("copilot", ListRenderer)
+        .scope(DeployScope {
+            features: ["commands".to_string()].into_iter().collect(),
+            ..Default::default()
+        });
+    deployer.sync_packages().unwrap();
+
+    let plan = deployer.plan().unwrap();
+    let outputs: Vec<_> = plan
+        .units
+        .iter()
+        .flat_map(|unit| &unit.outputs)
+        .map(|output| (output.path.clone(), output.content.as_str()))
+        .collect();
+
+    assert_eq!(
+        outputs,
+        [
+            (root.join("copilot/hello.txt"), "hello for commands\n"),
+            (root.join("copilot/shared.txt"), "shared for commands\n"),
+        ]
+    );
+}
+
+#[test]
+fn rejects_unknown_providers_in_the_scope() {
+    let dir = workspace();
+    let root = fs::canonicalize(dir.path()).unwrap();
+
+    let templater = Templater::for_workspace(&root).unwrap();
+    let config = AppConfig::from_application(&templater).unwrap();
+    let deployer = Deployer::new(&templater, config)
+        .workspace_dir(&root)
+        .scope(DeployScope {
+            providers: ["windsurf".to_string()].into_iter().collect(),
+            ..Default::default()
+        });
+
+    let error = deployer.plan().unwrap_err();
+
+    assert_eq!(error.to_string(), "validate deploy filters");
+}
+
+#[test]
+fn rolls_back_a_deploy_failing_half_way() {
+    let dir = workspace();
+    let root = fs::canonicalize(dir.path()).unwrap();
+
+    let templater = Templater::for_workspace(&root).unwrap();
+    let config = AppConfig::from_application(&templater).unwrap();
+    let deployer = Deployer::new(&templater, config).workspace_dir(&root);
+    deployer.sync_packages().unwrap();
+    deployer.apply(&deployer.plan().unwrap()).unwrap();
+
+    let cache_file = root.join(ROOT_DIR).join("cache/cache.toml");
+    let cache = fs::read(&cache_file).unwrap();
+    // Not valid UTF-8, so only a byte-for-byte restore passes.
+    fs::write(root.join("NOTES.md"), b"mine \xff\n").unwrap();
+    write(&root, "blocker", "a file where a directory is needed");
+
+    let mut plan = deployer.plan().unwrap();
+    let output = |path: &str| PlannedOutput {
+        item: None,
+        path: root.join(path),
+        content: "deployed".into(),
+        strategy: OutputStrategy::Write,
+    };
+    plan.units[0].outputs.extend([
+        output("NOTES.md"),
+        output(".fresh/nested/rule.md"),
+        output("blocker/rule.md"),
+    ]);
+
+    let error = deployer.apply(&plan).unwrap_err();
+
+    assert_eq!(error.to_string(), "write deployed files");
+    assert_eq!(fs::read(root.join("NOTES.md")).unwrap(), b"mine \xff\n");
+    assert!(!root.join(".fresh").exists());
+    assert_eq!(fs::read(&cache_file).unwrap(), cache);
+
+    // Nothing was lost, so the backup of NOTES.md went with the rollback.
+    let backups = root.join(ROOT_DIR).join("cache/backups");
+    assert!(fs::read_dir(backups).map_or(true, |mut entries| entries.next().is_none()));
+}